@@ -0,0 +1,5 @@
+DROP INDEX payments_refund_state_index;
+
+ALTER TABLE payments DROP COLUMN refund_state;
+
+DROP TYPE RefundState;
@@ -0,0 +1,12 @@
+CREATE TYPE RefundState AS ENUM ('None', 'Partial', 'Full');
+
+ALTER TABLE payments ADD COLUMN refund_state RefundState NOT NULL DEFAULT 'None';
+
+UPDATE payments p
+SET refund_state = CASE WHEN r.refunded_amount >= p.amount THEN 'Full' ELSE 'Partial' END::RefundState
+FROM (
+    SELECT payment_id, SUM(amount) AS refunded_amount FROM refunds GROUP BY payment_id
+) r
+WHERE r.payment_id = p.id;
+
+CREATE INDEX payments_refund_state_index ON payments(refund_state);
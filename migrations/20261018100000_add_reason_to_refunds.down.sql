@@ -0,0 +1,3 @@
+ALTER TABLE refunds DROP COLUMN reason;
+
+DROP TYPE RefundReason;
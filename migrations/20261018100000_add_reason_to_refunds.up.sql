@@ -0,0 +1,4 @@
+CREATE TYPE RefundReason AS ENUM ('Duplicate', 'Fraudulent', 'RequestedByCustomer', 'Other');
+
+ALTER TABLE refunds ADD COLUMN reason RefundReason NOT NULL DEFAULT 'Other';
+ALTER TABLE refunds ALTER COLUMN reason DROP DEFAULT;
@@ -0,0 +1,7 @@
+ALTER TABLE refunds DROP COLUMN reviewed_at;
+ALTER TABLE refunds DROP COLUMN reviewed_by;
+ALTER TABLE refunds DROP COLUMN requested_by;
+
+ALTER TABLE refunds DROP COLUMN status;
+
+DROP TYPE RefundStatus;
@@ -0,0 +1,9 @@
+CREATE TYPE RefundStatus AS ENUM ('AwaitingApproval', 'Succeeded', 'Rejected');
+
+ALTER TABLE refunds ADD COLUMN status RefundStatus NOT NULL DEFAULT 'Succeeded';
+ALTER TABLE refunds ALTER COLUMN status DROP DEFAULT;
+
+-- audit trail of the maker-checker workflow
+ALTER TABLE refunds ADD COLUMN requested_by character varying(255);
+ALTER TABLE refunds ADD COLUMN reviewed_by character varying(255);
+ALTER TABLE refunds ADD COLUMN reviewed_at timestamp;
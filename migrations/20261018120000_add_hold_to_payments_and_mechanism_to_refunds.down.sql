@@ -0,0 +1,8 @@
+ALTER TABLE refunds DROP COLUMN mechanism;
+
+DROP TYPE RefundMechanism;
+
+ALTER TABLE payments DROP COLUMN hold_status;
+ALTER TABLE payments DROP COLUMN hold_id;
+
+DROP TYPE HoldStatus;
@@ -0,0 +1,13 @@
+CREATE TYPE HoldStatus AS ENUM ('Placed', 'Withdrawn', 'Released');
+
+ALTER TABLE payments ADD COLUMN hold_id uuid;
+ALTER TABLE payments ADD COLUMN hold_status HoldStatus;
+
+-- approved payments always had their funds withdrawn right away
+UPDATE payments SET hold_status = 'Withdrawn' WHERE status = 'Approved';
+
+CREATE TYPE RefundMechanism AS ENUM ('ReleaseHold', 'ReducedCapture', 'Credit');
+
+ALTER TABLE refunds ADD COLUMN mechanism RefundMechanism;
+
+UPDATE refunds SET mechanism = 'Credit' WHERE status = 'Succeeded';
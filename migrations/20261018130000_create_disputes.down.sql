@@ -0,0 +1,8 @@
+DROP FUNCTION refundable_amount(uuid);
+
+DROP TABLE merchant_debits;
+
+DROP INDEX disputes_payment_id_index;
+DROP TABLE disputes;
+
+DROP TYPE DisputeStatus;
@@ -0,0 +1,40 @@
+CREATE TYPE DisputeStatus AS ENUM ('Opened', 'EvidenceSubmitted', 'Won', 'Lost');
+
+CREATE TABLE disputes (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    payment_id uuid REFERENCES payments(id) NOT NULL,
+    amount integer NOT NULL,
+    reason character varying(255) NOT NULL,
+    status DisputeStatus NOT NULL,
+    evidence_text text,
+    evidence_attachments text[] NOT NULL DEFAULT '{}',
+    inserted_at timestamp not null default current_timestamp,
+    updated_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX disputes_payment_id_index ON disputes(payment_id);
+
+-- money taken back from merchants, e.g. when they lose a dispute
+CREATE TABLE merchant_debits (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    payment_id uuid REFERENCES payments(id) NOT NULL,
+    dispute_id uuid REFERENCES disputes(id) UNIQUE,
+    amount integer NOT NULL,
+    inserted_at timestamp not null default current_timestamp
+);
+
+-- how much of a payment can still be refunded (or disputed): its amount, minus
+-- its succeeded refunds, minus the disputes that weren't won by the merchant
+CREATE FUNCTION refundable_amount(payment_id uuid) RETURNS integer AS $$
+    SELECT p.amount
+        - COALESCE((
+            SELECT SUM(r.amount) FROM refunds r
+            WHERE r.payment_id = p.id AND r.status = 'Succeeded'
+        ), 0)::integer
+        - COALESCE((
+            SELECT SUM(d.amount) FROM disputes d
+            WHERE d.payment_id = p.id AND d.status <> 'Won'
+        ), 0)::integer
+    FROM payments p
+    WHERE p.id = $1
+$$ LANGUAGE sql STABLE;
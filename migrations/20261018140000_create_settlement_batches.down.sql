@@ -0,0 +1,16 @@
+DROP TRIGGER settlement_lines_immutable ON settlement_lines;
+DROP TRIGGER settlement_batches_immutable ON settlement_batches;
+DROP FUNCTION forbid_settlement_changes();
+
+DROP INDEX settlement_lines_refund_id_index;
+DROP INDEX settlement_lines_payment_id_index;
+DROP INDEX settlement_lines_batch_id_index;
+DROP TABLE settlement_lines;
+
+DROP TYPE SettlementLineKind;
+
+DROP INDEX settlement_batches_merchant_id_index;
+DROP TABLE settlement_batches;
+
+DROP INDEX payments_merchant_id_index;
+ALTER TABLE payments DROP COLUMN merchant_id;
@@ -0,0 +1,46 @@
+ALTER TABLE payments ADD COLUMN merchant_id character varying(255) NOT NULL DEFAULT 'default';
+
+CREATE INDEX payments_merchant_id_index ON payments(merchant_id);
+
+CREATE TABLE settlement_batches (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    merchant_id character varying(255) NOT NULL,
+    cut_off_at timestamp NOT NULL,
+    payments_amount bigint NOT NULL,
+    refunds_amount bigint NOT NULL,
+    net_amount bigint NOT NULL,
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX settlement_batches_merchant_id_index ON settlement_batches(merchant_id);
+
+CREATE TYPE SettlementLineKind AS ENUM ('Payment', 'Refund');
+
+CREATE TABLE settlement_lines (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    batch_id uuid REFERENCES settlement_batches(id) NOT NULL,
+    kind SettlementLineKind NOT NULL,
+    payment_id uuid REFERENCES payments(id) NOT NULL,
+    refund_id uuid REFERENCES refunds(id),
+    amount integer NOT NULL,
+    CHECK ((kind = 'Payment') = (refund_id IS NULL))
+);
+
+CREATE INDEX settlement_lines_batch_id_index ON settlement_lines(batch_id);
+-- each payment and refund is settled exactly once
+CREATE UNIQUE INDEX settlement_lines_payment_id_index ON settlement_lines(payment_id) WHERE kind = 'Payment';
+CREATE UNIQUE INDEX settlement_lines_refund_id_index ON settlement_lines(refund_id);
+
+CREATE FUNCTION forbid_settlement_changes() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'settlement batches are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER settlement_batches_immutable
+    BEFORE UPDATE OR DELETE ON settlement_batches
+    FOR EACH ROW EXECUTE FUNCTION forbid_settlement_changes();
+
+CREATE TRIGGER settlement_lines_immutable
+    BEFORE UPDATE OR DELETE ON settlement_lines
+    FOR EACH ROW EXECUTE FUNCTION forbid_settlement_changes();
@@ -0,0 +1,8 @@
+DROP INDEX reconciliation_discrepancies_hold_id_index;
+DROP INDEX reconciliation_discrepancies_run_id_index;
+DROP TABLE reconciliation_discrepancies;
+
+DROP TABLE reconciliation_runs;
+
+DROP TYPE DiscrepancyKind;
+DROP TYPE LedgerOperation;
@@ -0,0 +1,27 @@
+CREATE TYPE LedgerOperation AS ENUM ('PlaceHold', 'ReleaseHold', 'WithdrawFunds', 'CaptureFunds', 'CreditFunds');
+
+CREATE TYPE DiscrepancyKind AS ENUM ('Missing', 'Extra', 'AmountMismatch');
+
+CREATE TABLE reconciliation_runs (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    source character varying(255) NOT NULL,
+    entries_count integer NOT NULL,
+    discrepancies_count integer NOT NULL,
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE TABLE reconciliation_discrepancies (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    run_id uuid REFERENCES reconciliation_runs(id) NOT NULL,
+    kind DiscrepancyKind NOT NULL,
+    hold_id uuid NOT NULL,
+    operation LedgerOperation NOT NULL,
+    account character varying(255) NOT NULL,
+    payment_id uuid REFERENCES payments(id),
+    expected_amount bigint,
+    actual_amount bigint,
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX reconciliation_discrepancies_run_id_index ON reconciliation_discrepancies(run_id);
+CREATE INDEX reconciliation_discrepancies_hold_id_index ON reconciliation_discrepancies(hold_id);
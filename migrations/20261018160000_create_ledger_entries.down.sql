@@ -0,0 +1,16 @@
+DROP TRIGGER ledger_entries_append_only ON ledger_entries;
+DROP TRIGGER ledger_transactions_append_only ON ledger_transactions;
+DROP FUNCTION forbid_ledger_changes();
+
+DROP TRIGGER ledger_entries_balanced ON ledger_entries;
+DROP FUNCTION check_ledger_transaction_balance();
+
+DROP INDEX ledger_entries_account_index;
+DROP INDEX ledger_entries_transaction_id_index;
+DROP TABLE ledger_entries;
+
+DROP INDEX ledger_transactions_payment_id_index;
+DROP TABLE ledger_transactions;
+
+DROP TYPE LedgerTransactionKind;
+DROP TYPE LedgerAccount;
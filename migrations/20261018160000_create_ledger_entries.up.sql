@@ -0,0 +1,57 @@
+CREATE TYPE LedgerAccount AS ENUM ('CustomerFunds', 'CustomerFundsHeld', 'MerchantReceivable', 'RefundsPayable');
+
+CREATE TYPE LedgerTransactionKind AS ENUM ('HoldPlaced', 'FundsCaptured', 'HoldReleased', 'Refund', 'RefundPayout', 'DisputeLost');
+
+-- money movements are only recorded from this migration on: earlier payments
+-- and refunds have no ledger transactions
+CREATE TABLE ledger_transactions (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    kind LedgerTransactionKind NOT NULL,
+    payment_id uuid REFERENCES payments(id) NOT NULL,
+    refund_id uuid REFERENCES refunds(id),
+    dispute_id uuid REFERENCES disputes(id),
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX ledger_transactions_payment_id_index ON ledger_transactions(payment_id);
+
+CREATE TABLE ledger_entries (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    transaction_id uuid REFERENCES ledger_transactions(id) NOT NULL,
+    account LedgerAccount NOT NULL,
+    -- positive when money goes into the account, negative when it leaves it
+    amount bigint NOT NULL,
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX ledger_entries_transaction_id_index ON ledger_entries(transaction_id);
+CREATE INDEX ledger_entries_account_index ON ledger_entries(account);
+
+-- checked at commit time, once all the entries of the transaction are inserted
+CREATE FUNCTION check_ledger_transaction_balance() RETURNS trigger AS $$
+BEGIN
+    IF (SELECT SUM(amount) FROM ledger_entries WHERE transaction_id = NEW.transaction_id) <> 0 THEN
+        RAISE EXCEPTION 'ledger transaction % does not sum to zero', NEW.transaction_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER ledger_entries_balanced
+    AFTER INSERT ON ledger_entries
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION check_ledger_transaction_balance();
+
+CREATE FUNCTION forbid_ledger_changes() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'the ledger is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER ledger_transactions_append_only
+    BEFORE UPDATE OR DELETE ON ledger_transactions
+    FOR EACH ROW EXECUTE FUNCTION forbid_ledger_changes();
+
+CREATE TRIGGER ledger_entries_append_only
+    BEFORE UPDATE OR DELETE ON ledger_entries
+    FOR EACH ROW EXECUTE FUNCTION forbid_ledger_changes();
@@ -0,0 +1,8 @@
+ALTER TABLE settlement_lines DROP COLUMN fee_amount;
+ALTER TABLE settlement_batches DROP COLUMN fees_amount;
+
+ALTER TABLE refunds DROP COLUMN fee_amount;
+
+ALTER TABLE payments DROP COLUMN fee_amount;
+
+DROP TABLE fee_schedules;
@@ -0,0 +1,18 @@
+CREATE TABLE fee_schedules (
+    merchant_id character varying(255) PRIMARY KEY UNIQUE,
+    percent_bps integer NOT NULL CHECK (percent_bps BETWEEN 0 AND 10000),
+    fixed_amount integer NOT NULL CHECK (fixed_amount >= 0),
+    min_amount integer NOT NULL CHECK (min_amount >= 0),
+    max_amount integer CHECK (max_amount >= min_amount),
+    inserted_at timestamp not null default current_timestamp,
+    updated_at timestamp not null default current_timestamp
+);
+
+-- fee charged to the merchant when the payment was approved
+ALTER TABLE payments ADD COLUMN fee_amount integer NOT NULL DEFAULT 0;
+
+-- part of the payment's fee given back to the merchant
+ALTER TABLE refunds ADD COLUMN fee_amount integer NOT NULL DEFAULT 0;
+
+ALTER TABLE settlement_batches ADD COLUMN fees_amount bigint NOT NULL DEFAULT 0;
+ALTER TABLE settlement_lines ADD COLUMN fee_amount integer NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+DROP INDEX risk_decisions_account_number_index;
+DROP TABLE risk_decisions;
+
+DROP TYPE RiskOutcome;
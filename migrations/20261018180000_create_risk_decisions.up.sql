@@ -0,0 +1,15 @@
+CREATE TYPE RiskOutcome AS ENUM ('Allow', 'Decline', 'Review');
+
+CREATE TABLE risk_decisions (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    payment_id uuid REFERENCES payments(id) NOT NULL UNIQUE,
+    account_number character varying(255) NOT NULL,
+    amount integer NOT NULL,
+    outcome RiskOutcome NOT NULL,
+    -- name of the rule that fired, if any
+    rule character varying(255),
+    reason character varying(255),
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX risk_decisions_account_number_index ON risk_decisions(account_number, inserted_at);
@@ -0,0 +1,26 @@
+CREATE OR REPLACE FUNCTION refundable_amount(payment_id uuid) RETURNS integer AS $$
+    SELECT p.amount
+        - COALESCE((
+            SELECT SUM(r.amount) FROM refunds r
+            WHERE r.payment_id = p.id AND r.status = 'Succeeded'
+        ), 0)::integer
+        - COALESCE((
+            SELECT SUM(d.amount) FROM disputes d
+            WHERE d.payment_id = p.id AND d.status <> 'Won'
+        ), 0)::integer
+    FROM payments p
+    WHERE p.id = $1
+$$ LANGUAGE sql STABLE;
+
+DROP INDEX payments_review_deadline_at_index;
+
+ALTER TABLE payments DROP COLUMN reviewed_at;
+ALTER TABLE payments DROP COLUMN reviewed_by;
+ALTER TABLE payments DROP COLUMN review_deadline_at;
+
+-- enum values can't be dropped: recreate the type without 'RequiresReview'
+UPDATE payments SET status = 'Declined' WHERE status = 'RequiresReview';
+ALTER TYPE Status RENAME TO Status_old;
+CREATE TYPE Status AS ENUM ('Processing', 'Approved', 'Declined', 'Failed');
+ALTER TABLE payments ALTER COLUMN status TYPE Status USING status::text::Status;
+DROP TYPE Status_old;
@@ -0,0 +1,25 @@
+ALTER TYPE Status ADD VALUE 'RequiresReview' AFTER 'Processing';
+
+ALTER TABLE payments ADD COLUMN review_deadline_at timestamp;
+ALTER TABLE payments ADD COLUMN reviewed_by character varying(255);
+ALTER TABLE payments ADD COLUMN reviewed_at timestamp;
+
+CREATE INDEX payments_review_deadline_at_index ON payments(review_deadline_at);
+
+-- payments awaiting review hold the customer's funds, but the merchant isn't
+-- paid yet: nothing can be refunded or disputed until they're approved
+CREATE OR REPLACE FUNCTION refundable_amount(payment_id uuid) RETURNS integer AS $$
+    SELECT CASE WHEN p.status = 'Approved' THEN
+        p.amount
+        - COALESCE((
+            SELECT SUM(r.amount) FROM refunds r
+            WHERE r.payment_id = p.id AND r.status = 'Succeeded'
+        ), 0)::integer
+        - COALESCE((
+            SELECT SUM(d.amount) FROM disputes d
+            WHERE d.payment_id = p.id AND d.status <> 'Won'
+        ), 0)::integer
+    ELSE 0 END
+    FROM payments p
+    WHERE p.id = $1
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,2 @@
+DROP INDEX account_spendings_account_number_index;
+DROP TABLE account_spendings;
@@ -0,0 +1,8 @@
+CREATE TABLE account_spendings (
+    payment_id uuid REFERENCES payments(id) NOT NULL PRIMARY KEY,
+    account_number character varying(255) NOT NULL,
+    amount integer NOT NULL,
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX account_spendings_account_number_index ON account_spendings(account_number, inserted_at);
@@ -0,0 +1,7 @@
+DROP INDEX risk_decisions_payment_id_index;
+ALTER TABLE risk_decisions ADD CONSTRAINT risk_decisions_payment_id_key UNIQUE (payment_id);
+
+DROP TRIGGER payments_record_try ON payments;
+DROP FUNCTION record_payment_try();
+
+DROP TABLE payment_tries;
@@ -0,0 +1,44 @@
+-- a card stays single-use, but a payment that was declined or failed before
+-- any hold was placed can be tried again with the same card: every try is
+-- recorded under the same payment
+CREATE TABLE payment_tries (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    payment_id uuid REFERENCES payments(id) NOT NULL,
+    number integer NOT NULL,
+    amount integer NOT NULL,
+    status Status NOT NULL,
+    hold_id uuid,
+    inserted_at timestamp not null default current_timestamp,
+    updated_at timestamp not null default current_timestamp,
+    UNIQUE (payment_id, number)
+);
+
+INSERT INTO payment_tries ( payment_id, number, amount, status, hold_id, inserted_at, updated_at )
+SELECT id, 1, amount, status, hold_id, inserted_at, updated_at FROM payments;
+
+-- the latest try of a payment follows the payment, and a new try starts
+-- whenever a declined or failed payment is processed again
+CREATE FUNCTION record_payment_try() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR (NEW.status = 'Processing' AND OLD.status IN ('Declined', 'Failed')) THEN
+        INSERT INTO payment_tries ( payment_id, number, amount, status, hold_id )
+        SELECT NEW.id, COALESCE(MAX(number), 0) + 1, NEW.amount, NEW.status, NEW.hold_id
+        FROM payment_tries
+        WHERE payment_id = NEW.id;
+    ELSE
+        UPDATE payment_tries
+        SET status = NEW.status, hold_id = NEW.hold_id, updated_at = current_timestamp
+        WHERE payment_id = NEW.id
+          AND number = (SELECT MAX(number) FROM payment_tries WHERE payment_id = NEW.id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER payments_record_try
+    AFTER INSERT OR UPDATE OF status, hold_id ON payments
+    FOR EACH ROW EXECUTE FUNCTION record_payment_try();
+
+-- risk rules and spend limits are checked again on every try
+ALTER TABLE risk_decisions DROP CONSTRAINT risk_decisions_payment_id_key;
+CREATE INDEX risk_decisions_payment_id_index ON risk_decisions(payment_id);
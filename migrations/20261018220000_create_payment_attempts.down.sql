@@ -0,0 +1,2 @@
+DROP INDEX payment_attempts_payment_id_index;
+DROP TABLE payment_attempts;
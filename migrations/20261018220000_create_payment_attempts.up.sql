@@ -0,0 +1,18 @@
+-- every call made to the account service on behalf of a payment
+CREATE TABLE payment_attempts (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    payment_id uuid REFERENCES payments(id) NOT NULL,
+    -- no foreign key: refunds are rolled back when the account service fails
+    -- to refund them, but their attempts are kept
+    refund_id uuid,
+    operation LedgerOperation NOT NULL,
+    amount integer,
+    hold_id uuid,
+    succeeded boolean NOT NULL,
+    -- the value returned by the account service, or its error
+    result text NOT NULL,
+    latency_us bigint NOT NULL,
+    inserted_at timestamp not null default clock_timestamp()
+);
+
+CREATE INDEX payment_attempts_payment_id_index ON payment_attempts(payment_id, inserted_at);
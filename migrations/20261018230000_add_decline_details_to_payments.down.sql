@@ -0,0 +1,5 @@
+ALTER TABLE payments DROP COLUMN failure_reason;
+ALTER TABLE payments DROP COLUMN decline_code;
+
+DROP TYPE FailureReason;
+DROP TYPE DeclineCode;
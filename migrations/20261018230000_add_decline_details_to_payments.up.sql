@@ -0,0 +1,7 @@
+-- why payments didn't go through: declined payments have a decline code, failed
+-- ones a failure reason
+CREATE TYPE DeclineCode AS ENUM ('InsufficientFunds', 'InvalidAccountNumber', 'RiskDeclined', 'SpendLimitExceeded', 'ReviewRejected', 'ReviewExpired');
+CREATE TYPE FailureReason AS ENUM ('InvalidAmount', 'ServiceUnavailable', 'AccountServiceError');
+
+ALTER TABLE payments ADD COLUMN decline_code DeclineCode;
+ALTER TABLE payments ADD COLUMN failure_reason FailureReason;
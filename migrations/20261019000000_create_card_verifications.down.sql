@@ -0,0 +1,3 @@
+DROP TABLE card_verifications;
+
+DROP TYPE VerificationStatus;
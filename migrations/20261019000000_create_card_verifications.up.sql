@@ -0,0 +1,17 @@
+CREATE TYPE VerificationStatus AS ENUM ('Verified', 'Declined');
+
+-- zero-amount payments only check the card: a hold is placed and released
+-- right away, nothing is charged
+CREATE TABLE card_verifications (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    card_number character varying(255) NOT NULL,
+    merchant_id character varying(255) NOT NULL,
+    status VerificationStatus NOT NULL,
+    decline_code DeclineCode,
+    hold_id uuid,
+    hold_status HoldStatus,
+    inserted_at timestamp not null default current_timestamp
+);
+
+CREATE INDEX card_verifications_card_number_index ON card_verifications(card_number);
+CREATE INDEX card_verifications_hold_id_index ON card_verifications(hold_id);
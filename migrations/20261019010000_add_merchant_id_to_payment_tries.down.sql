@@ -0,0 +1,18 @@
+CREATE OR REPLACE FUNCTION record_payment_try() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR (NEW.status = 'Processing' AND OLD.status IN ('Declined', 'Failed')) THEN
+        INSERT INTO payment_tries ( payment_id, number, amount, status, hold_id )
+        SELECT NEW.id, COALESCE(MAX(number), 0) + 1, NEW.amount, NEW.status, NEW.hold_id
+        FROM payment_tries
+        WHERE payment_id = NEW.id;
+    ELSE
+        UPDATE payment_tries
+        SET status = NEW.status, hold_id = NEW.hold_id, updated_at = current_timestamp
+        WHERE payment_id = NEW.id
+          AND number = (SELECT MAX(number) FROM payment_tries WHERE payment_id = NEW.id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE payment_tries DROP COLUMN merchant_id;
@@ -0,0 +1,22 @@
+-- a payment is only tried again by the merchant who made it, but every try
+-- records its merchant all the same
+ALTER TABLE payment_tries ADD COLUMN merchant_id character varying(255);
+UPDATE payment_tries t SET merchant_id = p.merchant_id FROM payments p WHERE p.id = t.payment_id;
+ALTER TABLE payment_tries ALTER COLUMN merchant_id SET NOT NULL;
+
+CREATE OR REPLACE FUNCTION record_payment_try() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR (NEW.status = 'Processing' AND OLD.status IN ('Declined', 'Failed')) THEN
+        INSERT INTO payment_tries ( payment_id, number, amount, merchant_id, status, hold_id )
+        SELECT NEW.id, COALESCE(MAX(number), 0) + 1, NEW.amount, NEW.merchant_id, NEW.status, NEW.hold_id
+        FROM payment_tries
+        WHERE payment_id = NEW.id;
+    ELSE
+        UPDATE payment_tries
+        SET status = NEW.status, hold_id = NEW.hold_id, updated_at = current_timestamp
+        WHERE payment_id = NEW.id
+          AND number = (SELECT MAX(number) FROM payment_tries WHERE payment_id = NEW.id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,9 @@
+-- enum values can't be dropped: recreate the type without 'Pending'
+DELETE FROM refunds WHERE status = 'Pending' AND reviewed_by IS NULL;
+UPDATE refunds
+SET status = 'AwaitingApproval', mechanism = NULL, reviewed_by = NULL, reviewed_at = NULL
+WHERE status = 'Pending';
+ALTER TYPE RefundStatus RENAME TO RefundStatus_old;
+CREATE TYPE RefundStatus AS ENUM ('AwaitingApproval', 'Succeeded', 'Rejected');
+ALTER TABLE refunds ALTER COLUMN status TYPE RefundStatus USING status::text::RefundStatus;
+DROP TYPE RefundStatus_old;
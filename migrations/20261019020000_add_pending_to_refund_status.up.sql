@@ -0,0 +1,2 @@
+-- refunds whose money the account service is still giving back
+ALTER TYPE RefundStatus ADD VALUE 'Pending' BEFORE 'Succeeded';
@@ -0,0 +1,15 @@
+CREATE OR REPLACE FUNCTION refundable_amount(payment_id uuid) RETURNS integer AS $$
+    SELECT CASE WHEN p.status = 'Approved' THEN
+        p.amount
+        - COALESCE((
+            SELECT SUM(r.amount) FROM refunds r
+            WHERE r.payment_id = p.id AND r.status = 'Succeeded'
+        ), 0)::integer
+        - COALESCE((
+            SELECT SUM(d.amount) FROM disputes d
+            WHERE d.payment_id = p.id AND d.status <> 'Won'
+        ), 0)::integer
+    ELSE 0 END
+    FROM payments p
+    WHERE p.id = $1
+$$ LANGUAGE sql STABLE;
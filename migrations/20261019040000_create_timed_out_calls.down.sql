@@ -0,0 +1,8 @@
+DROP TABLE timed_out_calls;
+
+-- enum values can't be dropped: recreate the type without 'Timeout'
+UPDATE payments SET failure_reason = 'AccountServiceError' WHERE failure_reason = 'Timeout';
+ALTER TYPE FailureReason RENAME TO FailureReason_old;
+CREATE TYPE FailureReason AS ENUM ('InvalidAmount', 'ServiceUnavailable', 'AccountServiceError');
+ALTER TABLE payments ALTER COLUMN failure_reason TYPE FailureReason USING failure_reason::text::FailureReason;
+DROP TYPE FailureReason_old;
@@ -0,0 +1,21 @@
+-- account service calls which timed out: they may have gone through all the
+-- same, so what they were made for stays pending until they're resolved
+-- against a ledger export
+CREATE TABLE timed_out_calls (
+    id uuid default uuid_generate_v4() PRIMARY KEY UNIQUE,
+    payment_id uuid REFERENCES payments(id) NOT NULL,
+    -- no foreign key, like payment_attempts: abandoned refunds are deleted
+    refund_id uuid,
+    operation LedgerOperation NOT NULL,
+    amount integer,
+    hold_id uuid,
+    went_through boolean,
+    inserted_at timestamp not null default clock_timestamp(),
+    resolved_at timestamp
+);
+
+CREATE INDEX timed_out_calls_unresolved_index ON timed_out_calls(payment_id)
+    WHERE resolved_at IS NULL;
+
+-- payments given up because their hold placement timed out
+ALTER TYPE FailureReason ADD VALUE 'Timeout';
@@ -0,0 +1,11 @@
+DELETE FROM timed_out_calls WHERE verification_id IS NOT NULL;
+ALTER TABLE timed_out_calls DROP CONSTRAINT timed_out_calls_subject_check;
+ALTER TABLE timed_out_calls DROP COLUMN verification_id;
+ALTER TABLE timed_out_calls ALTER COLUMN payment_id SET NOT NULL;
+
+-- enum values can't be dropped: recreate the type without 'Processing' and 'Failed'
+DELETE FROM card_verifications WHERE status IN ('Processing', 'Failed');
+ALTER TYPE VerificationStatus RENAME TO VerificationStatus_old;
+CREATE TYPE VerificationStatus AS ENUM ('Verified', 'Declined');
+ALTER TABLE card_verifications ALTER COLUMN status TYPE VerificationStatus USING status::text::VerificationStatus;
+DROP TYPE VerificationStatus_old;
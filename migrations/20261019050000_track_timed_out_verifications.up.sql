@@ -0,0 +1,10 @@
+-- verifications whose hold placement timed out stay processing until the call
+-- is resolved, and fail if the hold wasn't placed after all
+ALTER TYPE VerificationStatus ADD VALUE 'Processing' BEFORE 'Verified';
+ALTER TYPE VerificationStatus ADD VALUE 'Failed';
+
+-- timed-out calls are made for a payment, or for a card verification
+ALTER TABLE timed_out_calls ALTER COLUMN payment_id DROP NOT NULL;
+ALTER TABLE timed_out_calls ADD COLUMN verification_id uuid REFERENCES card_verifications(id);
+ALTER TABLE timed_out_calls ADD CONSTRAINT timed_out_calls_subject_check
+    CHECK ( (payment_id IS NULL) <> (verification_id IS NULL) );
@@ -1,4 +1,15 @@
 pub mod accounts;
+pub mod attempts;
+pub mod disputes;
+pub mod fees;
+pub mod ledger;
 pub mod payment_instruments;
 pub mod payments;
+pub mod reconciliation;
 pub mod refunds;
+pub mod reviews;
+pub mod risk;
+pub mod settlements;
+pub mod spending;
+pub mod timeouts;
+pub mod verifications;
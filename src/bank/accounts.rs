@@ -1,3 +1,5 @@
+use std::{future::Future, time::Duration};
+
 use uuid::Uuid;
 
 /// Represents a hold on a bank customer's funds within their account.
@@ -9,10 +11,20 @@ use uuid::Uuid;
 /// reference contains this information.
 #[derive(Debug, Clone, Copy)]
 pub struct HoldRef {
-    #[allow(dead_code)]
     id: Uuid,
 }
 
+impl HoldRef {
+    /// Rebuilds a hold reference from the id persisted with a payment.
+    pub fn new(id: Uuid) -> Self {
+        Self { id }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
 /// Client to interact with a remote service that manages customer accounts.
 #[async_trait::async_trait]
 pub trait AccountService: Clone + Send + Sync + 'static {
@@ -51,6 +63,30 @@ pub trait AccountService: Clone + Send + Sync + 'static {
     /// This is the mechanism by which money is transferred out from the customer's account and
     /// into the merchant's account during the settlement process.
     async fn withdraw_funds(&self, hold_ref: HoldRef) -> Result<(), String>;
+
+    /// Withdraws part of the held money from the account.
+    ///
+    /// Decreases the current balance of the account linked to the hold reference by `amount`,
+    /// which must not exceed the amount previously held. The rest of the hold is implicitly
+    /// released atomically.
+    ///
+    /// This is used instead of `withdraw_funds` when the merchant is owed less than what was
+    /// held (e.g. the payment was partially refunded before its funds were withdrawn).
+    async fn capture_funds(&self, hold_ref: HoldRef, amount: i32) -> Result<(), String>;
+
+    /// Credits money to the account.
+    ///
+    /// Increases the `account_number` account's actual and current balances by `amount`.
+    ///
+    /// This is how payments are refunded once their funds have been withdrawn.
+    async fn credit_funds(&self, account_number: &str, amount: i32) -> Result<(), String>;
+
+    /// Checks that the service is reachable, for readiness probes.
+    ///
+    /// Services which can't be probed are assumed reachable.
+    async fn health(&self) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 /// A naive implementation of the `Bank.Accounts.Service` behavior.
@@ -108,4 +144,107 @@ impl AccountService for DummyService {
         let _ = hold_ref;
         Ok(())
     }
+
+    async fn capture_funds(&self, hold_ref: HoldRef, amount: i32) -> Result<(), String> {
+        let _ = hold_ref;
+        if amount < Self::MIN_VALID_AMOUNT {
+            Err("invalid_amount".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Credits money to the account.
+    ///
+    /// - If the `account_number` is `DummyService::INVALID_ACCOUNT_NUMBER`, returns `invalid_account_number`.
+    /// - If the `amount` is negative, returns `invalid_amount`.
+    async fn credit_funds(&self, account_number: &str, amount: i32) -> Result<(), String> {
+        if account_number == Self::INVALID_ACCOUNT_NUMBER {
+            Err("invalid_account_number".into())
+        } else if amount < Self::MIN_VALID_AMOUNT {
+            Err("invalid_amount".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn health(&self) -> Result<(), String> {
+        #[cfg(test)]
+        if let Some(response) = &self.response {
+            return Err(response.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Error of the calls which took too long, see `Timeout`.
+pub const TIMEOUT: &str = "timeout";
+
+/// Fails the calls to an account service which take longer than `timeout`,
+/// with `TIMEOUT`.
+///
+/// A call which timed out may still go through: unlike the service's own
+/// errors, it mustn't be taken as a refusal.
+#[derive(Clone)]
+pub struct Timeout<T> {
+    inner: T,
+    timeout: Duration,
+}
+
+impl<T: AccountService> Timeout<T> {
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    async fn call<R>(&self, future: impl Future<Output = Result<R, String>>) -> Result<R, String> {
+        tokio::time::timeout(self.timeout, future)
+            .await
+            .unwrap_or_else(|_| Err(TIMEOUT.into()))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: AccountService> AccountService for Timeout<T> {
+    async fn place_hold(&self, account_number: &str, amount: i32) -> Result<HoldRef, String> {
+        self.call(self.inner.place_hold(account_number, amount))
+            .await
+    }
+
+    async fn release_hold(&self, hold_ref: HoldRef) -> Result<(), String> {
+        self.call(self.inner.release_hold(hold_ref)).await
+    }
+
+    async fn withdraw_funds(&self, hold_ref: HoldRef) -> Result<(), String> {
+        self.call(self.inner.withdraw_funds(hold_ref)).await
+    }
+
+    async fn capture_funds(&self, hold_ref: HoldRef, amount: i32) -> Result<(), String> {
+        self.call(self.inner.capture_funds(hold_ref, amount)).await
+    }
+
+    async fn credit_funds(&self, account_number: &str, amount: i32) -> Result<(), String> {
+        self.call(self.inner.credit_funds(account_number, amount))
+            .await
+    }
+
+    async fn health(&self) -> Result<(), String> {
+        self.call(self.inner.health()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let service = Timeout::new(DummyService::default(), Duration::from_millis(10));
+        assert!(service.place_hold("01", 100).await.is_ok());
+
+        let result = service
+            .call(std::future::pending::<Result<(), String>>())
+            .await;
+        assert_eq!(result, Err(TIMEOUT.to_string()));
+    }
 }
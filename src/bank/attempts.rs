@@ -0,0 +1,240 @@
+use std::{fmt::Debug, future::Future};
+
+use sqlx::PgPool;
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::bank::{
+    accounts::{self, HoldRef},
+    reconciliation::Operation,
+};
+use crate::metrics;
+
+/// A call made to the account service on behalf of a payment.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Attempt {
+    pub id: Uuid,
+    pub payment_id: Uuid,
+    /// The refund the call was made for, if any.
+    pub refund_id: Option<Uuid>,
+    pub operation: Operation,
+    /// Amount sent to the account service, if the operation takes one.
+    pub amount: Option<i32>,
+    pub hold_id: Option<Uuid>,
+    pub succeeded: bool,
+    /// The value returned by the account service, or its error.
+    pub result: String,
+    pub latency_us: i64,
+    pub inserted_at: PrimitiveDateTime,
+}
+
+/// Who (and what for) an account service call is made.
+#[derive(Debug, Clone, Copy)]
+pub struct Call {
+    pub payment_id: Uuid,
+    pub refund_id: Option<Uuid>,
+    pub operation: Operation,
+    pub amount: Option<i32>,
+    pub hold_id: Option<Uuid>,
+}
+
+impl Call {
+    pub fn new(payment_id: Uuid, operation: Operation) -> Self {
+        Self {
+            payment_id,
+            refund_id: None,
+            operation,
+            amount: None,
+            hold_id: None,
+        }
+    }
+
+    pub fn with_amount(mut self, amount: i32) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn with_hold(mut self, hold_ref: HoldRef) -> Self {
+        self.hold_id = Some(hold_ref.id());
+        self
+    }
+
+    pub fn with_refund(mut self, refund_id: Uuid) -> Self {
+        self.refund_id = Some(refund_id);
+        self
+    }
+}
+
+/// Values returned by the account service.
+pub trait Returned: Debug {
+    /// The hold the value refers to, if any.
+    fn hold_id(&self) -> Option<Uuid> {
+        None
+    }
+}
+
+impl Returned for () {}
+
+impl Returned for HoldRef {
+    fn hold_id(&self) -> Option<Uuid> {
+        Some(self.id())
+    }
+}
+
+/// An attempt that was made, but isn't recorded yet.
+#[derive(Debug, Clone)]
+struct Made {
+    call: Call,
+    succeeded: bool,
+    hold_id: Option<Uuid>,
+    result: String,
+    latency_us: i64,
+}
+
+/// Attempts made while holding a transaction, to be recorded once it's over.
+///
+/// Attempts are recorded apart from the transaction making the calls, so that
+/// they're kept if it rolls back, but only after it's over: recording them
+/// meanwhile would take another connection while holding row locks, which
+/// may be awaited by every other connection of the pool.
+#[derive(Debug, Default)]
+pub struct Attempts(Vec<Made>);
+
+impl Attempts {
+    /// Makes an account service call, timing it (see `metrics::account_service_call`).
+    pub async fn make<R: Returned>(
+        &mut self,
+        call: Call,
+        future: impl Future<Output = Result<R, String>>,
+    ) -> Result<R, String> {
+        let (result, latency) = metrics::account_service_call(call.operation, future).await;
+        let latency_us = i64::try_from(latency.as_micros()).unwrap_or(i64::MAX);
+
+        let (succeeded, hold_id, raw_result) = match &result {
+            Ok(value) => (true, value.hold_id().or(call.hold_id), format!("{value:?}")),
+            Err(error) => (false, call.hold_id, error.clone()),
+        };
+        self.0.push(Made {
+            call,
+            succeeded,
+            hold_id,
+            result: raw_result,
+            latency_us,
+        });
+
+        result
+    }
+
+    /// The last call made, if it timed out (see `accounts::TIMEOUT`).
+    pub fn timed_out(&self) -> Option<Call> {
+        self.0
+            .last()
+            .filter(|made| !made.succeeded && made.result == accounts::TIMEOUT)
+            .map(|made| made.call)
+    }
+
+    /// Records the attempts made.
+    ///
+    /// The calls were made whatever happens here, so failing to record them
+    /// is only logged: it mustn't fail the payment or refund they were made for.
+    pub async fn record(self, pool: &PgPool) {
+        for made in self.0 {
+            let recorded = sqlx::query!(
+                r#"
+                    INSERT INTO payment_attempts ( payment_id, refund_id, operation, amount, hold_id, succeeded, result, latency_us )
+                    VALUES ( $1, $2, $3, $4, $5, $6, $7, $8 )
+                "#,
+                made.call.payment_id,
+                made.call.refund_id,
+                made.call.operation as Operation,
+                made.call.amount,
+                made.hold_id,
+                made.succeeded,
+                made.result,
+                made.latency_us
+            )
+            .execute(pool)
+            .await;
+            if let Err(error) = recorded {
+                tracing::error!(
+                    payment_id = %made.call.payment_id,
+                    operation = %made.call.operation,
+                    result = made.result,
+                    %error,
+                    "failed to record account service attempt"
+                );
+            }
+        }
+    }
+}
+
+/// Makes an account service call outside of any transaction, and records it
+/// as an attempt.
+pub async fn record<R: Returned>(
+    pool: &PgPool,
+    call: Call,
+    future: impl Future<Output = Result<R, String>>,
+) -> Result<R, String> {
+    let mut attempts = Attempts::default();
+    let result = attempts.make(call, future).await;
+    attempts.record(pool).await;
+
+    result
+}
+
+/// Lists the attempts of a payment, in the order they were made.
+pub async fn list(pool: &PgPool, payment_id: Uuid) -> Result<Vec<Attempt>, sqlx::Error> {
+    sqlx::query_as!(
+        Attempt,
+        r#"
+            SELECT id, payment_id, refund_id, operation as "operation: _", amount, hold_id, succeeded,
+              result, latency_us, inserted_at
+            FROM payment_attempts
+            WHERE payment_id = $1
+            ORDER BY inserted_at, id
+        "#,
+        payment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::payments::Payment;
+
+    #[tokio::test]
+    async fn test_record() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let payment_id = Payment::new_test(&pool).await.unwrap().id;
+        let hold_ref = HoldRef::new(Uuid::new_v4());
+
+        let call = Call::new(payment_id, Operation::PlaceHold).with_amount(100);
+        let result = record(&pool, call, async { Ok(hold_ref) }).await;
+        assert_eq!(result.unwrap().id(), hold_ref.id());
+
+        let call = Call::new(payment_id, Operation::WithdrawFunds).with_hold(hold_ref);
+        let result = record(&pool, call, async {
+            Err::<(), _>("service_unavailable".to_string())
+        })
+        .await;
+        assert_eq!(result, Err("service_unavailable".to_string()));
+
+        let attempts = list(&pool, payment_id).await.unwrap();
+        let attempts: Vec<_> = attempts
+            .iter()
+            .map(|a| (a.operation, a.amount, a.hold_id, a.succeeded))
+            .collect();
+        assert_eq!(
+            attempts,
+            [
+                (Operation::PlaceHold, Some(100), Some(hold_ref.id()), true),
+                (Operation::WithdrawFunds, None, Some(hold_ref.id()), false),
+            ]
+        );
+    }
+}
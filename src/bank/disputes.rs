@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::bank::ledger;
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "DisputeStatus")]
+#[schema(as = DisputeStatus)]
+pub enum Status {
+    /// The card holder's bank disputed the payment, and waits for the merchant's
+    /// evidence.
+    Opened,
+    /// The merchant submitted evidence, and waits for the dispute to be decided.
+    EvidenceSubmitted,
+    /// The dispute was decided in favor of the merchant: the disputed amount
+    /// can be refunded again.
+    Won,
+    /// The dispute was decided in favor of the card holder: the merchant was
+    /// debited the disputed amount.
+    Lost,
+}
+
+/// How a dispute was decided.
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = DisputeResolution)]
+pub enum Resolution {
+    Won,
+    Lost,
+}
+
+impl From<Resolution> for Status {
+    fn from(resolution: Resolution) -> Self {
+        match resolution {
+            Resolution::Won => Status::Won,
+            Resolution::Lost => Status::Lost,
+        }
+    }
+}
+
+/// Module and schema representing a dispute (a.k.a. chargeback).
+///
+/// A dispute is opened by the account service on behalf of the card holder,
+/// against (part of) an approved payment. Until it's won by the merchant, the
+/// disputed amount can't be refunded anymore, so the card holder can't get
+/// their money back twice. Losing a dispute debits the merchant the disputed
+/// amount, which is recorded in `merchant_debits`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Dispute {
+    pub id: Uuid,
+    pub payment_id: Uuid,
+    pub amount: i32,
+    pub reason: String,
+    pub status: Status,
+    pub evidence_text: Option<String>,
+    /// References to the evidence documents (e.g. URLs), stored elsewhere.
+    pub evidence_attachments: Vec<String>,
+    /// Amount debited to the merchant, once the dispute is lost.
+    pub debited_amount: Option<i32>,
+    #[allow(dead_code)]
+    pub inserted_at: PrimitiveDateTime,
+    #[allow(dead_code)]
+    pub updated_at: PrimitiveDateTime,
+}
+
+/// Opens a dispute against an approved payment, unless the disputed amount
+/// exceeds the payment's refundable amount.
+///
+/// Returns `None` if the dispute couldn't be opened. The payment is locked
+/// while checking its refundable amount, like `refunds::checked_insert` does,
+/// so disputes and refunds can't concurrently exceed the payment amount.
+pub async fn open(
+    pool: &PgPool,
+    payment_id: Uuid,
+    amount: i32,
+    reason: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "SELECT id FROM payments WHERE id = $1 FOR UPDATE",
+        payment_id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let dispute_id = sqlx::query!(
+        r#"
+          INSERT INTO disputes ( payment_id, amount, reason, status )
+          SELECT p.id, $2, $3, 'Opened'
+          FROM payments p
+          WHERE p.id = $1
+            AND p.status = 'Approved'
+            AND refundable_amount(p.id) >= $2::integer
+          RETURNING id
+        "#,
+        payment_id,
+        amount,
+        reason
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .map(|record| record.id);
+
+    tx.commit().await?;
+
+    Ok(dispute_id)
+}
+
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Dispute, sqlx::Error> {
+    sqlx::query_as!(
+        Dispute,
+        r#"
+            SELECT d.id, d.payment_id, d.amount, d.reason, d.status as "status: _",
+              d.evidence_text, d.evidence_attachments, md.amount as "debited_amount?",
+              d.inserted_at, d.updated_at
+            FROM disputes d
+            LEFT JOIN merchant_debits md ON md.dispute_id = d.id
+            WHERE d.id = $1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn list(pool: &PgPool, payment_id: Uuid) -> Result<Vec<Dispute>, sqlx::Error> {
+    sqlx::query_as!(
+        Dispute,
+        r#"
+            SELECT d.id, d.payment_id, d.amount, d.reason, d.status as "status: _",
+              d.evidence_text, d.evidence_attachments, md.amount as "debited_amount?",
+              d.inserted_at, d.updated_at
+            FROM disputes d
+            LEFT JOIN merchant_debits md ON md.dispute_id = d.id
+            WHERE d.payment_id = $1
+            ORDER BY d.inserted_at, d.id
+        "#,
+        payment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Records the merchant's evidence against an undecided dispute, replacing any
+/// evidence submitted before.
+///
+/// Returns `false` if the dispute was already decided.
+pub async fn submit_evidence(
+    pool: &PgPool,
+    id: Uuid,
+    text: &str,
+    attachments: &[String],
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+            UPDATE disputes
+            SET status = 'EvidenceSubmitted', evidence_text = $2, evidence_attachments = $3,
+              updated_at = current_timestamp
+            WHERE id = $1 AND status IN ('Opened', 'EvidenceSubmitted')
+            RETURNING id
+        "#,
+        id,
+        text,
+        attachments
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|record| record.is_some())
+}
+
+/// Decides an undecided dispute, debiting the merchant the disputed amount if
+/// it was lost.
+///
+/// Returns `false` if the dispute was already decided.
+pub async fn resolve(pool: &PgPool, id: Uuid, resolution: Resolution) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let Some(dispute) = sqlx::query!(
+        r#"
+            UPDATE disputes
+            SET status = $2, updated_at = current_timestamp
+            WHERE id = $1 AND status IN ('Opened', 'EvidenceSubmitted')
+            RETURNING payment_id, amount
+        "#,
+        id,
+        Status::from(resolution) as Status
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    else {
+        tx.rollback().await?;
+        return Ok(false);
+    };
+
+    if resolution == Resolution::Lost {
+        sqlx::query!(
+            r#"
+                INSERT INTO merchant_debits ( payment_id, dispute_id, amount )
+                VALUES ( $1, $2, $3 )
+            "#,
+            dispute.payment_id,
+            id,
+            dispute.amount
+        )
+        .execute(&mut tx)
+        .await?;
+
+        ledger::dispute_lost(&mut tx, dispute.payment_id, id, dispute.amount).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::{payments::Payment, refunds};
+
+    pub const DISPUTE_AMOUNT: i32 = 100;
+    pub const DISPUTE_REASON: &str = "goods not received";
+
+    impl Dispute {
+        pub async fn new_test(pool: &PgPool) -> Result<Dispute, sqlx::Error> {
+            let payment = Payment::new_test(pool).await?;
+
+            let id = open(pool, payment.id, DISPUTE_AMOUNT, DISPUTE_REASON)
+                .await?
+                .ok_or(sqlx::Error::RowNotFound)?;
+
+            get(pool, id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispute_lifecycle() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let dispute = Dispute::new_test(&pool)
+            .await
+            .expect("failed to open dispute");
+        assert_eq!(dispute.status, Status::Opened);
+        assert_eq!(dispute.amount, DISPUTE_AMOUNT);
+
+        let attachments = vec!["https://example.com/receipt.pdf".to_string()];
+        let submitted = submit_evidence(&pool, dispute.id, "delivered", &attachments)
+            .await
+            .expect("failed to submit evidence");
+        assert!(submitted);
+        let dispute = get(&pool, dispute.id).await.unwrap();
+        assert_eq!(dispute.status, Status::EvidenceSubmitted);
+        assert_eq!(dispute.evidence_text.as_deref(), Some("delivered"));
+        assert_eq!(dispute.evidence_attachments, attachments);
+
+        assert!(resolve(&pool, dispute.id, Resolution::Lost).await.unwrap());
+        let dispute = get(&pool, dispute.id).await.unwrap();
+        assert_eq!(dispute.status, Status::Lost);
+        assert_eq!(dispute.debited_amount, Some(DISPUTE_AMOUNT));
+
+        // decided disputes are final
+        assert!(!resolve(&pool, dispute.id, Resolution::Won).await.unwrap());
+        assert!(!submit_evidence(&pool, dispute.id, "late", &[])
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_disputed_amount_isnt_refundable() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let dispute = Dispute::new_test(&pool)
+            .await
+            .expect("failed to open dispute");
+        let payment_amount = crate::bank::payments::tests::PAYMENT_AMOUNT;
+
+        let balance = refunds::balance(&pool, dispute.payment_id).await.unwrap();
+        assert_eq!(balance.disputed_amount, DISPUTE_AMOUNT);
+        assert_eq!(balance.refundable_amount, payment_amount - DISPUTE_AMOUNT);
+
+        // the payment can't be disputed beyond its refundable amount either
+        let opened = open(&pool, dispute.payment_id, payment_amount, DISPUTE_REASON)
+            .await
+            .unwrap();
+        assert!(opened.is_none());
+
+        // winning the dispute makes its amount refundable again
+        resolve(&pool, dispute.id, Resolution::Won).await.unwrap();
+        let balance = refunds::balance(&pool, dispute.payment_id).await.unwrap();
+        assert_eq!(balance.disputed_amount, 0);
+        assert_eq!(balance.refundable_amount, payment_amount);
+    }
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Basis points in 100%.
+const BPS: i64 = 10_000;
+
+/// How much a merchant is charged for each approved payment: a percentage of
+/// the payment amount plus a fixed amount, bounded by a minimum and an optional
+/// cap.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    sqlx::FromRow,
+    utoipa::ToSchema,
+)]
+#[schema(as = FeeSchedule)]
+pub struct Schedule {
+    /// Percentage of the payment amount, in basis points (1/100th of a percent).
+    pub percent_bps: i32,
+    pub fixed_amount: i32,
+    pub min_amount: i32,
+    pub max_amount: Option<i32>,
+}
+
+/// Divides, rounding halves up. Both operands must be positive.
+fn div_round(dividend: i64, divisor: i64) -> i64 {
+    (dividend + divisor / 2) / divisor
+}
+
+/// Why a schedule doesn't make sense.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleViolation {
+    PercentOutOfRange,
+    NegativeAmount,
+    CapBelowMinimum,
+}
+
+impl Schedule {
+    /// Checks the schedule makes sense, returning why it doesn't otherwise.
+    pub fn validate(&self) -> Result<(), ScheduleViolation> {
+        if !(0..=BPS as i32).contains(&self.percent_bps) {
+            return Err(ScheduleViolation::PercentOutOfRange);
+        }
+        if self.fixed_amount < 0 || self.min_amount < 0 {
+            return Err(ScheduleViolation::NegativeAmount);
+        }
+        if self
+            .max_amount
+            .is_some_and(|max_amount| max_amount < self.min_amount)
+        {
+            return Err(ScheduleViolation::CapBelowMinimum);
+        }
+        Ok(())
+    }
+
+    /// Computes the fee of a payment of `amount`.
+    ///
+    /// The percentage is rounded to the nearest unit (halves up) before adding
+    /// the fixed amount and applying the minimum and cap. The fee never exceeds
+    /// the payment amount.
+    pub fn fee(&self, amount: i32) -> i32 {
+        let amount = i64::from(amount);
+        let mut fee =
+            div_round(amount * i64::from(self.percent_bps), BPS) + i64::from(self.fixed_amount);
+        fee = fee.max(i64::from(self.min_amount));
+        if let Some(max_amount) = self.max_amount {
+            fee = fee.min(i64::from(max_amount));
+        }
+        fee.min(amount) as i32
+    }
+}
+
+/// Computes the part of a payment's `fee` given back to the merchant for a
+/// refund of `refund_amount`, `refunded_amount` having already been refunded.
+///
+/// The fee is reversed in proportion to the refunded amount. Rounding applies
+/// to the total reversed so far rather than to each refund, so a fully
+/// refunded payment always has its whole fee reversed.
+pub fn reversal(fee: i32, payment_amount: i32, refunded_amount: i32, refund_amount: i32) -> i32 {
+    let reversed = |refunded_amount: i32| {
+        div_round(
+            i64::from(fee) * i64::from(refunded_amount.min(payment_amount)),
+            i64::from(payment_amount),
+        )
+    };
+
+    (reversed(refunded_amount + refund_amount) - reversed(refunded_amount)) as i32
+}
+
+pub async fn get(
+    executor: impl sqlx::PgExecutor<'_>,
+    merchant_id: &str,
+) -> Result<Option<Schedule>, sqlx::Error> {
+    sqlx::query_as!(
+        Schedule,
+        r#"
+            SELECT percent_bps, fixed_amount, min_amount, max_amount
+            FROM fee_schedules
+            WHERE merchant_id = $1
+        "#,
+        merchant_id
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Sets the fee schedule of a merchant. It only applies to payments approved
+/// from now on.
+pub async fn put(pool: &PgPool, merchant_id: &str, schedule: &Schedule) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO fee_schedules ( merchant_id, percent_bps, fixed_amount, min_amount, max_amount )
+            VALUES ( $1, $2, $3, $4, $5 )
+            ON CONFLICT ( merchant_id ) DO UPDATE
+            SET percent_bps = $2, fixed_amount = $3, min_amount = $4, max_amount = $5,
+              updated_at = current_timestamp
+        "#,
+        merchant_id,
+        schedule.percent_bps,
+        schedule.fixed_amount,
+        schedule.min_amount,
+        schedule.max_amount
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_fee() {
+        // 2.9% + 30
+        let schedule = Schedule {
+            percent_bps: 290,
+            fixed_amount: 30,
+            ..Schedule::default()
+        };
+        assert_eq!(schedule.fee(1000), 59);
+        // 2.9% of 1050 is 30.45, rounded down
+        assert_eq!(schedule.fee(1050), 60);
+        // 2.9% of 50 is 1.45, rounded down; 2.9% of 1500 is 43.5, rounded up
+        assert_eq!(schedule.fee(50), 31);
+        assert_eq!(schedule.fee(1500), 74);
+        // never more than the payment
+        assert_eq!(schedule.fee(20), 20);
+
+        let schedule = Schedule {
+            percent_bps: 100,
+            fixed_amount: 0,
+            min_amount: 5,
+            max_amount: Some(50),
+        };
+        assert_eq!(schedule.fee(100), 5);
+        assert_eq!(schedule.fee(2000), 20);
+        assert_eq!(schedule.fee(100_000), 50);
+
+        assert_eq!(Schedule::default().fee(1000), 0);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Schedule::default().validate().is_ok());
+
+        let schedule = Schedule {
+            percent_bps: 10_001,
+            ..Schedule::default()
+        };
+        assert_eq!(
+            schedule.validate(),
+            Err(ScheduleViolation::PercentOutOfRange)
+        );
+
+        let schedule = Schedule {
+            min_amount: 10,
+            max_amount: Some(5),
+            ..Schedule::default()
+        };
+        assert_eq!(schedule.validate(), Err(ScheduleViolation::CapBelowMinimum));
+    }
+
+    #[test]
+    fn test_reversal() {
+        // 1/3 of a fee of 10 is 3.33, rounded down
+        assert_eq!(reversal(10, 300, 0, 100), 3);
+        // 2/3 is 6.67, rounded up: 3 more
+        assert_eq!(reversal(10, 300, 100, 100), 4);
+        // the whole fee is reversed by the last refund
+        assert_eq!(reversal(10, 300, 200, 100), 3);
+        assert_eq!(reversal(10, 300, 0, 300), 10);
+
+        // half of 5 is 2.5, rounded up
+        assert_eq!(reversal(5, 100, 0, 50), 3);
+        assert_eq!(reversal(5, 100, 50, 50), 2);
+
+        assert_eq!(reversal(0, 100, 0, 100), 0);
+    }
+}
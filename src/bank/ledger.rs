@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Ledger accounts money moves between.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "LedgerAccount")]
+#[schema(as = LedgerAccount)]
+pub enum Account {
+    /// Customers' money, freely available on their account.
+    CustomerFunds,
+    /// Customers' money held for payments, not withdrawn yet.
+    CustomerFundsHeld,
+    /// Money withdrawn for payments, owed to merchants.
+    MerchantReceivable,
+    /// Refunds owed to customers, not credited to their account yet.
+    RefundsPayable,
+}
+
+impl Account {
+    pub const ALL: [Account; 4] = [
+        Account::CustomerFunds,
+        Account::CustomerFundsHeld,
+        Account::MerchantReceivable,
+        Account::RefundsPayable,
+    ];
+}
+
+/// The money movement a ledger transaction records.
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "LedgerTransactionKind")]
+pub enum TransactionKind {
+    HoldPlaced,
+    FundsCaptured,
+    HoldReleased,
+    Refund,
+    RefundPayout,
+    DisputeLost,
+}
+
+/// What a ledger transaction relates to.
+#[derive(Debug, Clone, Copy)]
+struct Subject {
+    payment_id: Uuid,
+    refund_id: Option<Uuid>,
+    dispute_id: Option<Uuid>,
+}
+
+impl Subject {
+    fn payment(payment_id: Uuid) -> Self {
+        Self {
+            payment_id,
+            refund_id: None,
+            dispute_id: None,
+        }
+    }
+
+    fn refund(payment_id: Uuid, refund_id: Option<Uuid>) -> Self {
+        Self {
+            refund_id,
+            ..Self::payment(payment_id)
+        }
+    }
+}
+
+/// Records a transaction made of `entries`, each moving an amount into (when
+/// positive) or out of (when negative) an account.
+///
+/// Entries must sum to zero: the database refuses to commit unbalanced
+/// transactions.
+async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: TransactionKind,
+    subject: Subject,
+    entries: &[(Account, i64)],
+) -> Result<Uuid, sqlx::Error> {
+    debug_assert_eq!(entries.iter().map(|(_, amount)| amount).sum::<i64>(), 0);
+
+    let transaction_id = sqlx::query!(
+        r#"
+            INSERT INTO ledger_transactions ( kind, payment_id, refund_id, dispute_id )
+            VALUES ( $1, $2, $3, $4 )
+            RETURNING id
+        "#,
+        kind as TransactionKind,
+        subject.payment_id,
+        subject.refund_id,
+        subject.dispute_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    for &(account, amount) in entries {
+        sqlx::query!(
+            r#"
+                INSERT INTO ledger_entries ( transaction_id, account, amount )
+                VALUES ( $1, $2, $3 )
+            "#,
+            transaction_id,
+            account as Account,
+            amount
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(transaction_id)
+}
+
+/// Records a hold placed on the customer's funds for a payment.
+pub async fn hold_placed(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+    amount: i32,
+) -> Result<(), sqlx::Error> {
+    let amount = i64::from(amount);
+    record(
+        tx,
+        TransactionKind::HoldPlaced,
+        Subject::payment(payment_id),
+        &[
+            (Account::CustomerFunds, -amount),
+            (Account::CustomerFundsHeld, amount),
+        ],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Records the capture of `captured_amount` out of the `held_amount` held for a
+/// payment, on behalf of the refund `refund_id` if any. Whatever wasn't
+/// captured goes back to the customer.
+pub async fn funds_captured(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+    refund_id: Option<Uuid>,
+    held_amount: i32,
+    captured_amount: i32,
+) -> Result<(), sqlx::Error> {
+    let (held_amount, captured_amount) = (i64::from(held_amount), i64::from(captured_amount));
+    let mut entries = vec![
+        (Account::CustomerFundsHeld, -held_amount),
+        (Account::MerchantReceivable, captured_amount),
+    ];
+    if captured_amount < held_amount {
+        entries.push((Account::CustomerFunds, held_amount - captured_amount));
+    }
+
+    record(
+        tx,
+        TransactionKind::FundsCaptured,
+        Subject::refund(payment_id, refund_id),
+        &entries,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Records the release of the hold placed for a payment (a.k.a. void), on
+/// behalf of the refund `refund_id` if any.
+pub async fn hold_released(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+    refund_id: Option<Uuid>,
+    amount: i32,
+) -> Result<(), sqlx::Error> {
+    let amount = i64::from(amount);
+    record(
+        tx,
+        TransactionKind::HoldReleased,
+        Subject::refund(payment_id, refund_id),
+        &[
+            (Account::CustomerFundsHeld, -amount),
+            (Account::CustomerFunds, amount),
+        ],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Records a refund of captured funds: it's owed to the customer by the
+/// merchant, then credited back to the customer's account.
+pub async fn refunded(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+    refund_id: Uuid,
+    amount: i32,
+) -> Result<(), sqlx::Error> {
+    let amount = i64::from(amount);
+    let subject = Subject::refund(payment_id, Some(refund_id));
+
+    record(
+        tx,
+        TransactionKind::Refund,
+        subject,
+        &[
+            (Account::MerchantReceivable, -amount),
+            (Account::RefundsPayable, amount),
+        ],
+    )
+    .await?;
+    record(
+        tx,
+        TransactionKind::RefundPayout,
+        subject,
+        &[
+            (Account::RefundsPayable, -amount),
+            (Account::CustomerFunds, amount),
+        ],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Records a lost dispute: the disputed amount is taken back from the merchant
+/// and given back to the customer.
+pub async fn dispute_lost(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+    dispute_id: Uuid,
+    amount: i32,
+) -> Result<(), sqlx::Error> {
+    let amount = i64::from(amount);
+    let subject = Subject {
+        dispute_id: Some(dispute_id),
+        ..Subject::payment(payment_id)
+    };
+
+    record(
+        tx,
+        TransactionKind::DisputeLost,
+        subject,
+        &[
+            (Account::MerchantReceivable, -amount),
+            (Account::CustomerFunds, amount),
+        ],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Computes the balance of a ledger account, i.e. the sum of all its entries,
+/// optionally restricted to the transactions of a single payment.
+pub async fn balance(
+    pool: &PgPool,
+    account: Account,
+    payment_id: Option<Uuid>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+            SELECT COALESCE(SUM(e.amount), 0)::bigint AS "balance!"
+            FROM ledger_entries e
+            JOIN ledger_transactions t ON t.id = e.transaction_id
+            WHERE e.account = $1
+              AND ( $2::uuid IS NULL OR t.payment_id = $2 )
+        "#,
+        account as Account,
+        payment_id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|record| record.balance)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::{payments::Payment, refunds::tests::REFUND_REASON};
+
+    async fn balances(pool: &PgPool, payment_id: Uuid) -> [i64; 4] {
+        let mut balances = [0; 4];
+        for (balance, account) in balances.iter_mut().zip(Account::ALL) {
+            *balance = super::balance(pool, account, Some(payment_id))
+                .await
+                .expect("failed to compute balance");
+        }
+        balances
+    }
+
+    #[tokio::test]
+    async fn test_money_movements() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = Payment::new_test(&pool)
+            .await
+            .expect("failed to create payment");
+        let refund_id = crate::bank::refunds::insert(&pool, payment.id, 20, REFUND_REASON)
+            .await
+            .expect("failed to create refund");
+
+        let mut tx = pool.begin().await.unwrap();
+        hold_placed(&mut tx, payment.id, 100).await.unwrap();
+        funds_captured(&mut tx, payment.id, None, 100, 90)
+            .await
+            .unwrap();
+        refunded(&mut tx, payment.id, refund_id, 20).await.unwrap();
+        tx.commit().await.unwrap();
+
+        // customer funds, held funds, merchant receivable, refunds payable
+        assert_eq!(balances(&pool, payment.id).await, [-70, 0, 70, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_unbalanced_transactions_are_rejected() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = Payment::new_test(&pool)
+            .await
+            .expect("failed to create payment");
+
+        let mut tx = pool.begin().await.unwrap();
+        let transaction_id = sqlx::query!(
+            "INSERT INTO ledger_transactions ( kind, payment_id ) VALUES ( 'HoldPlaced', $1 ) RETURNING id",
+            payment.id
+        )
+        .fetch_one(&mut tx)
+        .await
+        .unwrap()
+        .id;
+        sqlx::query!(
+            "INSERT INTO ledger_entries ( transaction_id, account, amount ) VALUES ( $1, 'CustomerFundsHeld', 100 )",
+            transaction_id
+        )
+        .execute(&mut tx)
+        .await
+        .unwrap();
+
+        assert!(tx.commit().await.is_err());
+        assert_eq!(balances(&pool, payment.id).await, [0; 4]);
+    }
+}
@@ -2,6 +2,8 @@ use std::{fmt::Display, num::ParseIntError};
 
 const CARD_NUMBER_LENGTH: usize = 15;
 const ACCOUNT_PREFIX_LENGTH: usize = 2;
+/// Digits left unmasked at the end of masked card numbers.
+const SHOWN_DIGITS: usize = 4;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CardError {
@@ -55,6 +57,13 @@ impl Card {
     pub fn card_number(&self) -> &str {
         &self.0
     }
+
+    /// Returns the card number with all but its last digits masked, to show
+    /// to merchants.
+    pub fn masked(&self) -> String {
+        let (masked, shown) = self.0.split_at(CARD_NUMBER_LENGTH - SHOWN_DIGITS);
+        format!("{}{shown}", "*".repeat(masked.len()))
+    }
 }
 
 #[cfg(test)]
@@ -92,4 +101,10 @@ pub mod tests {
             Self::try_from(card_number).expect("failed to parse card_number")
         }
     }
+
+    #[test]
+    fn test_masked() {
+        let card = Card::try_from("123456789012345".to_string()).unwrap();
+        assert_eq!(card.masked(), "***********2345");
+    }
 }
@@ -1,13 +1,28 @@
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use time::PrimitiveDateTime;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type)]
+use crate::bank::{
+    accounts::{AccountService, HoldRef},
+    attempts::{Attempts, Call},
+    fees, ledger,
+    reconciliation::Operation,
+    timeouts,
+};
+use crate::metrics::metrics;
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
 #[serde(rename_all = "snake_case")]
+#[schema(as = PaymentStatus)]
 pub enum Status {
     /// The payment is being processed, and it's state is unknown.
     Processing,
+    /// The funds are held, but the payment must be reviewed before they're
+    /// withdrawn (see `reviews`).
+    RequiresReview,
     /// The payment was approved by the bank.
     Approved,
     /// The payment was declined by the bank (e.g. insufficient funds).
@@ -16,6 +31,91 @@ pub enum Status {
     Failed,
 }
 
+/// How much of an approved payment has been refunded.
+///
+/// Derived from the payment's refunds, and kept in sync with them whenever a
+/// refund is inserted (see `refunds::checked_insert`).
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundState {
+    /// Nothing was refunded.
+    None,
+    /// Part of the payment amount was refunded.
+    Partial,
+    /// The whole payment amount was refunded.
+    Full,
+}
+
+/// Where the hold placed on the customer's funds for a payment stands.
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldStatus {
+    /// The funds are held, but haven't been withdrawn yet.
+    Placed,
+    /// The funds (or part of them) were withdrawn and sent to the merchant.
+    Withdrawn,
+    /// The hold was released without withdrawing anything.
+    Released,
+}
+
+/// Why a payment was declined.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DeclineCode {
+    /// The customer's account doesn't have enough funds.
+    InsufficientFunds,
+    /// The card doesn't belong to a known account.
+    InvalidAccountNumber,
+    /// Declined by the risk rules, before reaching the account service.
+    RiskDeclined,
+    /// The account's daily or monthly spend limit would be exceeded.
+    SpendLimitExceeded,
+    /// Rejected by a reviewer.
+    ReviewRejected,
+    /// Not reviewed before its review deadline.
+    ReviewExpired,
+}
+
+/// Why a payment failed.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// The account service refused the amount.
+    InvalidAmount,
+    /// The account service is unavailable.
+    ServiceUnavailable,
+    /// The account service failed in any other way.
+    AccountServiceError,
+    /// An account service call timed out: the payment was given up once the
+    /// call was resolved (see `timeouts::resolve`).
+    Timeout,
+}
+
+/// How a payment ends when it doesn't go through.
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum Reversal {
+    Declined(DeclineCode),
+    Failed(FailureReason),
+}
+
+impl Reversal {
+    pub fn status(&self) -> Status {
+        match self {
+            Self::Declined(_) => Status::Declined,
+            Self::Failed(_) => Status::Failed,
+        }
+    }
+}
+
+/// Merchant of the payments that don't specify one.
+pub const DEFAULT_MERCHANT_ID: &str = "default";
+
 // Struct representing a payment.
 //
 // Once a payment has been persisted with an "approved" state, the merchant is guaranteed to
@@ -25,21 +125,38 @@ pub struct Payment {
     pub id: Uuid,
     pub amount: i32,
     pub card_number: String,
+    /// Merchant who gets paid, once the payment is settled.
+    pub merchant_id: String,
     pub status: Status,
+    /// Fee charged to the merchant, once the payment is approved.
+    pub fee_amount: i32,
+    pub refund_state: RefundState,
+    pub hold_id: Option<Uuid>,
+    pub hold_status: Option<HoldStatus>,
+    /// When a payment requiring review is rejected, unless reviewed before.
+    pub review_deadline_at: Option<PrimitiveDateTime>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<PrimitiveDateTime>,
+    pub decline_code: Option<DeclineCode>,
+    pub failure_reason: Option<FailureReason>,
     pub inserted_at: PrimitiveDateTime,
     pub updated_at: PrimitiveDateTime,
 }
 
+/// Inserts a payment with any status, for tests to set up their payments.
+#[cfg(test)]
 pub async fn insert(
     pool: &PgPool,
     amount: i32,
     card_number: String,
+    merchant_id: &str,
     status: Status,
 ) -> Result<Uuid, sqlx::Error> {
     sqlx::query!(
-        r#"INSERT INTO payments ( amount, card_number, status ) VALUES ( $1, $2, $3 ) RETURNING id"#,
+        r#"INSERT INTO payments ( amount, card_number, merchant_id, status ) VALUES ( $1, $2, $3, $4 ) RETURNING id"#,
         amount,
         card_number,
+        merchant_id,
         status as Status
     )
     .fetch_one(pool)
@@ -47,6 +164,46 @@ pub async fn insert(
     .map(|record| record.id)
 }
 
+/// Inserts a `Processing` payment, or tries again the payment previously made
+/// with the same card for the same merchant if it was declined or failed
+/// without holding any funds: either before any hold was placed, or after its
+/// hold was released (see `reverse`).
+///
+/// Returns `None` if the card was already used by a payment that was approved,
+/// is still in flight, still holds funds or was made for another merchant.
+/// Concurrent calls with the same card are serialized by the card number's
+/// unique index, so only one of them gets the payment.
+pub async fn insert_or_retry(
+    pool: &PgPool,
+    amount: i32,
+    card_number: String,
+    merchant_id: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO payments ( amount, card_number, merchant_id, status )
+            VALUES ( $1, $2, $3, 'Processing' )
+            ON CONFLICT ( card_number ) DO UPDATE
+            SET amount = EXCLUDED.amount, status = 'Processing', fee_amount = 0,
+                hold_id = NULL, hold_status = NULL,
+                review_deadline_at = NULL, reviewed_by = NULL, reviewed_at = NULL,
+                decline_code = NULL, failure_reason = NULL, updated_at = current_timestamp
+            WHERE payments.status IN ('Declined', 'Failed')
+              AND ( payments.hold_id IS NULL OR payments.hold_status = 'Released' )
+              AND payments.merchant_id = EXCLUDED.merchant_id
+            RETURNING id
+        "#,
+        amount,
+        card_number,
+        merchant_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|record| record.map(|record| record.id))
+}
+
+/// Sets the status of a payment, for tests to set up their payments.
+#[cfg(test)]
 pub async fn update(pool: &PgPool, id: Uuid, status: Status) -> Result<Uuid, sqlx::Error> {
     sqlx::query!(
         r#"UPDATE payments SET status = $2 WHERE id = $1 RETURNING id"#,
@@ -58,11 +215,233 @@ pub async fn update(pool: &PgPool, id: Uuid, status: Status) -> Result<Uuid, sql
     .map(|record| record.id)
 }
 
+/// Declines or fails a payment which didn't go through, recording why. The
+/// merchant isn't charged any fee for it.
+///
+/// If funds are still held for the payment, the hold is released: every hold
+/// must be matched by a release or a withdrawal (see
+/// `AccountService::place_hold`). The payment row is locked meanwhile, like
+/// `withdraw_funds` does. If the account service fails to release the hold,
+/// it stays placed and the failure is logged.
+pub async fn reverse<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    id: Uuid,
+    reversal: Reversal,
+) -> Result<(), sqlx::Error> {
+    let (decline_code, failure_reason) = match reversal {
+        Reversal::Declined(code) => (Some(code), None),
+        Reversal::Failed(reason) => (None, Some(reason)),
+    };
+
+    let mut tx = pool.begin().await?;
+    let mut attempts = Attempts::default();
+
+    let record = sqlx::query!(
+        r#"SELECT amount, hold_id, hold_status as "hold_status: HoldStatus" FROM payments WHERE id = $1 FOR UPDATE"#,
+        id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let mut hold_status = record.hold_status;
+    if let (Some(hold_id), Some(HoldStatus::Placed)) = (record.hold_id, record.hold_status) {
+        let hold_ref = HoldRef::new(hold_id);
+        let call = Call::new(id, Operation::ReleaseHold).with_hold(hold_ref);
+        match attempts
+            .make(call, account_service.release_hold(hold_ref))
+            .await
+        {
+            Ok(()) => {
+                ledger::hold_released(&mut tx, id, None, record.amount).await?;
+                hold_status = Some(HoldStatus::Released);
+            }
+            Err(error) => {
+                tracing::error!(payment_id = %id, %error, "failed to release the hold of a reversed payment")
+            }
+        }
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE payments
+            SET status = $2, decline_code = $3, failure_reason = $4, hold_status = $5,
+              fee_amount = 0
+            WHERE id = $1
+        "#,
+        id,
+        reversal.status() as Status,
+        decline_code as Option<DeclineCode>,
+        failure_reason as Option<FailureReason>,
+        hold_status as Option<HoldStatus>
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    attempts.record(pool).await;
+
+    metrics().payment_reversed(reversal);
+    Ok(())
+}
+
+/// Approves the payment, charging the merchant the fee of their fee schedule.
+///
+/// The payment only counts as approved once its funds are withdrawn.
+pub async fn approve(pool: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let fee_amount = fee(&mut tx, id).await?;
+    sqlx::query!(
+        r#"UPDATE payments SET status = 'Approved', fee_amount = $2 WHERE id = $1"#,
+        id,
+        fee_amount
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// The fee of the payment, according to its merchant's fee schedule as of
+/// now: it's charged when the payment is approved.
+pub(crate) async fn fee(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> Result<i32, sqlx::Error> {
+    let payment = sqlx::query!(
+        r#"SELECT amount, merchant_id FROM payments WHERE id = $1"#,
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let schedule = fees::get(&mut *tx, &payment.merchant_id).await?;
+    Ok(schedule.map_or(0, |schedule| schedule.fee(payment.amount)))
+}
+
+/// Records the hold placed on the customer's funds for the payment, in the
+/// payment and in the ledger.
+pub async fn record_hold(pool: &PgPool, id: Uuid, hold_ref: HoldRef) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let amount = sqlx::query!(
+        r#"UPDATE payments SET hold_id = $2, hold_status = 'Placed' WHERE id = $1 RETURNING amount"#,
+        id,
+        hold_ref.id()
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .amount;
+
+    ledger::hold_placed(&mut tx, id, amount).await?;
+
+    tx.commit().await
+}
+
+/// Withdraws the funds held for the payment.
+///
+/// The payment row is locked meanwhile, so refunds of the payment (which may
+/// release or capture the same hold, see `refunds::checked_insert`) wait for
+/// the withdrawal to complete. If a refund already released or captured the
+/// hold, or is about to (see `refunds::checked_insert`), there's nothing left
+/// to withdraw.
+///
+/// If the account service times out, the funds may have been withdrawn all
+/// the same: they stay held until the call is resolved (see
+/// `timeouts::resolve`).
+///
+/// The inner result holds the account service's error, if any.
+pub async fn withdraw_funds<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    id: Uuid,
+) -> Result<Result<(), String>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut attempts = Attempts::default();
+
+    let withdrawal = withdraw_held_funds(&mut tx, &mut attempts, account_service, id).await?;
+    let timed_out = attempts.timed_out();
+    if let Some(call) = timed_out {
+        timeouts::record(&mut tx, call).await?;
+    }
+    if withdrawal.is_ok() || timed_out.is_some() {
+        tx.commit().await?;
+    } else {
+        tx.rollback().await?;
+    }
+    attempts.record(pool).await;
+
+    Ok(withdrawal)
+}
+
+/// Withdraws the funds held for the payment within `tx`, see `withdraw_funds`.
+pub(crate) async fn withdraw_held_funds<T: AccountService>(
+    tx: &mut Transaction<'_, Postgres>,
+    attempts: &mut Attempts,
+    account_service: &T,
+    id: Uuid,
+) -> Result<Result<(), String>, sqlx::Error> {
+    let record = sqlx::query!(
+        r#"
+          SELECT amount, hold_id, hold_status as "hold_status: HoldStatus",
+            EXISTS (
+              SELECT 1 FROM refunds r
+              WHERE r.payment_id = p.id AND r.status = 'Pending'
+                AND r.mechanism IN ('ReleaseHold', 'ReducedCapture')
+            ) AS "hold_claimed!"
+          FROM payments p
+          WHERE id = $1
+          FOR UPDATE
+        "#,
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let (Some(hold_id), Some(HoldStatus::Placed)) = (record.hold_id, record.hold_status) else {
+        return Ok(Ok(()));
+    };
+    if record.hold_claimed {
+        return Ok(Ok(()));
+    }
+
+    let hold_ref = HoldRef::new(hold_id);
+    let call = Call::new(id, Operation::WithdrawFunds).with_hold(hold_ref);
+    if let Err(error) = attempts
+        .make(call, account_service.withdraw_funds(hold_ref))
+        .await
+    {
+        return Ok(Err(error));
+    }
+
+    record_withdrawal(tx, id, record.amount).await?;
+
+    Ok(Ok(()))
+}
+
+/// Records that the funds held for the payment were withdrawn, in the payment
+/// and in the ledger.
+pub(crate) async fn record_withdrawal(
+    tx: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    amount: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE payments SET hold_status = 'Withdrawn' WHERE id = $1"#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    ledger::funds_captured(tx, id, None, amount, amount).await
+}
+
 pub async fn get(pool: &PgPool, id: Uuid) -> Result<Payment, sqlx::Error> {
     sqlx::query_as!(
             Payment,
             r#"
-                SELECT id, amount, card_number, inserted_at, updated_at, status as "status: _"  FROM payments
+                SELECT id, amount, card_number, merchant_id, inserted_at, updated_at, status as "status: _", fee_amount, refund_state as "refund_state: _",
+                  hold_id, hold_status as "hold_status: _", review_deadline_at, reviewed_by, reviewed_at,
+                  decline_code as "decline_code: _", failure_reason as "failure_reason: _"
+                FROM payments
                 WHERE id = $1
             "#,
             id
@@ -71,6 +450,72 @@ pub async fn get(pool: &PgPool, id: Uuid) -> Result<Payment, sqlx::Error> {
         .await
 }
 
+/// A try at processing a payment.
+///
+/// Payments that were declined or failed without holding any funds can be
+/// tried again with the same card (see `insert_or_retry`). Each try keeps the
+/// hold placed for it, if any.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Try {
+    pub number: i32,
+    pub amount: i32,
+    pub merchant_id: String,
+    pub status: Status,
+    pub hold_id: Option<Uuid>,
+    pub inserted_at: PrimitiveDateTime,
+    pub updated_at: PrimitiveDateTime,
+}
+
+/// Lists the tries of a payment, in order.
+pub async fn tries(pool: &PgPool, id: Uuid) -> Result<Vec<Try>, sqlx::Error> {
+    sqlx::query_as!(
+        Try,
+        r#"
+            SELECT number, amount, merchant_id, status as "status: _", hold_id, inserted_at, updated_at
+            FROM payment_tries
+            WHERE payment_id = $1
+            ORDER BY number
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Criteria to narrow down a listing of payments. `None` means "any".
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Filter {
+    #[param(value_type = Option<PaymentStatus>)]
+    pub status: Option<Status>,
+    pub refund_state: Option<RefundState>,
+}
+
+/// Maximum number of payments returned by `list`.
+pub const LIST_LIMIT: i64 = 100;
+
+/// Lists the most recent payments matching `filter`, newest first.
+pub async fn list(pool: &PgPool, filter: &Filter) -> Result<Vec<Payment>, sqlx::Error> {
+    sqlx::query_as!(
+        Payment,
+        r#"
+            SELECT id, amount, card_number, merchant_id, inserted_at, updated_at, status as "status: _", fee_amount, refund_state as "refund_state: _",
+              hold_id, hold_status as "hold_status: _", review_deadline_at, reviewed_by, reviewed_at,
+                  decline_code as "decline_code: _", failure_reason as "failure_reason: _"
+            FROM payments
+            WHERE ( $1::Status IS NULL OR status = $1 )
+              AND ( $2::RefundState IS NULL OR refund_state = $2 )
+            ORDER BY inserted_at DESC, id
+            LIMIT $3
+        "#,
+        filter.status as Option<Status>,
+        filter.refund_state as Option<RefundState>,
+        LIST_LIMIT
+    )
+    .fetch_all(pool)
+    .await
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -84,7 +529,14 @@ pub mod tests {
         pub async fn new_test(pool: &PgPool) -> Result<Payment, sqlx::Error> {
             let card = Card::new_test();
 
-            let id = insert(pool, PAYMENT_AMOUNT, card.into(), PAYMENT_STATUS).await?;
+            let id = insert(
+                pool,
+                PAYMENT_AMOUNT,
+                card.into(),
+                DEFAULT_MERCHANT_ID,
+                PAYMENT_STATUS,
+            )
+            .await?;
 
             get(pool, id).await
         }
@@ -102,5 +554,76 @@ pub mod tests {
 
         assert_eq!(payment.amount, PAYMENT_AMOUNT);
         assert_eq!(payment.status, PAYMENT_STATUS);
+        assert_eq!(payment.refund_state, RefundState::None);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let card_number: String = Card::new_test().into();
+
+        let id = insert_or_retry(&pool, 100, card_number.clone(), DEFAULT_MERCHANT_ID)
+            .await
+            .unwrap()
+            .expect("failed to insert payment");
+        update(&pool, id, Status::Failed).await.unwrap();
+
+        // another merchant can't take the payment over
+        let retried_id = insert_or_retry(&pool, 200, card_number.clone(), "other_merchant")
+            .await
+            .unwrap();
+        assert_eq!(retried_id, None);
+
+        let retried_id = insert_or_retry(&pool, 200, card_number.clone(), DEFAULT_MERCHANT_ID)
+            .await
+            .unwrap();
+        assert_eq!(retried_id, Some(id));
+        approve(&pool, id).await.unwrap();
+
+        // approved payments can't be tried again
+        let retried_id = insert_or_retry(&pool, 200, card_number, DEFAULT_MERCHANT_ID)
+            .await
+            .unwrap();
+        assert_eq!(retried_id, None);
+
+        let tries = tries(&pool, id).await.unwrap();
+        let tries: Vec<_> = tries
+            .iter()
+            .map(|t| (t.number, t.amount, t.merchant_id.as_str(), t.status))
+            .collect();
+        assert_eq!(
+            tries,
+            [
+                (1, 100, DEFAULT_MERCHANT_ID, Status::Failed),
+                (2, 200, DEFAULT_MERCHANT_ID, Status::Approved)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_by_refund_state() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = Payment::new_test(&pool)
+            .await
+            .expect("failed to create payment");
+
+        let filter = Filter {
+            status: Some(PAYMENT_STATUS),
+            refund_state: Some(RefundState::None),
+        };
+        let payments = list(&pool, &filter).await.expect("failed to list payments");
+        assert!(payments.iter().any(|p| p.id == payment.id));
+
+        let filter = Filter {
+            refund_state: Some(RefundState::Full),
+            ..Filter::default()
+        };
+        let payments = list(&pool, &filter).await.expect("failed to list payments");
+        assert!(payments.iter().all(|p| p.refund_state == RefundState::Full));
     }
 }
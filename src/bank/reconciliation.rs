@@ -0,0 +1,573 @@
+use std::{collections::BTreeMap, fmt::Display, io::Read, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use uuid::Uuid;
+
+use crate::bank::{payment_instruments::Card, verifications};
+
+/// An account service call that moved (or held) money, named after the
+/// `AccountService` method.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Copy,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "LedgerOperation")]
+#[schema(as = LedgerOperation)]
+pub enum Operation {
+    PlaceHold,
+    ReleaseHold,
+    WithdrawFunds,
+    CaptureFunds,
+    CreditFunds,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// An entry of the account service's ledger export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// The hold the operation relates to. Credits reference the hold of the
+    /// refunded payment.
+    pub hold_id: Uuid,
+    pub account: String,
+    pub amount: i32,
+    pub operation: Operation,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+}
+
+/// Reads a ledger export, in CSV (with a header line) or NDJSON depending on
+/// the file extension.
+pub fn read_export(path: &Path) -> Result<Vec<LedgerEntry>, String> {
+    let file = std::fs::File::open(path).map_err(|error| error.to_string())?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => parse_csv(file),
+        Some("ndjson" | "jsonl") => parse_ndjson(file),
+        _ => Err("ledger export must be a .csv or .ndjson file".to_string()),
+    }
+}
+
+pub fn parse_csv(reader: impl Read) -> Result<Vec<LedgerEntry>, String> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize()
+        .enumerate()
+        // the header is line 1
+        .map(|(index, entry)| entry.map_err(|error| format!("line {}: {error}", index + 2)))
+        .collect()
+}
+
+pub fn parse_ndjson(mut reader: impl Read) -> Result<Vec<LedgerEntry>, String> {
+    let mut content = String::new();
+    reader
+        .read_to_string(&mut content)
+        .map_err(|error| error.to_string())?;
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|error| format!("line {}: {error}", index + 1))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "DiscrepancyKind")]
+pub enum DiscrepancyKind {
+    /// We moved money the account service has no record of.
+    Missing,
+    /// The account service moved money we have no record of.
+    Extra,
+    /// Both sides moved money, but not the same amount.
+    AmountMismatch,
+}
+
+/// A money movement the payments and refunds tables imply, i.e. what the
+/// account service's ledger should hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movement {
+    /// `None` for the holds of card verifications.
+    pub payment_id: Option<Uuid>,
+    pub hold_id: Uuid,
+    pub account: String,
+    pub operation: Operation,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub kind: DiscrepancyKind,
+    pub hold_id: Uuid,
+    pub operation: Operation,
+    pub account: String,
+    pub payment_id: Option<Uuid>,
+    /// What we expected the account service to have moved, if anything.
+    pub expected_amount: Option<i64>,
+    /// What the account service actually moved, if anything.
+    pub actual_amount: Option<i64>,
+}
+
+/// Lists the movements implied by the payments whose hold is in `hold_ids`, or
+/// which were made between `from` and `to`, and likewise by the earlier tries
+/// of payments (whose holds were released before they were tried again) and by
+/// card verifications.
+pub async fn expected_movements(
+    pool: &PgPool,
+    hold_ids: &[Uuid],
+    from: PrimitiveDateTime,
+    to: PrimitiveDateTime,
+) -> Result<Vec<Movement>, sqlx::Error> {
+    let records = sqlx::query!(
+        r#"
+            SELECT p.id AS "payment_id?", p.hold_id AS "hold_id!", p.card_number AS "card_number!",
+              m.operation AS "operation!: Operation", m.amount AS "amount!"
+            FROM payments p
+            CROSS JOIN LATERAL (
+              SELECT 'PlaceHold'::LedgerOperation AS operation, p.amount::bigint AS amount
+              UNION ALL
+              SELECT 'ReleaseHold', p.amount::bigint
+              WHERE p.hold_status = 'Released'
+              UNION ALL
+              SELECT 'WithdrawFunds', p.amount::bigint
+              WHERE p.hold_status = 'Withdrawn'
+                AND NOT EXISTS (
+                  SELECT 1 FROM refunds r
+                  WHERE r.payment_id = p.id AND r.mechanism = 'ReducedCapture'
+                    AND r.status = 'Succeeded'
+                )
+              UNION ALL
+              SELECT 'CaptureFunds', (p.amount - r.amount)::bigint
+              FROM refunds r
+              WHERE r.payment_id = p.id AND r.mechanism = 'ReducedCapture'
+                AND r.status = 'Succeeded'
+              UNION ALL
+              SELECT 'CreditFunds', SUM(r.amount)::bigint
+              FROM refunds r
+              WHERE r.payment_id = p.id AND r.mechanism = 'Credit' AND r.status = 'Succeeded'
+              HAVING COUNT(*) > 0
+            ) m
+            WHERE p.hold_id IS NOT NULL
+              AND ( p.hold_id = ANY($1) OR p.inserted_at BETWEEN $2 AND $3 )
+            UNION ALL
+            SELECT t.payment_id, t.hold_id, p.card_number, m.operation, t.amount::bigint
+            FROM payment_tries t
+            JOIN payments p ON p.id = t.payment_id
+            CROSS JOIN LATERAL (
+              SELECT 'PlaceHold'::LedgerOperation AS operation
+              UNION ALL
+              SELECT 'ReleaseHold'
+            ) m
+            WHERE t.hold_id IS NOT NULL AND t.hold_id IS DISTINCT FROM p.hold_id
+              AND ( t.hold_id = ANY($1) OR t.inserted_at BETWEEN $2 AND $3 )
+            UNION ALL
+            SELECT NULL, v.hold_id, v.card_number, m.operation, $4::bigint
+            FROM card_verifications v
+            CROSS JOIN LATERAL (
+              SELECT 'PlaceHold'::LedgerOperation AS operation
+              UNION ALL
+              SELECT 'ReleaseHold'
+              WHERE v.hold_status = 'Released'
+            ) m
+            WHERE v.hold_id IS NOT NULL
+              AND ( v.hold_id = ANY($1) OR v.inserted_at BETWEEN $2 AND $3 )
+        "#,
+        hold_ids,
+        from,
+        to,
+        i64::from(verifications::HOLD_AMOUNT)
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| Movement {
+            payment_id: record.payment_id,
+            hold_id: record.hold_id,
+            account: Card::try_from(record.card_number.clone())
+                .map(|card| card.account_number().to_string())
+                .unwrap_or(record.card_number),
+            operation: record.operation,
+            amount: record.amount,
+        })
+        .collect())
+}
+
+/// Matches the expected movements against the ledger entries, per hold and
+/// operation (amounts of several entries for the same hold and operation,
+/// e.g. several credits, are summed up).
+pub fn compare(expected: &[Movement], entries: &[LedgerEntry]) -> Vec<Discrepancy> {
+    let mut expected_amounts = BTreeMap::new();
+    for movement in expected {
+        expected_amounts
+            .entry((movement.hold_id, movement.operation))
+            .or_insert((movement.payment_id, movement.account.as_str(), 0))
+            .2 += movement.amount;
+    }
+
+    let mut actual_amounts = BTreeMap::new();
+    for entry in entries {
+        actual_amounts
+            .entry((entry.hold_id, entry.operation))
+            .or_insert((entry.account.as_str(), 0))
+            .1 += i64::from(entry.amount);
+    }
+
+    let mut discrepancies = Vec::new();
+    for (&(hold_id, operation), &(payment_id, account, expected_amount)) in &expected_amounts {
+        let (kind, actual_amount) = match actual_amounts.get(&(hold_id, operation)) {
+            None => (DiscrepancyKind::Missing, None),
+            Some(&(_, actual_amount)) if actual_amount != expected_amount => {
+                (DiscrepancyKind::AmountMismatch, Some(actual_amount))
+            }
+            Some(_) => continue,
+        };
+        discrepancies.push(Discrepancy {
+            kind,
+            hold_id,
+            operation,
+            account: account.to_string(),
+            payment_id,
+            expected_amount: Some(expected_amount),
+            actual_amount,
+        });
+    }
+
+    for (&(hold_id, operation), &(account, actual_amount)) in &actual_amounts {
+        if expected_amounts.contains_key(&(hold_id, operation)) {
+            continue;
+        }
+        // the hold may still be one of ours, for an operation we didn't expect
+        let payment_id = expected
+            .iter()
+            .find(|movement| movement.hold_id == hold_id)
+            .and_then(|movement| movement.payment_id);
+        discrepancies.push(Discrepancy {
+            kind: DiscrepancyKind::Extra,
+            hold_id,
+            operation,
+            account: account.to_string(),
+            payment_id,
+            expected_amount: None,
+            actual_amount: Some(actual_amount),
+        });
+    }
+
+    discrepancies
+}
+
+/// Reconciles the payments and refunds tables against a ledger export.
+///
+/// Only the payments the export covers are considered: those whose hold it
+/// mentions, or which were made within its time span.
+pub async fn reconcile(
+    pool: &PgPool,
+    entries: &[LedgerEntry],
+) -> Result<Vec<Discrepancy>, sqlx::Error> {
+    let timestamps = entries.iter().map(|entry| entry.timestamp);
+    let (Some(from), Some(to)) = (timestamps.clone().min(), timestamps.max()) else {
+        return Ok(Vec::new());
+    };
+
+    let hold_ids: Vec<_> = entries.iter().map(|entry| entry.hold_id).collect();
+    let expected = expected_movements(pool, &hold_ids, to_utc(from), to_utc(to)).await?;
+
+    Ok(compare(&expected, entries))
+}
+
+pub(crate) fn to_utc(timestamp: OffsetDateTime) -> PrimitiveDateTime {
+    let timestamp = timestamp.to_offset(UtcOffset::UTC);
+    PrimitiveDateTime::new(timestamp.date(), timestamp.time())
+}
+
+/// Persists the discrepancies found while reconciling `source`, for follow-up.
+pub async fn record_run(
+    pool: &PgPool,
+    source: &str,
+    entries_count: usize,
+    discrepancies: &[Discrepancy],
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let run_id = sqlx::query!(
+        r#"
+            INSERT INTO reconciliation_runs ( source, entries_count, discrepancies_count )
+            VALUES ( $1, $2, $3 )
+            RETURNING id
+        "#,
+        source,
+        entries_count as i32,
+        discrepancies.len() as i32
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .id;
+
+    for discrepancy in discrepancies {
+        sqlx::query!(
+            r#"
+                INSERT INTO reconciliation_discrepancies
+                  ( run_id, kind, hold_id, operation, account, payment_id, expected_amount, actual_amount )
+                VALUES ( $1, $2, $3, $4, $5, $6, $7, $8 )
+            "#,
+            run_id,
+            discrepancy.kind as DiscrepancyKind,
+            discrepancy.hold_id,
+            discrepancy.operation as Operation,
+            discrepancy.account,
+            discrepancy.payment_id,
+            discrepancy.expected_amount,
+            discrepancy.actual_amount
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(run_id)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::{
+        accounts::{AccountService, DummyService},
+        payments::{self, Payment},
+    };
+
+    const HOLD_ID: Uuid = Uuid::from_u128(1);
+
+    fn entry(hold_id: Uuid, operation: Operation, amount: i32) -> LedgerEntry {
+        LedgerEntry {
+            hold_id,
+            account: "12".to_string(),
+            amount,
+            operation,
+            timestamp: OffsetDateTime::now_utc(),
+        }
+    }
+
+    fn movement(hold_id: Uuid, operation: Operation, amount: i64) -> Movement {
+        Movement {
+            payment_id: Some(Uuid::from_u128(2)),
+            hold_id,
+            account: "12".to_string(),
+            operation,
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let csv = "hold_id,account,amount,operation,timestamp\n\
+            00000000-0000-0000-0000-000000000001, 12, 123, withdraw_funds, 2026-10-18T12:00:00Z\n";
+        let entries = parse_csv(csv.as_bytes()).expect("failed to parse CSV");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].hold_id, HOLD_ID);
+        assert_eq!(entries[0].operation, Operation::WithdrawFunds);
+
+        let ndjson = r#"{"hold_id":"00000000-0000-0000-0000-000000000001","account":"12","amount":123,"operation":"withdraw_funds","timestamp":"2026-10-18T12:00:00Z"}"#;
+        assert_eq!(parse_ndjson(ndjson.as_bytes()), Ok(entries));
+
+        let error =
+            parse_csv("hold_id,account,amount,operation,timestamp\nnope,12,1,x,y\n".as_bytes())
+                .unwrap_err();
+        assert!(error.starts_with("line 2:"));
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected = [
+            movement(HOLD_ID, Operation::PlaceHold, 100),
+            movement(HOLD_ID, Operation::WithdrawFunds, 100),
+            movement(HOLD_ID, Operation::CreditFunds, 30),
+        ];
+        let entries = [
+            entry(HOLD_ID, Operation::PlaceHold, 100),
+            entry(HOLD_ID, Operation::WithdrawFunds, 90),
+            entry(HOLD_ID, Operation::ReleaseHold, 100),
+        ];
+
+        let discrepancies = compare(&expected, &entries);
+        let kinds: Vec<_> = discrepancies
+            .iter()
+            .map(|discrepancy| (discrepancy.kind, discrepancy.operation))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (DiscrepancyKind::AmountMismatch, Operation::WithdrawFunds),
+                (DiscrepancyKind::Missing, Operation::CreditFunds),
+                (DiscrepancyKind::Extra, Operation::ReleaseHold),
+            ]
+        );
+        assert_eq!(discrepancies[0].actual_amount, Some(90));
+        assert_eq!(discrepancies[2].payment_id, Some(Uuid::from_u128(2)));
+
+        // several entries for the same operation add up
+        let entries = [
+            entry(HOLD_ID, Operation::PlaceHold, 100),
+            entry(HOLD_ID, Operation::WithdrawFunds, 100),
+            entry(HOLD_ID, Operation::CreditFunds, 10),
+            entry(HOLD_ID, Operation::CreditFunds, 20),
+        ];
+        assert!(compare(&expected, &entries).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = Payment::new_test(&pool)
+            .await
+            .expect("failed to create payment");
+        let hold_ref = DummyService::default()
+            .place_hold("12", payment.amount)
+            .await
+            .unwrap();
+        payments::record_hold(&pool, payment.id, hold_ref)
+            .await
+            .unwrap();
+        payments::withdraw_funds(&pool, &DummyService::default(), payment.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let hold_id = hold_ref.id();
+        let entries = [
+            entry(hold_id, Operation::PlaceHold, payment.amount),
+            entry(hold_id, Operation::WithdrawFunds, payment.amount - 1),
+        ];
+        let discrepancies = reconcile(&pool, &entries)
+            .await
+            .expect("failed to reconcile");
+        let discrepancies: Vec<_> = discrepancies
+            .into_iter()
+            .filter(|discrepancy| discrepancy.hold_id == hold_id)
+            .collect();
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].kind, DiscrepancyKind::AmountMismatch);
+        assert_eq!(discrepancies[0].payment_id, Some(payment.id));
+        assert_eq!(
+            discrepancies[0].expected_amount,
+            Some(payment.amount as i64)
+        );
+
+        let run_id = record_run(&pool, "test", entries.len(), &discrepancies)
+            .await
+            .expect("failed to record discrepancies");
+        let recorded = sqlx::query!(
+            "SELECT COUNT(*) AS \"count!\" FROM reconciliation_discrepancies WHERE run_id = $1",
+            run_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap()
+        .count;
+        assert_eq!(recorded, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_retried_payment() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let account_service = DummyService::default();
+        let card_number: String = Card::new_test().into();
+
+        // the first try's hold is released, the second try's is withdrawn
+        let mut hold_ids = vec![];
+        for withdrawn in [false, true] {
+            let id = payments::insert_or_retry(&pool, 100, card_number.clone(), "default")
+                .await
+                .unwrap()
+                .expect("failed to insert payment");
+            let hold_ref = account_service.place_hold("12", 100).await.unwrap();
+            payments::record_hold(&pool, id, hold_ref).await.unwrap();
+            if withdrawn {
+                payments::withdraw_funds(&pool, &account_service, id)
+                    .await
+                    .unwrap()
+                    .unwrap();
+            } else {
+                let reversal =
+                    payments::Reversal::Failed(payments::FailureReason::AccountServiceError);
+                payments::reverse(&pool, &account_service, id, reversal)
+                    .await
+                    .unwrap();
+            }
+            hold_ids.push(hold_ref.id());
+        }
+
+        let entries = [
+            entry(hold_ids[0], Operation::PlaceHold, 100),
+            entry(hold_ids[0], Operation::ReleaseHold, 100),
+            entry(hold_ids[1], Operation::PlaceHold, 100),
+            entry(hold_ids[1], Operation::WithdrawFunds, 100),
+        ];
+        let discrepancies = reconcile(&pool, &entries)
+            .await
+            .expect("failed to reconcile");
+        assert!(discrepancies
+            .iter()
+            .all(|discrepancy| !hold_ids.contains(&discrepancy.hold_id)));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_verification() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let hold_id = Uuid::new_v4();
+        let outcome = verifications::Outcome::Verified {
+            hold_id,
+            released: false,
+        };
+        verifications::insert(&pool, "123456789012345", "default", outcome)
+            .await
+            .expect("failed to insert verification");
+
+        // the hold wasn't released: only its placement is expected
+        let entries = [
+            entry(hold_id, Operation::PlaceHold, verifications::HOLD_AMOUNT),
+            entry(hold_id, Operation::ReleaseHold, verifications::HOLD_AMOUNT),
+        ];
+        let discrepancies = reconcile(&pool, &entries)
+            .await
+            .expect("failed to reconcile");
+        let discrepancies: Vec<_> = discrepancies
+            .into_iter()
+            .filter(|discrepancy| discrepancy.hold_id == hold_id)
+            .collect();
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].kind, DiscrepancyKind::Extra);
+        assert_eq!(discrepancies[0].operation, Operation::ReleaseHold);
+        assert_eq!(discrepancies[0].payment_id, None);
+    }
+}
@@ -1,7 +1,57 @@
-use sqlx::PgPool;
-use time::PrimitiveDateTime;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
 use uuid::Uuid;
 
+use crate::bank::{
+    accounts::{self, AccountService, HoldRef},
+    attempts::{Attempts, Call},
+    fees, ledger,
+    payment_instruments::Card,
+    payments::{self, HoldStatus, RefundState},
+    reconciliation::Operation,
+    timeouts,
+};
+use crate::metrics::metrics;
+
+/// Why a refund was requested.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "RefundReason")]
+#[schema(as = RefundReason)]
+pub enum Reason {
+    /// The customer was charged twice for the same purchase.
+    Duplicate,
+    /// The payment wasn't authorized by the card holder.
+    Fraudulent,
+    /// The customer asked for their money back (e.g. returned goods).
+    RequestedByCustomer,
+    Other,
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "RefundStatus")]
+#[schema(as = RefundStatus)]
+pub enum Status {
+    /// The refund is above the approval threshold, and waits for another
+    /// principal to approve or reject it.
+    AwaitingApproval,
+    /// The refund already counts against the payment amount, but the account
+    /// service is still giving the money back.
+    Pending,
+    /// The refund is effective.
+    Succeeded,
+    /// The refund was rejected during approval, and will never be effective.
+    Rejected,
+}
+
 /// Module and schema representing a refund.
 ///
 /// A refund is always tied to a specific payment record, but it is possible
@@ -10,26 +60,51 @@ use uuid::Uuid;
 /// payment record, the but sum of all refunded amounts for a given payment can
 /// never surpass the original payment amount.
 ///
-/// If a refund is persisted in the database, it is considered effective: the
-/// bank's client will have the money credited to their account.
+/// Once a refund has a "succeeded" status, it is considered effective: the
+/// bank's client will have the money credited to their account. Refunds above
+/// the policy's approval threshold are first persisted as "awaiting approval",
+/// and only count against the payment amount once approved. In between, while
+/// the account service gives the money back, the refund is "pending".
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct Refund {
     pub id: Uuid,
     pub payment_id: Uuid,
     pub amount: i32,
+    pub reason: Reason,
+    pub status: Status,
+    /// How the money was given back, once the refund succeeded.
+    pub mechanism: Option<Mechanism>,
+    /// Part of the payment's fee given back to the merchant, once the refund
+    /// succeeded.
+    pub fee_amount: i32,
+    /// Principal who requested the refund, if it was authenticated.
+    pub requested_by: Option<String>,
+    /// Principal who approved or rejected the refund.
+    pub reviewed_by: Option<String>,
+    #[allow(dead_code)]
+    pub reviewed_at: Option<PrimitiveDateTime>,
+    #[allow(dead_code)]
     pub inserted_at: PrimitiveDateTime,
+    #[allow(dead_code)]
     pub updated_at: PrimitiveDateTime,
 }
 
-pub async fn insert(pool: &PgPool, payment_id: Uuid, amount: i32) -> Result<Uuid, sqlx::Error> {
+#[allow(dead_code)]
+pub async fn insert(
+    pool: &PgPool,
+    payment_id: Uuid,
+    amount: i32,
+    reason: Reason,
+) -> Result<Uuid, sqlx::Error> {
     sqlx::query!(
         r#"
-            INSERT INTO refunds ( payment_id, amount )
-            VALUES ( $1, $2 )
+            INSERT INTO refunds ( payment_id, amount, reason, status, mechanism )
+            VALUES ( $1, $2, $3, 'Succeeded', 'Credit' )
             RETURNING id
         "#,
         payment_id,
         amount,
+        reason as Reason,
     )
     .fetch_one(pool)
     .await
@@ -40,7 +115,9 @@ pub async fn get(pool: &PgPool, id: Uuid) -> Result<Refund, sqlx::Error> {
     sqlx::query_as!(
         Refund,
         r#"
-            SELECT id, payment_id, amount, inserted_at, updated_at FROM refunds
+            SELECT id, payment_id, amount, reason as "reason: _", status as "status: _",
+              mechanism as "mechanism: _", fee_amount, requested_by, reviewed_by, reviewed_at, inserted_at, updated_at
+            FROM refunds
             WHERE id = $1
         "#,
         id
@@ -49,53 +126,908 @@ pub async fn get(pool: &PgPool, id: Uuid) -> Result<Refund, sqlx::Error> {
     .await
 }
 
-pub async fn checked_insert(
+pub async fn list(pool: &PgPool, payment_id: Uuid) -> Result<Vec<Refund>, sqlx::Error> {
+    sqlx::query_as!(
+        Refund,
+        r#"
+            SELECT id, payment_id, amount, reason as "reason: _", status as "status: _",
+              mechanism as "mechanism: _", fee_amount, requested_by, reviewed_by, reviewed_at, inserted_at, updated_at
+            FROM refunds
+            WHERE payment_id = $1
+            ORDER BY inserted_at, id
+        "#,
+        payment_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// How much of a payment has been refunded or disputed so far, and how much
+/// can still be refunded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balance {
+    pub refunded_amount: i32,
+    /// Part of the payment's fee given back to the merchant by the refunds.
+    pub refunded_fee_amount: i32,
+    pub disputed_amount: i32,
+    pub refundable_amount: i32,
+}
+
+/// Computes the refund balance of a payment.
+///
+/// Uses the same rule as `checked_insert`, the `refundable_amount` SQL function:
+/// the payment amount minus the sum of all its pending and succeeded refunds
+/// and of all its disputes that weren't won by the merchant. Payments that aren't approved
+/// can't be refunded, so their refundable amount is always 0.
+pub async fn balance(pool: &PgPool, payment_id: Uuid) -> Result<Balance, sqlx::Error> {
+    balances(pool, &[payment_id])
+        .await?
+        .remove(&payment_id)
+        .ok_or(sqlx::Error::RowNotFound)
+}
+
+/// Computes the refund balances of several payments at once, keyed by payment id.
+///
+/// Unknown payment ids are left out of the result.
+pub async fn balances(
+    pool: &PgPool,
+    payment_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Balance>, sqlx::Error> {
+    let records = sqlx::query!(
+        r#"
+            SELECT
+              p.id,
+              COALESCE((
+                SELECT SUM(r.amount) FROM refunds r
+                WHERE r.payment_id = p.id AND r.status = 'Succeeded'
+              ), 0)::integer AS "refunded_amount!",
+              COALESCE((
+                SELECT SUM(r.fee_amount) FROM refunds r
+                WHERE r.payment_id = p.id AND r.status = 'Succeeded'
+              ), 0)::integer AS "refunded_fee_amount!",
+              COALESCE((
+                SELECT SUM(d.amount) FROM disputes d
+                WHERE d.payment_id = p.id AND d.status <> 'Won'
+              ), 0)::integer AS "disputed_amount!",
+              CASE WHEN p.status = 'Approved'
+                THEN refundable_amount(p.id)
+                ELSE 0
+              END AS "refundable_amount!"
+            FROM payments p
+            WHERE p.id = ANY($1)
+        "#,
+        payment_ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| {
+            let balance = Balance {
+                refunded_amount: record.refunded_amount,
+                refunded_fee_amount: record.refunded_fee_amount,
+                disputed_amount: record.disputed_amount,
+                refundable_amount: record.refundable_amount,
+            };
+            (record.id, balance)
+        })
+        .collect())
+}
+
+/// Counts the refunds already requested against a payment, leaving out the
+/// rejected ones.
+async fn count(tx: &mut Transaction<'_, Postgres>, payment_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM refunds WHERE payment_id = $1 AND status <> 'Rejected'"#,
+        payment_id
+    )
+    .fetch_one(tx)
+    .await
+    .map(|record| record.count)
+}
+
+/// Business rules a refund must comply with, on top of the refundable balance.
+///
+/// Limits set to `None` aren't enforced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// How many days after the payment a refund can still be requested.
+    pub max_payment_age_days: Option<i64>,
+    /// Smallest amount that can be refunded at once.
+    pub min_amount: i32,
+    /// How many refunds can be made against a single payment.
+    pub max_refunds_per_payment: Option<i64>,
+    /// Refunds above this amount must be approved by another principal before
+    /// they're effective.
+    pub approval_threshold: Option<i32>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_payment_age_days: Some(180),
+            min_amount: 1,
+            max_refunds_per_payment: Some(10),
+            approval_threshold: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    MissingReason,
+    AmountBelowMinimum,
+    PaymentTooOld,
+    TooManyRefunds,
+}
+
+impl Policy {
+    /// Whether a refund of `amount` must be approved before it's effective.
+    pub fn requires_approval(&self, amount: i32) -> bool {
+        self.approval_threshold
+            .is_some_and(|threshold| amount > threshold)
+    }
+
+    /// Checks a refund of `amount` against a payment made at `paid_at`, which
+    /// already has `refund_count` refunds.
+    pub fn check(
+        &self,
+        paid_at: PrimitiveDateTime,
+        refund_count: i64,
+        amount: i32,
+        reason: Option<Reason>,
+        now: OffsetDateTime,
+    ) -> Result<Reason, PolicyViolation> {
+        let reason = reason.ok_or(PolicyViolation::MissingReason)?;
+
+        if amount < self.min_amount {
+            return Err(PolicyViolation::AmountBelowMinimum);
+        }
+
+        if let Some(max_age_days) = self.max_payment_age_days {
+            // timestamps are stored in the database's timezone, which is UTC
+            if now - paid_at.assume_utc() > Duration::days(max_age_days) {
+                return Err(PolicyViolation::PaymentTooOld);
+            }
+        }
+
+        if let Some(max_refunds) = self.max_refunds_per_payment {
+            if refund_count >= max_refunds {
+                return Err(PolicyViolation::TooManyRefunds);
+            }
+        }
+
+        Ok(reason)
+    }
+}
+
+/// How a refund gave the money back to the customer.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "RefundMechanism")]
+#[schema(as = RefundMechanism)]
+pub enum Mechanism {
+    /// The payment's funds were still held, and the whole hold was released.
+    ReleaseHold,
+    /// The payment's funds were still held, and only the amount that wasn't
+    /// refunded was withdrawn.
+    ReducedCapture,
+    /// The payment's funds were already withdrawn, and the refund amount was
+    /// credited back to the customer's account.
+    Credit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The refund is effective.
+    Refunded(Uuid),
+    /// The refund would exceed the refundable balance of the payment (or the
+    /// payment doesn't exist).
+    ExcessiveAmount,
+    /// The refund doesn't comply with the refund policy.
+    Violation(PolicyViolation),
+    /// Another refund of the payment is releasing or capturing its hold, or an
+    /// account service call made for the payment timed out and isn't resolved
+    /// yet: they must complete first.
+    InProgress,
+    /// The account service failed to give the money back, with the given error:
+    /// nothing was persisted.
+    Failed(String),
+    /// The account service timed out: the refund stays pending until the call
+    /// is resolved (see `timeouts::resolve`).
+    TimedOut(Uuid),
+}
+
+/// Refunds a payment, unless the refund doesn't comply with `policy` or would
+/// make the sum of all pending and succeeded refunds of the payment exceed the
+/// payment amount.
+///
+/// The payment row is locked while the refund is checked and recorded as
+/// pending, so concurrent refunds of the same payment are checked one after the
+/// other and can't both squeeze under the payment amount or the policy's
+/// limits. The lock is released before the account service is asked to give
+/// the money back, then the refund is completed or abandoned (see `execute`).
+///
+/// The payment's `refund_state` is updated when the refund completes, and a
+/// status change event is emitted when it changes.
+pub async fn checked_insert<T: AccountService>(
     pool: &PgPool,
+    account_service: &T,
+    policy: &Policy,
     payment_id: Uuid,
     refund_amount: i32,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    sqlx::query!(
+    reason: Option<Reason>,
+    requested_by: Option<&str>,
+) -> Result<Outcome, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let Some(payment) = lock_payment(&mut tx, payment_id).await? else {
+        return Ok(Outcome::ExcessiveAmount);
+    };
+
+    let refund_count = count(&mut tx, payment_id).await?;
+    let reason = match policy.check(
+        payment.inserted_at,
+        refund_count,
+        refund_amount,
+        reason,
+        OffsetDateTime::now_utc(),
+    ) {
+        Ok(reason) => reason,
+        Err(violation) => {
+            tx.rollback().await?;
+            return Ok(Outcome::Violation(violation));
+        }
+    };
+
+    let refund_id = sqlx::query!(
         r#"
-          INSERT into refunds ( payment_id, amount )
-          SELECT $1, $2
-          WHERE EXISTS (
-            SELECT ( t2.amount - SUM(t1.amount) ) 
-            FROM refunds t1 
-            JOIN payments t2 on t1.payment_id = t2.id 
-            WHERE t1.payment_id = $1 
-            GROUP BY t1.payment_id, t2.amount
-            HAVING t2.amount - SUM(t1.amount) >= $2::integer
-          ) OR (
-            NOT EXISTS (
-              SELECT * FROM refunds WHERE payment_id = $1
-            )
-            AND EXISTS (
-              SELECT * FROM payments WHERE id = $1 AND amount >= $2
-            )
-          )
+          INSERT INTO refunds ( payment_id, amount, reason, status, requested_by )
+          SELECT p.id, $2, $3, 'Pending', $4
+          FROM payments p
+          WHERE p.id = $1
+            AND refundable_amount(p.id) >= $2::integer
           RETURNING id
         "#,
         payment_id,
-        refund_amount
+        refund_amount,
+        reason as Reason,
+        requested_by
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .map(|record| record.id);
+
+    let Some(refund_id) = refund_id else {
+        tx.rollback().await?;
+        return Ok(Outcome::ExcessiveAmount);
+    };
+
+    let Some(mechanism) = claim_funds(&mut tx, &payment, refund_id, refund_amount).await? else {
+        tx.rollback().await?;
+        return Ok(Outcome::InProgress);
+    };
+
+    tx.commit().await?;
+
+    let claim = Claim {
+        payment,
+        refund_id,
+        amount: refund_amount,
+        mechanism,
+    };
+    Ok(match execute(pool, account_service, &claim).await? {
+        Ok(()) => Outcome::Refunded(refund_id),
+        Err(error) if error == accounts::TIMEOUT => Outcome::TimedOut(refund_id),
+        Err(error) => Outcome::Failed(error),
+    })
+}
+
+/// Inserts a refund awaiting approval by another principal than `requested_by`,
+/// unless it doesn't comply with `policy`.
+///
+/// The payment row is locked while the policy is checked, like `checked_insert`
+/// does, but the refundable balance isn't checked until the refund is approved.
+pub async fn insert_awaiting_approval(
+    pool: &PgPool,
+    policy: &Policy,
+    payment_id: Uuid,
+    amount: i32,
+    reason: Option<Reason>,
+    requested_by: &str,
+) -> Result<Result<Uuid, PolicyViolation>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let payment = lock_payment(&mut tx, payment_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    let refund_count = count(&mut tx, payment_id).await?;
+    let reason = match policy.check(
+        payment.inserted_at,
+        refund_count,
+        amount,
+        reason,
+        OffsetDateTime::now_utc(),
+    ) {
+        Ok(reason) => reason,
+        Err(violation) => {
+            tx.rollback().await?;
+            return Ok(Err(violation));
+        }
+    };
+
+    let refund_id = sqlx::query!(
+        r#"
+            INSERT INTO refunds ( payment_id, amount, reason, status, requested_by )
+            VALUES ( $1, $2, $3, 'AwaitingApproval', $4 )
+            RETURNING id
+        "#,
+        payment_id,
+        amount,
+        reason as Reason,
+        requested_by
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .id;
+
+    tx.commit().await?;
+
+    metrics().refund(Status::AwaitingApproval, amount);
+    Ok(Ok(refund_id))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Approval {
+    /// The refund is now effective.
+    Approved,
+    /// Approving the refund would exceed the payment amount; it's still awaiting
+    /// approval and can be rejected.
+    ExcessiveAmount,
+    /// The refund was already approved or rejected.
+    AlreadyReviewed,
+    /// Another refund or a timed-out call of the payment must complete first
+    /// (see `Outcome::InProgress`): the refund is still awaiting approval.
+    InProgress,
+    /// The account service failed to give the money back, with the given error:
+    /// the refund is still awaiting approval.
+    Failed(String),
+    /// The account service timed out: the refund is approved, but stays
+    /// pending until the call is resolved (see `timeouts::resolve`).
+    TimedOut,
+}
+
+/// Approves a refund awaiting approval, checking it against the refundable
+/// balance of its payment and executing it exactly like `checked_insert` does.
+///
+/// If the account service fails to give the money back, the refund goes back
+/// to awaiting approval.
+pub async fn approve<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    refund_id: Uuid,
+    approved_by: &str,
+) -> Result<Approval, sqlx::Error> {
+    let refund = get(pool, refund_id).await?;
+
+    let mut tx = pool.begin().await?;
+
+    let payment = lock_payment(&mut tx, refund.payment_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    let approved = sqlx::query!(
+        r#"
+          UPDATE refunds r
+          SET status = 'Pending', reviewed_by = $2, reviewed_at = current_timestamp,
+              updated_at = current_timestamp
+          FROM payments p
+          WHERE r.id = $1
+            AND r.status = 'AwaitingApproval'
+            AND p.id = r.payment_id
+            AND refundable_amount(p.id) >= r.amount
+          RETURNING r.id
+        "#,
+        refund_id,
+        approved_by
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .is_some();
+
+    if !approved {
+        let status = sqlx::query!(
+            r#"SELECT status as "status: Status" FROM refunds WHERE id = $1"#,
+            refund_id
+        )
+        .fetch_one(&mut tx)
+        .await?
+        .status;
+        tx.rollback().await?;
+
+        return Ok(match status {
+            Status::AwaitingApproval => Approval::ExcessiveAmount,
+            _ => Approval::AlreadyReviewed,
+        });
+    }
+
+    let Some(mechanism) = claim_funds(&mut tx, &payment, refund_id, refund.amount).await? else {
+        tx.rollback().await?;
+        return Ok(Approval::InProgress);
+    };
+
+    tx.commit().await?;
+
+    let claim = Claim {
+        payment,
+        refund_id,
+        amount: refund.amount,
+        mechanism,
+    };
+    Ok(match execute(pool, account_service, &claim).await? {
+        Ok(()) => Approval::Approved,
+        Err(error) if error == accounts::TIMEOUT => Approval::TimedOut,
+        Err(error) => Approval::Failed(error),
+    })
+}
+
+/// Rejects a refund awaiting approval.
+///
+/// Returns `false` if the refund was already approved or rejected.
+pub async fn reject(
+    pool: &PgPool,
+    refund_id: Uuid,
+    rejected_by: &str,
+) -> Result<bool, sqlx::Error> {
+    let rejected = sqlx::query!(
+        r#"
+          UPDATE refunds
+          SET status = 'Rejected', reviewed_by = $2, reviewed_at = current_timestamp,
+              updated_at = current_timestamp
+          WHERE id = $1 AND status = 'AwaitingApproval'
+          RETURNING id
+        "#,
+        refund_id,
+        rejected_by
     )
     .fetch_optional(pool)
+    .await?
+    .is_some();
+
+    if rejected {
+        metrics().refund(Status::Rejected, 0);
+    }
+    Ok(rejected)
+}
+
+/// The parts of a payment needed to refund it.
+struct LockedPayment {
+    id: Uuid,
+    amount: i32,
+    card_number: String,
+    refund_state: RefundState,
+    hold_id: Option<Uuid>,
+    hold_status: Option<HoldStatus>,
+    inserted_at: PrimitiveDateTime,
+}
+
+/// Locks the payment row until the end of the transaction, so the refunds of
+/// a payment are checked against its balance and the refund policy one at a
+/// time.
+async fn lock_payment(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+) -> Result<Option<LockedPayment>, sqlx::Error> {
+    sqlx::query_as!(
+        LockedPayment,
+        r#"
+          SELECT id, amount, card_number, refund_state as "refund_state: _",
+            hold_id, hold_status as "hold_status: _", inserted_at
+          FROM payments
+          WHERE id = $1
+          FOR UPDATE
+        "#,
+        payment_id
+    )
+    .fetch_optional(tx)
+    .await
+}
+
+/// Picks how `amount` of a locked payment is given back to the customer, and
+/// records it on the pending refund.
+///
+/// If the payment's hold is still outstanding, its funds were never withdrawn:
+/// rather than moving the money twice, the hold is released (for a full refund)
+/// or only the amount that isn't refunded is captured (for a partial refund).
+/// Otherwise, the amount is credited back to the customer's account.
+///
+/// A pending refund releasing or capturing the hold claims it, so the payment's
+/// withdrawal leaves the hold alone (see `payments::withdraw_funds`). Returns
+/// `None` if another pending refund already claimed the hold, or if a call
+/// made for the payment timed out and isn't resolved yet.
+async fn claim_funds(
+    tx: &mut Transaction<'_, Postgres>,
+    payment: &LockedPayment,
+    refund_id: Uuid,
+    amount: i32,
+) -> Result<Option<Mechanism>, sqlx::Error> {
+    let record = sqlx::query!(
+        r#"
+          SELECT
+            EXISTS (
+              SELECT 1 FROM refunds
+              WHERE payment_id = $1 AND status = 'Pending'
+                AND mechanism IN ('ReleaseHold', 'ReducedCapture')
+            ) AS "hold_claimed!",
+            EXISTS (
+              SELECT 1 FROM timed_out_calls
+              WHERE payment_id = $1 AND resolved_at IS NULL
+            ) AS "timed_out!"
+        "#,
+        payment.id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if record.timed_out {
+        return Ok(None);
+    }
+
+    let mechanism = match (payment.hold_id, payment.hold_status) {
+        (Some(_), Some(HoldStatus::Placed)) => {
+            if record.hold_claimed {
+                return Ok(None);
+            }
+
+            if amount == payment.amount {
+                Mechanism::ReleaseHold
+            } else {
+                Mechanism::ReducedCapture
+            }
+        }
+        _ => Mechanism::Credit,
+    };
+
+    sqlx::query!(
+        r#"UPDATE refunds SET mechanism = $2 WHERE id = $1"#,
+        refund_id,
+        mechanism as Mechanism
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(Some(mechanism))
+}
+
+/// A pending refund, whose money is yet to be given back.
+struct Claim {
+    payment: LockedPayment,
+    refund_id: Uuid,
+    amount: i32,
+    mechanism: Mechanism,
+}
+
+/// Asks the account service to give the money of a pending refund back, then
+/// completes the refund, or abandons it if the account service failed. If the
+/// account service timed out, the money may have been given back all the
+/// same: the refund stays pending until the call is resolved (see
+/// `timeouts::resolve`).
+///
+/// No row is locked meanwhile. The inner result holds the account service's
+/// error, if any.
+async fn execute<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    claim: &Claim,
+) -> Result<Result<(), String>, sqlx::Error> {
+    let mut attempts = Attempts::default();
+    let given_back = give_back(&mut attempts, account_service, claim).await;
+    let timed_out = attempts.timed_out();
+    attempts.record(pool).await;
+
+    match (given_back, timed_out) {
+        (Ok(()), _) => complete(pool, claim).await.map(Ok),
+        (Err(error), Some(call)) => {
+            timeouts::record(pool, call).await?;
+            Ok(Err(error))
+        }
+        (Err(error), None) => {
+            abandon(pool, account_service, claim).await?;
+            Ok(Err(error))
+        }
+    }
+}
+
+/// Resolves a pending refund whose account service call timed out (see
+/// `timeouts::resolve`): it completes if the call went through, and is
+/// abandoned otherwise.
+pub(crate) async fn resolve<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    refund_id: Uuid,
+    went_through: bool,
+) -> Result<(), sqlx::Error> {
+    let refund = get(pool, refund_id).await?;
+    let (Status::Pending, Some(mechanism)) = (refund.status, refund.mechanism) else {
+        return Ok(());
+    };
+
+    let mut tx = pool.begin().await?;
+    let payment = lock_payment(&mut tx, refund.payment_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    tx.rollback().await?;
+
+    let claim = Claim {
+        payment,
+        refund_id,
+        amount: refund.amount,
+        mechanism,
+    };
+    if went_through {
+        complete(pool, &claim).await
+    } else {
+        abandon(pool, account_service, &claim).await
+    }
+}
+
+/// Makes the account service call matching the mechanism of a pending refund.
+async fn give_back<T: AccountService>(
+    attempts: &mut Attempts,
+    account_service: &T,
+    claim: &Claim,
+) -> Result<(), String> {
+    let payment = &claim.payment;
+    let hold_ref = || HoldRef::new(payment.hold_id.expect("claimed holds are placed"));
+
+    match claim.mechanism {
+        Mechanism::ReleaseHold => {
+            let call = Call::new(payment.id, Operation::ReleaseHold)
+                .with_refund(claim.refund_id)
+                .with_hold(hold_ref());
+            attempts
+                .make(call, account_service.release_hold(hold_ref()))
+                .await
+        }
+        Mechanism::ReducedCapture => {
+            let captured_amount = payment.amount - claim.amount;
+            let call = Call::new(payment.id, Operation::CaptureFunds)
+                .with_refund(claim.refund_id)
+                .with_amount(captured_amount)
+                .with_hold(hold_ref());
+            attempts
+                .make(
+                    call,
+                    account_service.capture_funds(hold_ref(), captured_amount),
+                )
+                .await
+        }
+        Mechanism::Credit => {
+            let card = Card::try_from(payment.card_number.clone())
+                .expect("persisted card numbers are valid");
+            let call = Call::new(payment.id, Operation::CreditFunds)
+                .with_refund(claim.refund_id)
+                .with_amount(claim.amount);
+            attempts
+                .make(
+                    call,
+                    account_service.credit_funds(card.account_number(), claim.amount),
+                )
+                .await
+        }
+    }
+}
+
+/// Makes a pending refund effective once its money was given back, recording
+/// the movement in the ledger and on the payment's hold.
+///
+/// The payment row is locked again meanwhile, so the fee given back and the
+/// payment's `refund_state` account for the refunds completing concurrently.
+async fn complete(pool: &PgPool, claim: &Claim) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let payment = lock_payment(&mut tx, claim.payment.id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    let hold_status = match claim.mechanism {
+        Mechanism::ReleaseHold => {
+            ledger::hold_released(&mut tx, payment.id, Some(claim.refund_id), payment.amount)
+                .await?;
+            Some(HoldStatus::Released)
+        }
+        Mechanism::ReducedCapture => {
+            ledger::funds_captured(
+                &mut tx,
+                payment.id,
+                Some(claim.refund_id),
+                payment.amount,
+                payment.amount - claim.amount,
+            )
+            .await?;
+            Some(HoldStatus::Withdrawn)
+        }
+        Mechanism::Credit => {
+            ledger::refunded(&mut tx, payment.id, claim.refund_id, claim.amount).await?;
+            payment.hold_status
+        }
+    };
+
+    sqlx::query!(
+        r#"UPDATE payments SET hold_status = $2 WHERE id = $1"#,
+        payment.id,
+        hold_status as Option<HoldStatus>
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE refunds SET status = 'Succeeded', updated_at = current_timestamp WHERE id = $1"#,
+        claim.refund_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    reverse_fee(&mut tx, payment.id, claim.refund_id).await?;
+    let refund_state = sync_refund_state(&mut tx, payment.id).await?;
+
+    tx.commit().await?;
+
+    log_refund_state_change(payment.id, payment.refund_state, refund_state);
+
+    metrics().refund(Status::Succeeded, claim.amount);
+    Ok(())
+}
+
+/// Gives up on a pending refund whose money the account service failed to give
+/// back: a refund being approved goes back to awaiting approval, any other is
+/// dropped.
+///
+/// If the refund claimed the payment's hold, the funds are then withdrawn like
+/// they would have been without it.
+async fn abandon<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    claim: &Claim,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+          UPDATE refunds
+          SET status = 'AwaitingApproval', mechanism = NULL, reviewed_by = NULL,
+            reviewed_at = NULL, updated_at = current_timestamp
+          WHERE id = $1 AND status = 'Pending' AND reviewed_by IS NOT NULL
+        "#,
+        claim.refund_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"DELETE FROM refunds WHERE id = $1 AND status = 'Pending'"#,
+        claim.refund_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    metrics().refund_failed();
+
+    if claim.mechanism != Mechanism::Credit {
+        let payment_id = claim.payment.id;
+        if let Err(error) = payments::withdraw_funds(pool, account_service, payment_id).await? {
+            tracing::error!(%payment_id, %error, "failed to withdraw funds after a refund failed");
+        }
+    }
+
+    Ok(())
+}
+
+/// Gives back to the merchant the part of the payment's fee matching a refund
+/// that just succeeded (see `fees::reversal`).
+async fn reverse_fee(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+    refund_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let record = sqlx::query!(
+        r#"
+          SELECT p.fee_amount, p.amount, r.amount AS refund_amount,
+            COALESCE((
+              SELECT SUM(amount) FROM refunds
+              WHERE payment_id = p.id AND status = 'Succeeded' AND id <> r.id
+            ), 0)::integer AS "refunded_amount!"
+          FROM payments p
+          JOIN refunds r ON r.payment_id = p.id
+          WHERE p.id = $1 AND r.id = $2
+        "#,
+        payment_id,
+        refund_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let fee_amount = fees::reversal(
+        record.fee_amount,
+        record.amount,
+        record.refunded_amount,
+        record.refund_amount,
+    );
+
+    sqlx::query!(
+        r#"UPDATE refunds SET fee_amount = $2 WHERE id = $1"#,
+        refund_id,
+        fee_amount
+    )
+    .execute(&mut *tx)
+    .await
+    .map(|_| ())
+}
+
+/// Recomputes the refund state of a payment from its succeeded refunds.
+async fn sync_refund_state(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: Uuid,
+) -> Result<RefundState, sqlx::Error> {
+    sqlx::query!(
+        r#"
+          UPDATE payments p
+          SET refund_state = CASE
+                WHEN r.refunded_amount IS NULL THEN 'None'
+                WHEN r.refunded_amount >= p.amount THEN 'Full'
+                ELSE 'Partial'
+              END::RefundState,
+              updated_at = current_timestamp
+          FROM (
+            SELECT SUM(amount) AS refunded_amount FROM refunds
+            WHERE payment_id = $1 AND status = 'Succeeded'
+          ) r
+          WHERE p.id = $1
+          RETURNING p.refund_state as "refund_state: RefundState"
+        "#,
+        payment_id
+    )
+    .fetch_one(tx)
     .await
-    .map(|record| record.map(|r| r.id))
+    .map(|record| record.refund_state)
+}
+
+fn log_refund_state_change(
+    payment_id: Uuid,
+    previous_state: RefundState,
+    refund_state: RefundState,
+) {
+    if previous_state != refund_state {
+        tracing::info!(
+            %payment_id,
+            ?previous_state,
+            ?refund_state,
+            "payment refund state changed"
+        );
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
 
     use super::*;
-    use crate::bank::payments::Payment;
+    use crate::bank::{
+        accounts::DummyService,
+        payments::{self, Payment},
+    };
 
     pub const REFUND_AMOUNT: i32 = 42;
+    pub const REFUND_REASON: Reason = Reason::RequestedByCustomer;
 
     impl Refund {
         pub async fn new_test(pool: &PgPool) -> Result<Refund, sqlx::Error> {
             let payment = Payment::new_test(pool).await?;
 
-            let id = insert(pool, payment.id, REFUND_AMOUNT).await?;
+            let id = insert(pool, payment.id, REFUND_AMOUNT, REFUND_REASON).await?;
 
             get(pool, id).await
         }
@@ -112,5 +1044,481 @@ pub mod tests {
             .expect("failed to create refund");
 
         assert_eq!(refund.amount, REFUND_AMOUNT);
+        assert_eq!(refund.reason, REFUND_REASON);
+    }
+
+    #[tokio::test]
+    async fn test_balance() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let refund = Refund::new_test(&pool)
+            .await
+            .expect("failed to create refund");
+        insert(&pool, refund.payment_id, REFUND_AMOUNT, REFUND_REASON)
+            .await
+            .expect("failed to create refund");
+
+        let refunds = list(&pool, refund.payment_id)
+            .await
+            .expect("failed to list refunds");
+        assert_eq!(refunds.len(), 2);
+
+        let balance = balance(&pool, refund.payment_id)
+            .await
+            .expect("failed to compute balance");
+        assert_eq!(balance.refunded_amount, 2 * REFUND_AMOUNT);
+        assert_eq!(
+            balance.refundable_amount,
+            crate::bank::payments::tests::PAYMENT_AMOUNT - 2 * REFUND_AMOUNT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checked_insert_updates_refund_state() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = Payment::new_test(&pool)
+            .await
+            .expect("failed to create payment");
+
+        let account_service = DummyService::default();
+
+        let outcome = checked_insert(
+            &pool,
+            &account_service,
+            &Policy::default(),
+            payment.id,
+            REFUND_AMOUNT,
+            Some(REFUND_REASON),
+            None,
+        )
+        .await
+        .expect("failed to create refund");
+        assert!(matches!(outcome, Outcome::Refunded(_)));
+        let state = payments::get(&pool, payment.id).await.unwrap().refund_state;
+        assert_eq!(state, RefundState::Partial);
+
+        let outcome = checked_insert(
+            &pool,
+            &account_service,
+            &Policy::default(),
+            payment.id,
+            payment.amount,
+            Some(REFUND_REASON),
+            None,
+        )
+        .await
+        .expect("failed to create refund");
+        assert_eq!(outcome, Outcome::ExcessiveAmount);
+        let state = payments::get(&pool, payment.id).await.unwrap().refund_state;
+        assert_eq!(state, RefundState::Partial);
+
+        let outcome = checked_insert(
+            &pool,
+            &account_service,
+            &Policy::default(),
+            payment.id,
+            payment.amount - REFUND_AMOUNT,
+            Some(REFUND_REASON),
+            None,
+        )
+        .await
+        .expect("failed to create refund");
+        assert!(matches!(outcome, Outcome::Refunded(_)));
+        let state = payments::get(&pool, payment.id).await.unwrap().refund_state;
+        assert_eq!(state, RefundState::Full);
+    }
+
+    #[tokio::test]
+    async fn test_approval() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = Payment::new_test(&pool)
+            .await
+            .expect("failed to create payment");
+
+        let first = insert_awaiting_approval(
+            &pool,
+            &Policy::default(),
+            payment.id,
+            payment.amount,
+            Some(REFUND_REASON),
+            "maker",
+        )
+        .await
+        .expect("failed to create refund")
+        .unwrap();
+        let second = insert_awaiting_approval(
+            &pool,
+            &Policy::default(),
+            payment.id,
+            payment.amount,
+            Some(REFUND_REASON),
+            "maker",
+        )
+        .await
+        .expect("failed to create refund")
+        .unwrap();
+
+        // refunds awaiting approval aren't effective yet
+        let balance = balance(&pool, payment.id).await.unwrap();
+        assert_eq!(balance.refunded_amount, 0);
+
+        let account_service = DummyService::default();
+        let approval = approve(&pool, &account_service, first, "checker")
+            .await
+            .unwrap();
+        assert_eq!(approval, Approval::Approved);
+        let approval = approve(&pool, &account_service, first, "checker")
+            .await
+            .unwrap();
+        assert_eq!(approval, Approval::AlreadyReviewed);
+
+        let refund = get(&pool, first).await.unwrap();
+        assert_eq!(refund.status, Status::Succeeded);
+        assert_eq!(refund.requested_by.as_deref(), Some("maker"));
+        assert_eq!(refund.reviewed_by.as_deref(), Some("checker"));
+        assert!(refund.reviewed_at.is_some());
+        let state = payments::get(&pool, payment.id).await.unwrap().refund_state;
+        assert_eq!(state, RefundState::Full);
+
+        let approval = approve(&pool, &account_service, second, "checker")
+            .await
+            .unwrap();
+        assert_eq!(approval, Approval::ExcessiveAmount);
+        assert!(reject(&pool, second, "checker").await.unwrap());
+        assert!(!reject(&pool, second, "checker").await.unwrap());
+        assert_eq!(get(&pool, second).await.unwrap().status, Status::Rejected);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_refunds_comply_with_policy() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let policy = Policy {
+            max_refunds_per_payment: Some(1),
+            ..Policy::default()
+        };
+        let account_service = DummyService::default();
+
+        let payment = Payment::new_test(&pool).await.unwrap();
+        let (outcome_a, outcome_b) = tokio::join!(
+            checked_insert(
+                &pool,
+                &account_service,
+                &policy,
+                payment.id,
+                REFUND_AMOUNT,
+                Some(REFUND_REASON),
+                None
+            ),
+            insert_awaiting_approval(
+                &pool,
+                &policy,
+                payment.id,
+                REFUND_AMOUNT,
+                Some(REFUND_REASON),
+                "maker"
+            ),
+        );
+
+        let refunded = match outcome_a.unwrap() {
+            Outcome::Refunded(_) => Ok(()),
+            Outcome::Violation(violation) => Err(violation),
+            outcome => panic!("unexpected outcome {outcome:?}"),
+        };
+        let awaiting_approval = outcome_b.unwrap().map(|_| ());
+
+        let mut outcomes = [refunded, awaiting_approval];
+        outcomes.sort_by_key(Result::is_err);
+        assert_eq!(outcomes, [Ok(()), Err(PolicyViolation::TooManyRefunds)]);
+        assert_eq!(list(&pool, payment.id).await.unwrap().len(), 1);
+    }
+
+    async fn refund_held_payment(pool: &PgPool, amounts: &[i32]) -> (Payment, Vec<Refund>) {
+        let payment = Payment::new_test(pool)
+            .await
+            .expect("failed to create payment");
+        let hold_ref = HoldRef::new(Uuid::new_v4());
+        payments::record_hold(pool, payment.id, hold_ref)
+            .await
+            .expect("failed to record hold");
+
+        let mut refunds = vec![];
+        for amount in amounts {
+            let outcome = checked_insert(
+                pool,
+                &DummyService::default(),
+                &Policy::default(),
+                payment.id,
+                *amount,
+                Some(REFUND_REASON),
+                None,
+            )
+            .await
+            .expect("failed to create refund");
+            let Outcome::Refunded(refund_id) = outcome else {
+                panic!("refund should be accepted, got {outcome:?}");
+            };
+            refunds.push(get(pool, refund_id).await.unwrap());
+        }
+
+        let payment = payments::get(pool, payment.id).await.unwrap();
+        (payment, refunds)
+    }
+
+    /// Lists the refunds the ledger transactions of a payment were made for,
+    /// leaving out the hold placed for it.
+    async fn ledger_refund_ids(pool: &PgPool, payment_id: Uuid) -> Vec<Option<Uuid>> {
+        sqlx::query!(
+            r#"
+              SELECT refund_id FROM ledger_transactions
+              WHERE payment_id = $1 AND kind <> 'HoldPlaced'
+              ORDER BY inserted_at, kind
+            "#,
+            payment_id
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.refund_id)
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn test_refund_mechanism() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        // the whole amount is still held: release the hold
+        let (payment, refunds) =
+            refund_held_payment(&pool, &[payments::tests::PAYMENT_AMOUNT]).await;
+        assert_eq!(refunds[0].mechanism, Some(Mechanism::ReleaseHold));
+        assert_eq!(payment.hold_status, Some(HoldStatus::Released));
+        assert_eq!(
+            ledger_refund_ids(&pool, payment.id).await,
+            [Some(refunds[0].id)]
+        );
+
+        // part of the amount is still held: only capture the rest, then credit
+        let (payment, refunds) = refund_held_payment(&pool, &[REFUND_AMOUNT, REFUND_AMOUNT]).await;
+        assert_eq!(refunds[0].mechanism, Some(Mechanism::ReducedCapture));
+        assert_eq!(refunds[1].mechanism, Some(Mechanism::Credit));
+        assert_eq!(payment.hold_status, Some(HoldStatus::Withdrawn));
+        assert_eq!(
+            ledger_refund_ids(&pool, payment.id).await,
+            [
+                Some(refunds[0].id),
+                Some(refunds[1].id),
+                Some(refunds[1].id)
+            ]
+        );
+
+        // withdrawing the funds afterwards has nothing left to do
+        let withdrawal = payments::withdraw_funds(&pool, &DummyService::default(), payment.id)
+            .await
+            .expect("failed to withdraw funds");
+        assert_eq!(withdrawal, Ok(()));
+    }
+
+    /// Fails to give any money back, but withdraws funds like `DummyService`.
+    #[derive(Clone)]
+    struct FailingService;
+
+    #[async_trait::async_trait]
+    impl AccountService for FailingService {
+        async fn place_hold(&self, account_number: &str, amount: i32) -> Result<HoldRef, String> {
+            DummyService::default()
+                .place_hold(account_number, amount)
+                .await
+        }
+
+        async fn release_hold(&self, _hold_ref: HoldRef) -> Result<(), String> {
+            Err("unavailable".into())
+        }
+
+        async fn withdraw_funds(&self, hold_ref: HoldRef) -> Result<(), String> {
+            DummyService::default().withdraw_funds(hold_ref).await
+        }
+
+        async fn capture_funds(&self, _hold_ref: HoldRef, _amount: i32) -> Result<(), String> {
+            Err("unavailable".into())
+        }
+
+        async fn credit_funds(&self, _account_number: &str, _amount: i32) -> Result<(), String> {
+            Err("unavailable".into())
+        }
+    }
+
+    async fn held_payment(pool: &PgPool) -> Payment {
+        let payment = Payment::new_test(pool)
+            .await
+            .expect("failed to create payment");
+        payments::record_hold(pool, payment.id, HoldRef::new(Uuid::new_v4()))
+            .await
+            .expect("failed to record hold");
+        payment
+    }
+
+    #[tokio::test]
+    async fn test_failed_refund_is_abandoned() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = held_payment(&pool).await;
+        let outcome = checked_insert(
+            &pool,
+            &FailingService,
+            &Policy::default(),
+            payment.id,
+            REFUND_AMOUNT,
+            Some(REFUND_REASON),
+            None,
+        )
+        .await
+        .expect("failed to create refund");
+        assert_eq!(outcome, Outcome::Failed("unavailable".into()));
+        assert!(list(&pool, payment.id).await.unwrap().is_empty());
+        // the hold the refund would have captured is withdrawn instead
+        let payment = payments::get(&pool, payment.id).await.unwrap();
+        assert_eq!(payment.hold_status, Some(HoldStatus::Withdrawn));
+        assert_eq!(payment.refund_state, RefundState::None);
+
+        let payment = held_payment(&pool).await;
+        let refund_id = insert_awaiting_approval(
+            &pool,
+            &Policy::default(),
+            payment.id,
+            payment.amount,
+            Some(REFUND_REASON),
+            "maker",
+        )
+        .await
+        .expect("failed to create refund")
+        .unwrap();
+        let approval = approve(&pool, &FailingService, refund_id, "checker")
+            .await
+            .unwrap();
+        assert_eq!(approval, Approval::Failed("unavailable".into()));
+        let refund = get(&pool, refund_id).await.unwrap();
+        assert_eq!(refund.status, Status::AwaitingApproval);
+        assert_eq!(refund.mechanism, None);
+        assert_eq!(refund.reviewed_by, None);
+
+        // the funds were withdrawn meanwhile, so they're credited back
+        let approval = approve(&pool, &DummyService::default(), refund_id, "checker")
+            .await
+            .unwrap();
+        assert_eq!(approval, Approval::Approved);
+        let refund = get(&pool, refund_id).await.unwrap();
+        assert_eq!(refund.status, Status::Succeeded);
+        assert_eq!(refund.mechanism, Some(Mechanism::Credit));
+    }
+
+    #[tokio::test]
+    async fn test_pending_refund_claims_hold() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let payment = held_payment(&pool).await;
+        sqlx::query!(
+            r#"
+              INSERT INTO refunds ( payment_id, amount, reason, status, mechanism )
+              VALUES ( $1, $2, $3, 'Pending', 'ReducedCapture' )
+            "#,
+            payment.id,
+            REFUND_AMOUNT,
+            REFUND_REASON as Reason
+        )
+        .execute(&pool)
+        .await
+        .expect("failed to create refund");
+
+        // the pending refund counts against the payment amount
+        let balance = balance(&pool, payment.id).await.unwrap();
+        assert_eq!(balance.refunded_amount, 0);
+        assert_eq!(balance.refundable_amount, payment.amount - REFUND_AMOUNT);
+
+        let outcome = checked_insert(
+            &pool,
+            &DummyService::default(),
+            &Policy::default(),
+            payment.id,
+            REFUND_AMOUNT,
+            Some(REFUND_REASON),
+            None,
+        )
+        .await
+        .expect("failed to create refund");
+        assert_eq!(outcome, Outcome::InProgress);
+
+        // the pending refund takes care of the hold
+        let withdrawal = payments::withdraw_funds(&pool, &DummyService::default(), payment.id)
+            .await
+            .expect("failed to withdraw funds");
+        assert_eq!(withdrawal, Ok(()));
+        let payment = payments::get(&pool, payment.id).await.unwrap();
+        assert_eq!(payment.hold_status, Some(HoldStatus::Placed));
+    }
+
+    fn days_ago(days: i64) -> PrimitiveDateTime {
+        let paid_at = OffsetDateTime::now_utc() - Duration::days(days);
+        PrimitiveDateTime::new(paid_at.date(), paid_at.time())
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = Policy {
+            max_payment_age_days: Some(30),
+            min_amount: 10,
+            max_refunds_per_payment: Some(2),
+            approval_threshold: Some(100),
+        };
+        assert!(!policy.requires_approval(100));
+        assert!(policy.requires_approval(101));
+        let now = OffsetDateTime::now_utc();
+        let paid_at = days_ago(1);
+
+        assert_eq!(
+            policy.check(paid_at, 0, REFUND_AMOUNT, Some(REFUND_REASON), now),
+            Ok(REFUND_REASON)
+        );
+        assert_eq!(
+            policy.check(paid_at, 0, REFUND_AMOUNT, None, now),
+            Err(PolicyViolation::MissingReason)
+        );
+        assert_eq!(
+            policy.check(paid_at, 0, 9, Some(REFUND_REASON), now),
+            Err(PolicyViolation::AmountBelowMinimum)
+        );
+        assert_eq!(
+            policy.check(paid_at, 2, REFUND_AMOUNT, Some(REFUND_REASON), now),
+            Err(PolicyViolation::TooManyRefunds)
+        );
+        assert_eq!(
+            policy.check(days_ago(31), 0, REFUND_AMOUNT, Some(REFUND_REASON), now),
+            Err(PolicyViolation::PaymentTooOld)
+        );
+
+        let unlimited = Policy {
+            max_payment_age_days: None,
+            max_refunds_per_payment: None,
+            ..policy
+        };
+        assert_eq!(
+            unlimited.check(days_ago(3650), 100, REFUND_AMOUNT, Some(REFUND_REASON), now),
+            Ok(REFUND_REASON)
+        );
     }
 }
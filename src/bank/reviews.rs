@@ -0,0 +1,443 @@
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::bank::{
+    accounts::{AccountService, HoldRef},
+    attempts::{Attempts, Call},
+    ledger,
+    payments::{self, DeclineCode, HoldStatus, Payment, Reversal},
+    reconciliation::Operation,
+    timeouts,
+};
+use crate::metrics::metrics;
+use crate::shutdown::Shutdown;
+
+/// How long payments may wait for review before they're rejected, unless set
+/// otherwise.
+pub const DEFAULT_REVIEW_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often payments past their review deadline are looked for.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Review {
+    Approved,
+    Rejected,
+    /// The payment was already reviewed, or never required a review.
+    AlreadyReviewed,
+    /// The account service failed to withdraw or release the held funds, with
+    /// the given error: the payment still awaits review.
+    Failed(String),
+    /// The account service timed out: the review is decided, but only takes
+    /// effect once the call is resolved (see `timeouts::resolve`). Meanwhile,
+    /// the payment leaves the review queue.
+    TimedOut,
+}
+
+/// Puts a payment whose funds are held in the review queue, until
+/// `review_period` from now.
+pub async fn require(pool: &PgPool, id: Uuid, review_period: Duration) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            UPDATE payments
+            SET status = 'RequiresReview',
+                review_deadline_at = LOCALTIMESTAMP + make_interval(secs => $2)
+            WHERE id = $1
+        "#,
+        id,
+        review_period.as_secs_f64()
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+/// Lists the payments awaiting review, the closest to their deadline first.
+///
+/// Payments whose review was decided, but whose account service call timed
+/// out, aren't awaiting review anymore.
+pub async fn queue(pool: &PgPool) -> Result<Vec<Payment>, sqlx::Error> {
+    sqlx::query_as!(
+        Payment,
+        r#"
+            SELECT id, amount, card_number, merchant_id, inserted_at, updated_at, status as "status: _", fee_amount, refund_state as "refund_state: _",
+              hold_id, hold_status as "hold_status: _", review_deadline_at, reviewed_by, reviewed_at,
+              decline_code as "decline_code: _", failure_reason as "failure_reason: _"
+            FROM payments
+            WHERE status = 'RequiresReview' AND reviewed_at IS NULL
+            ORDER BY review_deadline_at, id
+            LIMIT $1
+        "#,
+        payments::LIST_LIMIT
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Locks a payment awaiting review until the end of the transaction, so it's
+/// reviewed only once. Returns the hold placed for it.
+async fn lock_awaiting_review(
+    tx: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<Option<(i32, Uuid)>, sqlx::Error> {
+    let record = sqlx::query!(
+        r#"
+            SELECT amount, hold_id
+            FROM payments
+            WHERE id = $1 AND status = 'RequiresReview' AND reviewed_at IS NULL
+              AND hold_status = 'Placed'
+            FOR UPDATE
+        "#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    Ok(record.and_then(|record| Some((record.amount, record.hold_id?))))
+}
+
+/// Approves a payment awaiting review, withdrawing its held funds and charging
+/// the merchant their fee.
+pub async fn approve<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    id: Uuid,
+    approved_by: &str,
+) -> Result<Review, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    if lock_awaiting_review(&mut tx, id).await?.is_none() {
+        return Ok(Review::AlreadyReviewed);
+    }
+
+    let mut attempts = Attempts::default();
+    let withdrawal =
+        payments::withdraw_held_funds(&mut tx, &mut attempts, account_service, id).await?;
+    if let Some(call) = attempts.timed_out() {
+        decide(&mut tx, id, Some(approved_by), call).await?;
+        tx.commit().await?;
+        attempts.record(pool).await;
+        return Ok(Review::TimedOut);
+    }
+    if let Err(error) = withdrawal {
+        tx.rollback().await?;
+        attempts.record(pool).await;
+        return Ok(Review::Failed(error));
+    }
+
+    record_approval(&mut tx, id, Some(approved_by)).await?;
+
+    tx.commit().await?;
+    attempts.record(pool).await;
+
+    metrics().payment_approved();
+    Ok(Review::Approved)
+}
+
+/// Rejects a payment awaiting review, releasing its held funds.
+///
+/// `rejected_by` is `None` when the payment is rejected because nobody
+/// reviewed it before its deadline.
+pub async fn reject<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    id: Uuid,
+    rejected_by: Option<&str>,
+) -> Result<Review, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let Some((amount, hold_id)) = lock_awaiting_review(&mut tx, id).await? else {
+        return Ok(Review::AlreadyReviewed);
+    };
+
+    let hold_ref = HoldRef::new(hold_id);
+    let call = Call::new(id, Operation::ReleaseHold).with_hold(hold_ref);
+    let mut attempts = Attempts::default();
+    let released = attempts
+        .make(call, account_service.release_hold(hold_ref))
+        .await;
+    if let Some(call) = attempts.timed_out() {
+        decide(&mut tx, id, rejected_by, call).await?;
+        tx.commit().await?;
+        attempts.record(pool).await;
+        return Ok(Review::TimedOut);
+    }
+    if let Err(error) = released {
+        tx.rollback().await?;
+        attempts.record(pool).await;
+        return Ok(Review::Failed(error));
+    }
+
+    let decline_code = record_rejection(&mut tx, id, amount, rejected_by).await?;
+
+    tx.commit().await?;
+    attempts.record(pool).await;
+
+    metrics().payment_reversed(Reversal::Declined(decline_code));
+    Ok(Review::Rejected)
+}
+
+/// Rejects the payments past their review deadline, returning how many were.
+pub async fn reject_expired<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+) -> Result<usize, sqlx::Error> {
+    let ids = sqlx::query!(
+        r#"
+            SELECT id
+            FROM payments
+            WHERE status = 'RequiresReview' AND reviewed_at IS NULL
+              AND review_deadline_at <= LOCALTIMESTAMP
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut rejected = 0;
+    for record in ids {
+        match reject(pool, account_service, record.id, None).await? {
+            Review::Rejected => rejected += 1,
+            Review::Failed(error) => {
+                tracing::error!(payment_id = %record.id, %error, "failed to reject expired payment")
+            }
+            Review::TimedOut => {
+                tracing::warn!(payment_id = %record.id, "timed out rejecting expired payment")
+            }
+            _ => {}
+        }
+    }
+
+    Ok(rejected)
+}
+
+/// Records the approval of a payment whose held funds were withdrawn, charging
+/// the merchant their fee.
+async fn record_approval(
+    tx: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    approved_by: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    // the fee schedule may have changed while the payment awaited review
+    let fee_amount = payments::fee(tx, id).await?;
+
+    sqlx::query!(
+        r#"
+            UPDATE payments
+            SET status = 'Approved', fee_amount = $2, reviewed_by = $3,
+              reviewed_at = COALESCE(reviewed_at, current_timestamp)
+            WHERE id = $1
+        "#,
+        id,
+        fee_amount,
+        approved_by
+    )
+    .execute(&mut *tx)
+    .await
+    .map(|_| ())
+}
+
+/// Records the rejection of a payment whose held funds were released, and
+/// returns why it's declined.
+async fn record_rejection(
+    tx: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    amount: i32,
+    rejected_by: Option<&str>,
+) -> Result<DeclineCode, sqlx::Error> {
+    let decline_code = match rejected_by {
+        Some(_) => DeclineCode::ReviewRejected,
+        None => DeclineCode::ReviewExpired,
+    };
+
+    sqlx::query!(
+        r#"
+            UPDATE payments
+            SET status = 'Declined', decline_code = $4, hold_status = $2, reviewed_by = $3,
+              reviewed_at = COALESCE(reviewed_at, current_timestamp)
+            WHERE id = $1
+        "#,
+        id,
+        HoldStatus::Released as HoldStatus,
+        rejected_by,
+        decline_code as DeclineCode
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    ledger::hold_released(tx, id, None, amount).await?;
+
+    Ok(decline_code)
+}
+
+/// Records the decision of a review whose account service call timed out, to
+/// be resolved later.
+async fn decide(
+    tx: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    reviewed_by: Option<&str>,
+    call: Call,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE payments SET reviewed_by = $2, reviewed_at = current_timestamp WHERE id = $1"#,
+        id,
+        reviewed_by
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    timeouts::record(&mut *tx, call).await
+}
+
+/// Resolves a review whose account service call timed out (see
+/// `timeouts::resolve`): if the call went through, the review takes effect;
+/// otherwise, the payment goes back to the review queue.
+pub(crate) async fn resolve(
+    pool: &PgPool,
+    id: Uuid,
+    operation: Operation,
+    went_through: bool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let record = sqlx::query!(
+        r#"
+            SELECT amount, reviewed_by
+            FROM payments
+            WHERE id = $1 AND status = 'RequiresReview' AND reviewed_at IS NOT NULL
+            FOR UPDATE
+        "#,
+        id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+    let Some(record) = record else {
+        return Ok(());
+    };
+
+    if !went_through {
+        sqlx::query!(
+            r#"UPDATE payments SET reviewed_by = NULL, reviewed_at = NULL WHERE id = $1"#,
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+        return tx.commit().await;
+    }
+
+    if operation == Operation::WithdrawFunds {
+        payments::record_withdrawal(&mut tx, id, record.amount).await?;
+        record_approval(&mut tx, id, record.reviewed_by.as_deref()).await?;
+        tx.commit().await?;
+        metrics().payment_approved();
+    } else {
+        let decline_code =
+            record_rejection(&mut tx, id, record.amount, record.reviewed_by.as_deref()).await?;
+        tx.commit().await?;
+        metrics().payment_reversed(Reversal::Declined(decline_code));
+    }
+
+    Ok(())
+}
+
+/// Rejects the payments past their review deadline as they expire, until the
+/// shutdown. A pass in progress is completed before stopping.
+pub async fn run_expiry<T: AccountService>(pool: PgPool, account_service: T, shutdown: Shutdown) {
+    loop {
+        match reject_expired(&pool, &account_service).await {
+            Ok(0) => {}
+            Ok(rejected) => tracing::info!(rejected, "rejected expired payment reviews"),
+            Err(error) => tracing::error!(%error, "failed to reject expired payment reviews"),
+        }
+
+        tokio::select! {
+            () = tokio::time::sleep(EXPIRY_INTERVAL) => {}
+            () = shutdown.triggered() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::{
+        accounts::DummyService,
+        payment_instruments::Card,
+        payments::{Status, DEFAULT_MERCHANT_ID},
+    };
+
+    /// Makes a payment awaiting review until `review_period` from now.
+    pub async fn payment_awaiting_review<T: AccountService>(
+        pool: &PgPool,
+        account_service: &T,
+        review_period: Duration,
+    ) -> Uuid {
+        let card = Card::new_test();
+        let hold_ref = account_service
+            .place_hold(card.account_number(), payments::tests::PAYMENT_AMOUNT)
+            .await
+            .unwrap();
+        let id = payments::insert(
+            pool,
+            payments::tests::PAYMENT_AMOUNT,
+            card.into(),
+            DEFAULT_MERCHANT_ID,
+            Status::Processing,
+        )
+        .await
+        .unwrap();
+        payments::record_hold(pool, id, hold_ref).await.unwrap();
+        require(pool, id, review_period).await.unwrap();
+
+        id
+    }
+
+    #[tokio::test]
+    async fn test_approve() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let account_service = DummyService::default();
+        let id = payment_awaiting_review(&pool, &account_service, DEFAULT_REVIEW_PERIOD).await;
+
+        let queue = queue(&pool).await.unwrap();
+        assert!(queue.iter().any(|payment| payment.id == id));
+
+        let review = approve(&pool, &account_service, id, "alice").await.unwrap();
+        assert_eq!(review, Review::Approved);
+
+        let payment = payments::get(&pool, id).await.unwrap();
+        assert_eq!(payment.status, Status::Approved);
+        assert_eq!(payment.hold_status, Some(HoldStatus::Withdrawn));
+        assert_eq!(payment.reviewed_by.as_deref(), Some("alice"));
+
+        let review = reject(&pool, &account_service, id, Some("bob"))
+            .await
+            .unwrap();
+        assert_eq!(review, Review::AlreadyReviewed);
+    }
+
+    #[tokio::test]
+    async fn test_reject_expired() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let account_service = DummyService::default();
+        let expired = payment_awaiting_review(&pool, &account_service, Duration::ZERO).await;
+        let pending = payment_awaiting_review(&pool, &account_service, DEFAULT_REVIEW_PERIOD).await;
+
+        assert!(reject_expired(&pool, &account_service).await.unwrap() >= 1);
+
+        let payment = payments::get(&pool, expired).await.unwrap();
+        assert_eq!(payment.status, Status::Declined);
+        assert_eq!(payment.decline_code, Some(DeclineCode::ReviewExpired));
+        assert_eq!(payment.hold_status, Some(HoldStatus::Released));
+        assert_eq!(payment.reviewed_by, None);
+        assert!(payment.reviewed_at.is_some());
+
+        let payment = payments::get(&pool, pending).await.unwrap();
+        assert_eq!(payment.status, Status::RequiresReview);
+    }
+}
@@ -0,0 +1,380 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "RiskOutcome")]
+pub enum Outcome {
+    /// The payment may proceed.
+    Allow,
+    /// The payment must be declined, without placing any hold.
+    Decline,
+    /// The payment should be reviewed by a human.
+    Review,
+}
+
+/// What a rule does with the payments it matches, unless it's an allowlist.
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Decline,
+    Review,
+}
+
+impl From<Action> for Outcome {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Decline => Outcome::Decline,
+            Action::Review => Outcome::Review,
+        }
+    }
+}
+
+/// A declarative risk rule, as found in the rules configuration file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rule {
+    /// Allows the payments of these accounts, skipping the rules after it.
+    Allowlist { name: String, accounts: Vec<String> },
+    /// Declines the payments of these accounts.
+    Blocklist { name: String, accounts: Vec<String> },
+    /// Matches payments of at least `min_amount`.
+    AmountThreshold {
+        name: String,
+        min_amount: i32,
+        action: Action,
+    },
+    /// Matches payments that would make an account exceed `max_payments`
+    /// payments, or `max_amount` in total, within the last `window_seconds`.
+    /// Payments that were declined or failed don't count, and a payment tried
+    /// several times only counts once.
+    Velocity {
+        name: String,
+        window_seconds: i64,
+        max_payments: Option<i64>,
+        max_amount: Option<i64>,
+        action: Action,
+    },
+}
+
+impl Rule {
+    pub fn name(&self) -> &str {
+        match self {
+            Rule::Allowlist { name, .. }
+            | Rule::Blocklist { name, .. }
+            | Rule::AmountThreshold { name, .. }
+            | Rule::Velocity { name, .. } => name,
+        }
+    }
+
+    /// Evaluates the rule against the payment `payment_id` of `amount` from
+    /// `account_number`.
+    ///
+    /// Returns `None` if the rule doesn't match the payment, otherwise what
+    /// should happen to it and why.
+    async fn evaluate(
+        &self,
+        pool: &PgPool,
+        payment_id: Uuid,
+        account_number: &str,
+        amount: i32,
+    ) -> Result<Option<(Outcome, String)>, sqlx::Error> {
+        let verdict = match self {
+            Rule::Allowlist { accounts, .. } if accounts.iter().any(|a| a == account_number) => {
+                Some((Outcome::Allow, "account is allowlisted".to_string()))
+            }
+            Rule::Blocklist { accounts, .. } if accounts.iter().any(|a| a == account_number) => {
+                Some((Outcome::Decline, "account is blocklisted".to_string()))
+            }
+            Rule::AmountThreshold {
+                min_amount, action, ..
+            } if amount >= *min_amount => {
+                Some(((*action).into(), format!("amount is at least {min_amount}")))
+            }
+            Rule::Velocity {
+                window_seconds,
+                max_payments,
+                max_amount,
+                action,
+                ..
+            } => {
+                let usage = sqlx::query!(
+                    r#"
+                        SELECT COUNT(*) AS "payments!", COALESCE(SUM(p.amount), 0)::bigint AS "amount!"
+                        FROM payments p
+                        WHERE p.id <> $3
+                          AND p.status NOT IN ('Declined', 'Failed')
+                          AND EXISTS (
+                            SELECT 1 FROM risk_decisions d
+                            WHERE d.payment_id = p.id
+                              AND d.account_number = $1
+                              AND d.inserted_at > LOCALTIMESTAMP - make_interval(secs => $2)
+                          )
+                    "#,
+                    account_number,
+                    *window_seconds as f64,
+                    payment_id
+                )
+                .fetch_one(pool)
+                .await?;
+
+                if max_payments.is_some_and(|max_payments| usage.payments + 1 > max_payments) {
+                    Some((
+                        (*action).into(),
+                        format!("too many payments within {window_seconds}s"),
+                    ))
+                } else if max_amount
+                    .is_some_and(|max_amount| usage.amount + i64::from(amount) > max_amount)
+                {
+                    Some((
+                        (*action).into(),
+                        format!("too much spent within {window_seconds}s"),
+                    ))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        Ok(verdict)
+    }
+}
+
+/// The outcome of the risk evaluation of a payment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub outcome: Outcome,
+    /// Name of the rule that fired, if any.
+    pub rule: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl Decision {
+    fn allow() -> Self {
+        Self {
+            outcome: Outcome::Allow,
+            rule: None,
+            reason: None,
+        }
+    }
+}
+
+/// Evaluates payments against risk rules, before placing any hold.
+///
+/// Rules are evaluated in order, and the first one that matches the payment
+/// decides what happens to it. Payments no rule matches are allowed.
+#[derive(Debug, Clone, Default)]
+pub struct Engine {
+    rules: Vec<Rule>,
+}
+
+impl Engine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    /// Evaluates the payment `payment_id` of `amount` from `account_number`.
+    pub async fn evaluate(
+        &self,
+        pool: &PgPool,
+        payment_id: Uuid,
+        account_number: &str,
+        amount: i32,
+    ) -> Result<Decision, sqlx::Error> {
+        for rule in &self.rules {
+            if let Some((outcome, reason)) = rule
+                .evaluate(pool, payment_id, account_number, amount)
+                .await?
+            {
+                return Ok(Decision {
+                    outcome,
+                    rule: Some(rule.name().to_string()),
+                    reason: Some(reason),
+                });
+            }
+        }
+
+        Ok(Decision::allow())
+    }
+}
+
+/// Persists the risk decision made for a payment.
+pub async fn record(
+    pool: &PgPool,
+    payment_id: Uuid,
+    account_number: &str,
+    amount: i32,
+    decision: &Decision,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO risk_decisions ( payment_id, account_number, amount, outcome, rule, reason )
+            VALUES ( $1, $2, $3, $4, $5, $6 )
+        "#,
+        payment_id,
+        account_number,
+        amount,
+        decision.outcome as Outcome,
+        decision.rule,
+        decision.reason
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::{
+        accounts::DummyService,
+        payment_instruments::Card,
+        payments::{self, DeclineCode, Payment, Reversal, Status, DEFAULT_MERCHANT_ID},
+    };
+
+    /// Fetches the risk decision made for the latest try of a payment.
+    pub async fn get(pool: &PgPool, payment_id: Uuid) -> Result<Decision, sqlx::Error> {
+        sqlx::query_as!(
+            Decision,
+            r#"
+            SELECT outcome as "outcome: _", rule, reason
+            FROM risk_decisions
+            WHERE payment_id = $1
+            ORDER BY inserted_at DESC
+            LIMIT 1
+        "#,
+            payment_id
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    fn rules() -> Vec<Rule> {
+        serde_json::from_str(
+            r#"[
+                { "type": "allowlist", "name": "trusted", "accounts": ["01"] },
+                { "type": "blocklist", "name": "blocked", "accounts": ["02"] },
+                { "type": "amount_threshold", "name": "huge", "min_amount": 100000, "action": "decline" },
+                { "type": "amount_threshold", "name": "large", "min_amount": 10000, "action": "review" }
+            ]"#,
+        )
+        .expect("failed to parse rules")
+    }
+
+    #[tokio::test]
+    async fn test_rules() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let engine = Engine::new(rules());
+
+        let decision = engine
+            .evaluate(&pool, Uuid::new_v4(), "03", 100)
+            .await
+            .unwrap();
+        assert_eq!(decision, Decision::allow());
+
+        let decision = engine
+            .evaluate(&pool, Uuid::new_v4(), "02", 100)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, Outcome::Decline);
+        assert_eq!(decision.rule.as_deref(), Some("blocked"));
+
+        let decision = engine
+            .evaluate(&pool, Uuid::new_v4(), "03", 10000)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, Outcome::Review);
+        assert_eq!(decision.rule.as_deref(), Some("large"));
+
+        let decision = engine
+            .evaluate(&pool, Uuid::new_v4(), "03", 100000)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, Outcome::Decline);
+        assert_eq!(decision.rule.as_deref(), Some("huge"));
+
+        // allowlisted accounts skip the rules after the allowlist
+        let decision = engine
+            .evaluate(&pool, Uuid::new_v4(), "01", 100000)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, Outcome::Allow);
+        assert_eq!(decision.rule.as_deref(), Some("trusted"));
+    }
+
+    #[tokio::test]
+    async fn test_velocity() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let engine = Engine::new(vec![Rule::Velocity {
+            name: "burst".to_string(),
+            window_seconds: 3600,
+            max_payments: Some(2),
+            max_amount: Some(1000),
+            action: Action::Decline,
+        }]);
+        // an account of its own, so that other tests' payments don't count
+        let account_number = Uuid::new_v4().to_string();
+
+        // payments declined by the account service don't count
+        let declined = payments::insert(
+            &pool,
+            100,
+            Card::new_test().into(),
+            DEFAULT_MERCHANT_ID,
+            Status::Processing,
+        )
+        .await
+        .unwrap();
+        let decision = engine
+            .evaluate(&pool, declined, &account_number, 100)
+            .await
+            .unwrap();
+        record(&pool, declined, &account_number, 100, &decision)
+            .await
+            .unwrap();
+        let reversal = Reversal::Declined(DeclineCode::InsufficientFunds);
+        payments::reverse(&pool, &DummyService::default(), declined, reversal)
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let payment = Payment::new_test(&pool).await.unwrap();
+            // every try of the payment is evaluated, but it only counts once
+            for _ in 0..2 {
+                let decision = engine
+                    .evaluate(&pool, payment.id, &account_number, 100)
+                    .await
+                    .unwrap();
+                assert_eq!(decision.outcome, Outcome::Allow);
+                record(&pool, payment.id, &account_number, 100, &decision)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let decision = engine
+            .evaluate(&pool, Uuid::new_v4(), &account_number, 100)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, Outcome::Decline);
+        assert_eq!(decision.rule.as_deref(), Some("burst"));
+
+        let other_account_number = Uuid::new_v4().to_string();
+        let decision = engine
+            .evaluate(&pool, Uuid::new_v4(), &other_account_number, 1001)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, Outcome::Decline);
+        assert_eq!(
+            decision.reason.as_deref(),
+            Some("too much spent within 3600s")
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, Time};
+use uuid::Uuid;
+
+use crate::shutdown::Shutdown;
+
+/// What a settlement line pays out (or takes back from) the merchant.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "SettlementLineKind")]
+#[schema(as = SettlementLineKind)]
+pub enum LineKind {
+    /// An approved payment, owed to the merchant.
+    Payment,
+    /// A succeeded refund, deducted from what's owed to the merchant.
+    Refund,
+}
+
+/// Module and schema representing a settlement batch.
+///
+/// A batch rolls up, for a single merchant, all the approved payments and
+/// succeeded refunds up to its cut-off time that weren't settled by a previous
+/// batch. Payments with a timed-out account service call wait for the call to
+/// be resolved (see `timeouts::resolve`). Batches and their lines are immutable once inserted (the database
+/// rejects updates and deletions), so they can be paid out as is.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Batch {
+    pub id: Uuid,
+    pub merchant_id: String,
+    pub cut_off_at: PrimitiveDateTime,
+    pub payments_amount: i64,
+    pub refunds_amount: i64,
+    /// Fees charged for the payments, minus fees given back for the refunds.
+    pub fees_amount: i64,
+    /// What the merchant is owed: `payments_amount - refunds_amount - fees_amount`.
+    pub net_amount: i64,
+    #[allow(dead_code)]
+    pub inserted_at: PrimitiveDateTime,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Line {
+    pub id: Uuid,
+    #[allow(dead_code)]
+    pub batch_id: Uuid,
+    pub kind: LineKind,
+    pub payment_id: Uuid,
+    pub refund_id: Option<Uuid>,
+    pub amount: i32,
+    /// Fee charged for a payment, or given back for a refund.
+    pub fee_amount: i32,
+}
+
+/// Settles all the approved payments and succeeded refunds up to `cut_off_at`
+/// that weren't settled yet, into one new batch per merchant.
+///
+/// Returns the ids of the new batches. Settling twice (or concurrently) for the
+/// same cut-off is harmless: a payment or refund is only ever settled once.
+pub async fn settle(
+    pool: &PgPool,
+    cut_off_at: PrimitiveDateTime,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // concurrent runs would pick the same unsettled lines: serialize them
+    sqlx::query!("LOCK TABLE settlement_batches IN EXCLUSIVE MODE")
+        .execute(&mut tx)
+        .await?;
+
+    // a single statement, so that the batches and their lines are built from
+    // the same snapshot of unsettled payments and refunds
+    let batch_ids = sqlx::query!(
+        r#"
+          WITH unsettled AS (
+            SELECT p.merchant_id, 'Payment'::SettlementLineKind AS kind, p.id AS payment_id,
+              NULL::uuid AS refund_id, p.amount, p.fee_amount
+            FROM payments p
+            WHERE p.status = 'Approved'
+              AND p.inserted_at <= $1
+              AND NOT EXISTS (
+                SELECT 1 FROM settlement_lines l
+                WHERE l.payment_id = p.id AND l.kind = 'Payment'
+              )
+              AND NOT EXISTS (
+                SELECT 1 FROM timed_out_calls c
+                WHERE c.payment_id = p.id AND c.resolved_at IS NULL
+              )
+            UNION ALL
+            SELECT p.merchant_id, 'Refund'::SettlementLineKind, r.payment_id, r.id, r.amount,
+              r.fee_amount
+            FROM refunds r
+            JOIN payments p ON p.id = r.payment_id
+            WHERE r.status = 'Succeeded'
+              AND COALESCE(r.reviewed_at, r.inserted_at) <= $1
+              AND NOT EXISTS (SELECT 1 FROM settlement_lines l WHERE l.refund_id = r.id)
+          ),
+          batches AS (
+            INSERT INTO settlement_batches
+              ( merchant_id, cut_off_at, payments_amount, refunds_amount, fees_amount, net_amount )
+            SELECT merchant_id, $1,
+              COALESCE(SUM(amount) FILTER (WHERE kind = 'Payment'), 0),
+              COALESCE(SUM(amount) FILTER (WHERE kind = 'Refund'), 0),
+              COALESCE(SUM(CASE WHEN kind = 'Payment' THEN fee_amount ELSE -fee_amount END), 0),
+              COALESCE(SUM(
+                CASE WHEN kind = 'Payment' THEN amount - fee_amount ELSE fee_amount - amount END
+              ), 0)
+            FROM unsettled
+            GROUP BY merchant_id
+            RETURNING id, merchant_id
+          ),
+          lines AS (
+            INSERT INTO settlement_lines
+              ( batch_id, kind, payment_id, refund_id, amount, fee_amount )
+            SELECT b.id, u.kind, u.payment_id, u.refund_id, u.amount, u.fee_amount
+            FROM unsettled u
+            JOIN batches b ON b.merchant_id = u.merchant_id
+          )
+          SELECT id AS "id!" FROM batches
+        "#,
+        cut_off_at
+    )
+    .fetch_all(&mut tx)
+    .await?
+    .into_iter()
+    .map(|record| record.id)
+    .collect();
+
+    tx.commit().await?;
+
+    Ok(batch_ids)
+}
+
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Batch, sqlx::Error> {
+    sqlx::query_as!(
+        Batch,
+        r#"
+            SELECT id, merchant_id, cut_off_at, payments_amount, refunds_amount, fees_amount,
+              net_amount, inserted_at
+            FROM settlement_batches
+            WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Maximum number of batches returned by `list`.
+pub const LIST_LIMIT: i64 = 100;
+
+/// Lists the most recent batches, of a single merchant if given, newest first.
+pub async fn list(pool: &PgPool, merchant_id: Option<&str>) -> Result<Vec<Batch>, sqlx::Error> {
+    sqlx::query_as!(
+        Batch,
+        r#"
+            SELECT id, merchant_id, cut_off_at, payments_amount, refunds_amount, fees_amount,
+              net_amount, inserted_at
+            FROM settlement_batches
+            WHERE ( $1::text IS NULL OR merchant_id = $1 )
+            ORDER BY cut_off_at DESC, inserted_at DESC, id
+            LIMIT $2
+        "#,
+        merchant_id,
+        LIST_LIMIT
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn lines(pool: &PgPool, batch_id: Uuid) -> Result<Vec<Line>, sqlx::Error> {
+    sqlx::query_as!(
+        Line,
+        r#"
+            SELECT id, batch_id, kind as "kind: _", payment_id, refund_id, amount, fee_amount
+            FROM settlement_lines
+            WHERE batch_id = $1
+            ORDER BY kind, payment_id, refund_id
+        "#,
+        batch_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The latest daily cut-off at `cut_off` (UTC) that isn't after `now`.
+fn last_cut_off_at(now: OffsetDateTime, cut_off: Time) -> PrimitiveDateTime {
+    let now = now.to_offset(time::UtcOffset::UTC);
+    let cut_off_at = PrimitiveDateTime::new(now.date(), cut_off);
+
+    if cut_off_at <= PrimitiveDateTime::new(now.date(), now.time()) {
+        cut_off_at
+    } else {
+        cut_off_at - Duration::days(1)
+    }
+}
+
+/// Settles every day at `cut_off` (UTC), until the shutdown.
+///
+/// Settles up to the last cut-off right away, in case the service wasn't
+/// running at that time. A settlement in progress is completed before
+/// stopping.
+pub async fn run_daily(pool: PgPool, cut_off: Time, shutdown: Shutdown) {
+    let mut cut_off_at = last_cut_off_at(OffsetDateTime::now_utc(), cut_off);
+
+    loop {
+        match settle(&pool, cut_off_at).await {
+            Ok(batch_ids) => {
+                tracing::info!(%cut_off_at, batches = batch_ids.len(), "settled payments")
+            }
+            Err(error) => tracing::error!(%cut_off_at, %error, "failed to settle payments"),
+        }
+
+        cut_off_at += Duration::days(1);
+        let delay = cut_off_at.assume_utc() - OffsetDateTime::now_utc();
+        tokio::select! {
+            () = tokio::time::sleep(delay.try_into().unwrap_or_default()) => {}
+            () = shutdown.triggered() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::{
+        accounts::DummyService,
+        fees,
+        payment_instruments::Card,
+        payments::{self, Status},
+        refunds::{self, tests::REFUND_AMOUNT, tests::REFUND_REASON},
+    };
+
+    fn now() -> PrimitiveDateTime {
+        let now = OffsetDateTime::now_utc();
+        PrimitiveDateTime::new(now.date(), now.time())
+    }
+
+    /// Inserts an approved payment for a merchant of its own, so that its
+    /// batches only hold what the test put there.
+    async fn merchant_payment(pool: &PgPool, amount: i32) -> (String, Uuid) {
+        let merchant_id = Uuid::new_v4().to_string();
+        let payment_id = payments::insert(
+            pool,
+            amount,
+            Card::new_test().into(),
+            &merchant_id,
+            Status::Approved,
+        )
+        .await
+        .expect("failed to create payment");
+
+        (merchant_id, payment_id)
+    }
+
+    #[tokio::test]
+    async fn test_settle_once() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let (merchant_id, payment_id) = merchant_payment(&pool, 1000).await;
+        refunds::insert(&pool, payment_id, REFUND_AMOUNT, REFUND_REASON)
+            .await
+            .expect("failed to create refund");
+
+        settle(&pool, now()).await.expect("failed to settle");
+        // re-running the job doesn't settle anything twice
+        settle(&pool, now()).await.expect("failed to settle");
+
+        let batches = list(&pool, Some(&merchant_id)).await.unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].payments_amount, 1000);
+        assert_eq!(batches[0].refunds_amount, REFUND_AMOUNT as i64);
+        assert_eq!(batches[0].net_amount, 1000 - REFUND_AMOUNT as i64);
+
+        let batch_lines = lines(&pool, batches[0].id).await.unwrap();
+        assert_eq!(batch_lines.len(), 2);
+        assert_eq!(batch_lines[0].kind, LineKind::Payment);
+        assert_eq!(batch_lines[1].kind, LineKind::Refund);
+
+        // refunds made after the payment was settled go in the next batch
+        refunds::insert(&pool, payment_id, REFUND_AMOUNT, REFUND_REASON)
+            .await
+            .expect("failed to create refund");
+        settle(&pool, now()).await.expect("failed to settle");
+
+        let batches = list(&pool, Some(&merchant_id)).await.unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].net_amount, -(REFUND_AMOUNT as i64));
+    }
+
+    #[tokio::test]
+    async fn test_settle_fees() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let merchant_id = Uuid::new_v4().to_string();
+        let schedule = fees::Schedule {
+            percent_bps: 250,
+            fixed_amount: 10,
+            ..fees::Schedule::default()
+        };
+        fees::put(&pool, &merchant_id, &schedule).await.unwrap();
+
+        let payment_id = payments::insert(
+            &pool,
+            1000,
+            Card::new_test().into(),
+            &merchant_id,
+            Status::Processing,
+        )
+        .await
+        .expect("failed to create payment");
+        payments::approve(&pool, payment_id).await.unwrap();
+        assert_eq!(
+            payments::get(&pool, payment_id).await.unwrap().fee_amount,
+            35
+        );
+
+        let outcome = refunds::checked_insert(
+            &pool,
+            &DummyService::default(),
+            &refunds::Policy::default(),
+            payment_id,
+            400,
+            Some(REFUND_REASON),
+            None,
+        )
+        .await
+        .expect("failed to refund");
+        let refunds::Outcome::Refunded(refund_id) = outcome else {
+            panic!("failed to refund: {outcome:?}");
+        };
+        // 40% of the fee, 14 rounded
+        assert_eq!(refunds::get(&pool, refund_id).await.unwrap().fee_amount, 14);
+
+        settle(&pool, now()).await.expect("failed to settle");
+
+        let batch = list(&pool, Some(&merchant_id)).await.unwrap().remove(0);
+        assert_eq!(batch.payments_amount, 1000);
+        assert_eq!(batch.refunds_amount, 400);
+        assert_eq!(batch.fees_amount, 35 - 14);
+        assert_eq!(batch.net_amount, 1000 - 400 - (35 - 14));
+    }
+
+    #[tokio::test]
+    async fn test_settle_up_to_cut_off() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let (merchant_id, _) = merchant_payment(&pool, 1000).await;
+
+        settle(&pool, now() - Duration::hours(1))
+            .await
+            .expect("failed to settle");
+        assert!(list(&pool, Some(&merchant_id)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_immutable() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+
+        let (merchant_id, _) = merchant_payment(&pool, 1000).await;
+        settle(&pool, now()).await.expect("failed to settle");
+        let batch = list(&pool, Some(&merchant_id)).await.unwrap().remove(0);
+
+        let updated = sqlx::query!(
+            "UPDATE settlement_batches SET net_amount = 0 WHERE id = $1",
+            batch.id
+        )
+        .execute(&pool)
+        .await;
+        assert!(updated.is_err());
+    }
+
+    #[test]
+    fn test_last_cut_off_at() {
+        let cut_off = Time::from_hms(18, 0, 0).unwrap();
+        let date = time::Date::from_calendar_date(2026, time::Month::October, 18).unwrap();
+
+        let now = PrimitiveDateTime::new(date, Time::from_hms(19, 30, 0).unwrap()).assume_utc();
+        assert_eq!(
+            last_cut_off_at(now, cut_off),
+            PrimitiveDateTime::new(date, cut_off)
+        );
+
+        let now = PrimitiveDateTime::new(date, Time::from_hms(9, 0, 0).unwrap()).assume_utc();
+        assert_eq!(
+            last_cut_off_at(now, cut_off),
+            PrimitiveDateTime::new(date.previous_day().unwrap(), cut_off)
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Caps on how much each account can spend, `None` meaning "no cap".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Cap on the payments of the current (calendar) day.
+    pub daily: Option<i64>,
+    /// Cap on the payments of the current (calendar) month.
+    pub monthly: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Daily,
+    Monthly,
+}
+
+/// How much an account spent in the current day and month.
+///
+/// Payments that were declined or failed don't count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub daily_amount: i64,
+    pub monthly_amount: i64,
+}
+
+async fn usage_within(
+    executor: impl sqlx::PgExecutor<'_>,
+    account_number: &str,
+) -> Result<Usage, sqlx::Error> {
+    sqlx::query_as!(
+        Usage,
+        r#"
+            SELECT
+              COALESCE(SUM(s.amount) FILTER (WHERE s.inserted_at >= date_trunc('day', LOCALTIMESTAMP)), 0)::bigint AS "daily_amount!",
+              COALESCE(SUM(s.amount), 0)::bigint AS "monthly_amount!"
+            FROM account_spendings s
+            JOIN payments p ON p.id = s.payment_id
+            WHERE s.account_number = $1
+              AND s.inserted_at >= date_trunc('month', LOCALTIMESTAMP)
+              AND p.status NOT IN ('Declined', 'Failed')
+        "#,
+        account_number
+    )
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn usage(pool: &PgPool, account_number: &str) -> Result<Usage, sqlx::Error> {
+    usage_within(pool, account_number).await
+}
+
+/// Records the spending of a payment against its account, unless it would
+/// exceed one of `limits`.
+///
+/// Tries of a payment replace its previous spending.
+///
+/// The account is locked for the duration of the transaction, so concurrent
+/// payments of the same account are checked one after the other and can't both
+/// squeeze under a limit.
+pub async fn reserve(
+    pool: &PgPool,
+    payment_id: Uuid,
+    account_number: &str,
+    amount: i32,
+    limits: &Limits,
+) -> Result<Result<(), LimitExceeded>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"SELECT pg_advisory_xact_lock(hashtext('account_spendings:' || $1))::text AS "locked!""#,
+        account_number
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let usage = usage_within(&mut tx, account_number).await?;
+    let spent = i64::from(amount);
+    if limits
+        .daily
+        .is_some_and(|limit| usage.daily_amount + spent > limit)
+    {
+        return Ok(Err(LimitExceeded::Daily));
+    }
+    if limits
+        .monthly
+        .is_some_and(|limit| usage.monthly_amount + spent > limit)
+    {
+        return Ok(Err(LimitExceeded::Monthly));
+    }
+
+    sqlx::query!(
+        r#"
+            INSERT INTO account_spendings ( payment_id, account_number, amount )
+            VALUES ( $1, $2, $3 )
+            ON CONFLICT ( payment_id ) DO UPDATE
+            SET account_number = EXCLUDED.account_number, amount = EXCLUDED.amount,
+                inserted_at = current_timestamp
+        "#,
+        payment_id,
+        account_number,
+        amount
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Ok(()))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use crate::bank::payments::{self, Payment, Status};
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let limits = Limits {
+            daily: Some(200),
+            monthly: Some(300),
+        };
+        // an account of its own, so that other tests' payments don't count
+        let account_number = Uuid::new_v4().to_string();
+
+        let payment = Payment::new_test(&pool).await.unwrap();
+        let reserved = reserve(&pool, payment.id, &account_number, 150, &limits)
+            .await
+            .unwrap();
+        assert_eq!(reserved, Ok(()));
+
+        let payment = Payment::new_test(&pool).await.unwrap();
+        let reserved = reserve(&pool, payment.id, &account_number, 100, &limits)
+            .await
+            .unwrap();
+        assert_eq!(reserved, Err(LimitExceeded::Daily));
+
+        let spent = usage(&pool, &account_number).await.unwrap();
+        assert_eq!(spent.daily_amount, 150);
+        assert_eq!(spent.monthly_amount, 150);
+
+        // declined payments don't count
+        let payment = Payment::new_test(&pool).await.unwrap();
+        reserve(&pool, payment.id, &account_number, 50, &limits)
+            .await
+            .unwrap()
+            .unwrap();
+        payments::update(&pool, payment.id, Status::Declined)
+            .await
+            .unwrap();
+        let spent = usage(&pool, &account_number).await.unwrap();
+        assert_eq!(spent.daily_amount, 150);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_reserve() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let limits = Limits {
+            daily: Some(200),
+            monthly: None,
+        };
+        let account_number = Uuid::new_v4().to_string();
+
+        let payment_a = Payment::new_test(&pool).await.unwrap();
+        let payment_b = Payment::new_test(&pool).await.unwrap();
+        let (reserved_a, reserved_b) = tokio::join!(
+            reserve(&pool, payment_a.id, &account_number, 123, &limits),
+            reserve(&pool, payment_b.id, &account_number, 123, &limits),
+        );
+
+        let mut reserved = [reserved_a.unwrap(), reserved_b.unwrap()];
+        reserved.sort_by_key(Result::is_err);
+        assert_eq!(reserved, [Ok(()), Err(LimitExceeded::Daily)]);
+    }
+}
@@ -0,0 +1,720 @@
+use std::collections::HashSet;
+
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::bank::{
+    accounts::{self, AccountService, HoldRef},
+    attempts::Call,
+    payment_instruments::Card,
+    payments::{self, FailureReason, Reversal, Status},
+    reconciliation::{self, LedgerEntry, Operation},
+    refunds, reviews,
+    verifications::{self, Outcome},
+};
+use crate::metrics::{self, metrics};
+
+/// Records an account service call which timed out (see `accounts::TIMEOUT`),
+/// to be resolved later.
+///
+/// The call may have gone through all the same, so what it was made for stays
+/// pending until the call is resolved against the account service's ledger
+/// export (see `resolve`):
+/// - a payment whose hold placement timed out stays processing;
+/// - a payment whose withdrawal timed out keeps its funds held, and isn't
+///   settled nor refunded;
+/// - a review whose withdrawal or release timed out is decided, but the
+///   payment still requires review and leaves the review queue;
+/// - a refund whose call timed out stays pending.
+///
+/// See `record_verification` for the calls made to verify a card.
+pub async fn record<'c>(executor: impl PgExecutor<'c>, call: Call) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO timed_out_calls ( payment_id, refund_id, operation, amount, hold_id )
+            VALUES ( $1, $2, $3, $4, $5 )
+        "#,
+        call.payment_id,
+        call.refund_id,
+        call.operation as Operation,
+        call.amount,
+        call.hold_id
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+/// Records a hold placement or release of a card verification which timed
+/// out, to be resolved later (see `resolve`): a verification whose placement
+/// timed out stays processing, and a hold whose release timed out stays placed.
+pub async fn record_verification<'c>(
+    executor: impl PgExecutor<'c>,
+    verification_id: Uuid,
+    operation: Operation,
+    hold_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO timed_out_calls ( verification_id, operation, amount, hold_id )
+            VALUES ( $1, $2, $3, $4 )
+        "#,
+        verification_id,
+        operation as Operation,
+        verifications::HOLD_AMOUNT,
+        hold_id
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+/// Resolves the timed-out calls made within the time span of a ledger export,
+/// returning how many were.
+///
+/// A hold placement went through if the export holds a hold we don't know of,
+/// on the payment's account and for the amount asked. The other calls went
+/// through if the export holds their operation for their hold (for credits,
+/// of which a hold may have several, if it holds more of them than there are
+/// refunds credited so far). Only the calls made within the export's time
+/// span are resolved, the others are left for another export.
+///
+/// What the call was made for is then completed, or given up:
+/// - a payment whose hold placement timed out fails, and the hold is released
+///   if it was placed after all;
+/// - a payment whose withdrawal timed out is approved if it went through, and
+///   fails otherwise (releasing its hold);
+/// - a review takes effect if its call went through, and the payment goes
+///   back to the review queue otherwise (see `reviews::resolve`);
+/// - a refund completes if its call went through, and is abandoned otherwise
+///   (see `refunds::resolve`);
+/// - a verification whose hold placement didn't go through fails, and the
+///   hold of a verification is released if it's still placed.
+pub async fn resolve<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    entries: &[LedgerEntry],
+) -> Result<usize, sqlx::Error> {
+    let timestamps = entries.iter().map(|entry| entry.timestamp);
+    let (Some(from), Some(to)) = (timestamps.clone().min(), timestamps.max()) else {
+        return Ok(0);
+    };
+
+    let calls = sqlx::query!(
+        r#"
+            SELECT c.id, c.payment_id, c.verification_id, c.refund_id,
+              c.operation AS "operation: Operation", c.amount, c.hold_id,
+              COALESCE(p.card_number, v.card_number) AS "card_number!",
+              p.status AS "status?: Status",
+              (
+                SELECT COUNT(*) FROM refunds r
+                WHERE r.payment_id = p.id AND r.mechanism = 'Credit' AND r.status = 'Succeeded'
+              ) AS "credits_count!"
+            FROM timed_out_calls c
+            LEFT JOIN payments p ON p.id = c.payment_id
+            LEFT JOIN card_verifications v ON v.id = c.verification_id
+            WHERE c.resolved_at IS NULL AND c.inserted_at BETWEEN $1 AND $2
+            ORDER BY c.inserted_at, c.id
+        "#,
+        reconciliation::to_utc(from),
+        reconciliation::to_utc(to)
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut placements = unknown_placements(pool, entries).await?;
+
+    for call in &calls {
+        let (placed, went_through) = match call.operation {
+            Operation::PlaceHold => {
+                let account = Card::try_from(call.card_number.clone())
+                    .map(|card| card.account_number().to_string())
+                    .unwrap_or_else(|_| call.card_number.clone());
+                let placed = placements
+                    .iter()
+                    .position(|entry| entry.account == account && Some(entry.amount) == call.amount)
+                    .map(|index| HoldRef::new(placements.remove(index).hold_id));
+                (placed, placed.is_some())
+            }
+            operation => {
+                let count = entries
+                    .iter()
+                    .filter(|entry| Some(entry.hold_id) == call.hold_id)
+                    .filter(|entry| entry.operation == operation)
+                    .count();
+                let went_through = match operation {
+                    Operation::CreditFunds => count as i64 > call.credits_count,
+                    _ => count > 0,
+                };
+                (None, went_through)
+            }
+        };
+
+        match (call.verification_id, call.payment_id, call.operation) {
+            (Some(verification_id), _, Operation::PlaceHold) => match placed {
+                Some(hold_ref) => {
+                    release_verification_hold(pool, account_service, verification_id, hold_ref)
+                        .await?
+                }
+                None => verifications::update(pool, verification_id, Outcome::Failed).await?,
+            },
+            (Some(verification_id), _, _) => match call.hold_id.map(HoldRef::new) {
+                Some(hold_ref) if !went_through => {
+                    release_verification_hold(pool, account_service, verification_id, hold_ref)
+                        .await?
+                }
+                Some(hold_ref) => {
+                    let outcome = Outcome::Verified {
+                        hold_id: hold_ref.id(),
+                        released: true,
+                    };
+                    verifications::update(pool, verification_id, outcome).await?
+                }
+                None => {}
+            },
+            (None, Some(payment_id), Operation::PlaceHold) => {
+                if let Some(hold_ref) = placed {
+                    payments::record_hold(pool, payment_id, hold_ref).await?;
+                }
+                let reversal = Reversal::Failed(FailureReason::Timeout);
+                payments::reverse(pool, account_service, payment_id, reversal).await?;
+            }
+            (None, Some(payment_id), operation) => match (call.refund_id, call.status) {
+                (Some(refund_id), _) => {
+                    refunds::resolve(pool, account_service, refund_id, went_through).await?
+                }
+                (None, Some(Status::RequiresReview)) => {
+                    reviews::resolve(pool, payment_id, operation, went_through).await?
+                }
+                (None, _) => {
+                    resolve_withdrawal(pool, account_service, payment_id, went_through).await?
+                }
+            },
+            (None, None, _) => {
+                unreachable!("timed-out calls are made for a payment or a verification")
+            }
+        }
+
+        sqlx::query!(
+            r#"
+                UPDATE timed_out_calls
+                SET went_through = $2, resolved_at = current_timestamp
+                WHERE id = $1
+            "#,
+            call.id,
+            went_through
+        )
+        .execute(pool)
+        .await?;
+        tracing::info!(
+            payment_id = ?call.payment_id,
+            verification_id = ?call.verification_id,
+            operation = %call.operation,
+            went_through,
+            "resolved timed-out account service call"
+        );
+    }
+
+    Ok(calls.len())
+}
+
+/// Lists the hold placements of a ledger export whose hold we don't know of,
+/// i.e. which may be the placements which timed out.
+async fn unknown_placements<'a>(
+    pool: &PgPool,
+    entries: &'a [LedgerEntry],
+) -> Result<Vec<&'a LedgerEntry>, sqlx::Error> {
+    let placements: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.operation == Operation::PlaceHold)
+        .collect();
+    let hold_ids: Vec<_> = placements.iter().map(|entry| entry.hold_id).collect();
+
+    let known: HashSet<Uuid> = sqlx::query!(
+        r#"
+            SELECT hold_id AS "hold_id!" FROM payments WHERE hold_id = ANY($1)
+            UNION
+            SELECT hold_id FROM payment_tries WHERE hold_id = ANY($1)
+            UNION
+            SELECT hold_id FROM card_verifications WHERE hold_id = ANY($1)
+        "#,
+        &hold_ids
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|record| record.hold_id)
+    .collect();
+
+    Ok(placements
+        .into_iter()
+        .filter(|entry| !known.contains(&entry.hold_id))
+        .collect())
+}
+
+/// Resolves the withdrawal of a payment which timed out: the payment is
+/// approved if it went through, and fails otherwise.
+async fn resolve_withdrawal<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    id: Uuid,
+    went_through: bool,
+) -> Result<(), sqlx::Error> {
+    if !went_through {
+        let reversal = Reversal::Failed(FailureReason::Timeout);
+        return payments::reverse(pool, account_service, id, reversal).await;
+    }
+
+    let mut tx = pool.begin().await?;
+    let amount = sqlx::query!(
+        r#"SELECT amount FROM payments WHERE id = $1 AND hold_status = 'Placed' FOR UPDATE"#,
+        id
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .map(|record| record.amount);
+    let Some(amount) = amount else {
+        return Ok(());
+    };
+
+    payments::record_withdrawal(&mut tx, id, amount).await?;
+    tx.commit().await?;
+
+    metrics().payment_approved();
+    Ok(())
+}
+
+/// Releases the hold of a verification whose placement or release timed out.
+/// A release which times out again is recorded, to be resolved later.
+async fn release_verification_hold<T: AccountService>(
+    pool: &PgPool,
+    account_service: &T,
+    id: Uuid,
+    hold_ref: HoldRef,
+) -> Result<(), sqlx::Error> {
+    let (released, _) = metrics::account_service_call(
+        Operation::ReleaseHold,
+        account_service.release_hold(hold_ref),
+    )
+    .await;
+    match &released {
+        Ok(()) => {}
+        Err(error) if error == accounts::TIMEOUT => {
+            record_verification(pool, id, Operation::ReleaseHold, Some(hold_ref.id())).await?
+        }
+        Err(error) => {
+            tracing::warn!(hold_id = %hold_ref.id(), %error, "failed to release verification hold")
+        }
+    }
+
+    let outcome = Outcome::Verified {
+        hold_id: hold_ref.id(),
+        released: released.is_ok(),
+    };
+    verifications::update(pool, id, outcome).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::LazyLock;
+
+    use time::{Duration, OffsetDateTime};
+
+    use super::*;
+    use crate::bank::{
+        accounts::DummyService,
+        payments::{HoldStatus, Payment, DEFAULT_MERCHANT_ID},
+        refunds::{Outcome, Policy, Reason},
+        reviews::Review,
+    };
+
+    /// Every test resolves all the calls within its export: they mustn't
+    /// resolve the calls of each other.
+    static RESOLVING: LazyLock<tokio::sync::Mutex<()>> = LazyLock::new(Default::default);
+
+    /// Times out the calls of `0`, and answers the others like `DummyService`.
+    #[derive(Clone)]
+    struct TimingOut(Operation);
+
+    impl TimingOut {
+        fn call(&self, operation: Operation) -> Result<(), String> {
+            if operation == self.0 {
+                Err(accounts::TIMEOUT.into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AccountService for TimingOut {
+        async fn place_hold(&self, account_number: &str, amount: i32) -> Result<HoldRef, String> {
+            self.call(Operation::PlaceHold)?;
+            DummyService::default()
+                .place_hold(account_number, amount)
+                .await
+        }
+
+        async fn release_hold(&self, _hold_ref: HoldRef) -> Result<(), String> {
+            self.call(Operation::ReleaseHold)
+        }
+
+        async fn withdraw_funds(&self, _hold_ref: HoldRef) -> Result<(), String> {
+            self.call(Operation::WithdrawFunds)
+        }
+
+        async fn capture_funds(&self, _hold_ref: HoldRef, _amount: i32) -> Result<(), String> {
+            self.call(Operation::CaptureFunds)
+        }
+
+        async fn credit_funds(&self, _account_number: &str, _amount: i32) -> Result<(), String> {
+            self.call(Operation::CreditFunds)
+        }
+    }
+
+    /// A ledger export spanning the calls made just before, with `entries`.
+    fn export(account: &str, entries: &[(Uuid, Operation, i32)]) -> Vec<LedgerEntry> {
+        let entry = |hold_id, operation, amount, timestamp| LedgerEntry {
+            hold_id,
+            account: account.to_string(),
+            amount,
+            operation,
+            timestamp,
+        };
+        let now = OffsetDateTime::now_utc();
+
+        let mut export = vec![entry(
+            Uuid::new_v4(),
+            Operation::ReleaseHold,
+            0,
+            now - Duration::minutes(1),
+        )];
+        export.extend(
+            entries
+                .iter()
+                .map(|&(hold_id, operation, amount)| entry(hold_id, operation, amount, now)),
+        );
+        export.push(entry(
+            Uuid::new_v4(),
+            Operation::ReleaseHold,
+            0,
+            now + Duration::minutes(1),
+        ));
+        export
+    }
+
+    /// Whether the timed-out calls of a payment went through, once resolved.
+    async fn went_through(pool: &PgPool, payment_id: Uuid) -> Vec<Option<bool>> {
+        sqlx::query!(
+            r#"
+              SELECT went_through FROM timed_out_calls
+              WHERE payment_id = $1
+              ORDER BY inserted_at, id
+            "#,
+            payment_id
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.went_through)
+        .collect()
+    }
+
+    async fn held_payment(pool: &PgPool) -> (Payment, HoldRef) {
+        let payment = Payment::new_test(pool).await.unwrap();
+        let hold_ref = HoldRef::new(Uuid::new_v4());
+        payments::record_hold(pool, payment.id, hold_ref)
+            .await
+            .unwrap();
+        (payment, hold_ref)
+    }
+
+    #[tokio::test]
+    async fn test_resolve_hold_placement() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let _resolving = RESOLVING.lock().await;
+        let account_service = DummyService::default();
+
+        let cards = [Card::new_test(), Card::new_test()];
+        let mut ids = vec![];
+        for card in &cards {
+            let id = payments::insert(
+                &pool,
+                payments::tests::PAYMENT_AMOUNT,
+                card.clone().into(),
+                DEFAULT_MERCHANT_ID,
+                Status::Processing,
+            )
+            .await
+            .unwrap();
+            let call =
+                Call::new(id, Operation::PlaceHold).with_amount(payments::tests::PAYMENT_AMOUNT);
+            record(&pool, call).await.unwrap();
+            ids.push(id);
+        }
+
+        // only the first placement went through
+        let hold_id = Uuid::new_v4();
+        let entries = export(
+            cards[0].account_number(),
+            &[(
+                hold_id,
+                Operation::PlaceHold,
+                payments::tests::PAYMENT_AMOUNT,
+            )],
+        );
+        assert!(resolve(&pool, &account_service, &entries).await.unwrap() >= 2);
+
+        let placed = payments::get(&pool, ids[0]).await.unwrap();
+        assert_eq!(placed.status, Status::Failed);
+        assert_eq!(placed.failure_reason, Some(FailureReason::Timeout));
+        assert_eq!(placed.hold_id, Some(hold_id));
+        assert_eq!(placed.hold_status, Some(HoldStatus::Released));
+        assert_eq!(went_through(&pool, ids[0]).await, [Some(true)]);
+
+        let not_placed = payments::get(&pool, ids[1]).await.unwrap();
+        assert_eq!(not_placed.status, Status::Failed);
+        assert_eq!(not_placed.hold_id, None);
+        assert_eq!(went_through(&pool, ids[1]).await, [Some(false)]);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_withdrawal() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let _resolving = RESOLVING.lock().await;
+
+        let (payment, hold_ref) = held_payment(&pool).await;
+        let withdrawal =
+            payments::withdraw_funds(&pool, &TimingOut(Operation::WithdrawFunds), payment.id)
+                .await
+                .unwrap();
+        assert_eq!(withdrawal, Err(accounts::TIMEOUT.to_string()));
+
+        // the payment can't be refunded until the withdrawal is resolved
+        let outcome = refunds::checked_insert(
+            &pool,
+            &DummyService::default(),
+            &Policy::default(),
+            payment.id,
+            payment.amount,
+            Some(Reason::Other),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome, Outcome::InProgress);
+
+        let entries = export(
+            Card::new_test().account_number(),
+            &[(hold_ref.id(), Operation::WithdrawFunds, payment.amount)],
+        );
+        resolve(&pool, &DummyService::default(), &entries)
+            .await
+            .unwrap();
+        let payment = payments::get(&pool, payment.id).await.unwrap();
+        assert_eq!(payment.status, Status::Approved);
+        assert_eq!(payment.hold_status, Some(HoldStatus::Withdrawn));
+
+        // a withdrawal which didn't go through fails the payment
+        let (payment, _) = held_payment(&pool).await;
+        payments::withdraw_funds(&pool, &TimingOut(Operation::WithdrawFunds), payment.id)
+            .await
+            .unwrap()
+            .unwrap_err();
+        resolve(
+            &pool,
+            &DummyService::default(),
+            &export(Card::new_test().account_number(), &[]),
+        )
+        .await
+        .unwrap();
+        let payment = payments::get(&pool, payment.id).await.unwrap();
+        assert_eq!(payment.status, Status::Failed);
+        assert_eq!(payment.hold_status, Some(HoldStatus::Released));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_refund() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let _resolving = RESOLVING.lock().await;
+
+        let (payment, hold_ref) = held_payment(&pool).await;
+        let outcome = refunds::checked_insert(
+            &pool,
+            &TimingOut(Operation::ReleaseHold),
+            &Policy::default(),
+            payment.id,
+            payment.amount,
+            Some(Reason::Other),
+            None,
+        )
+        .await
+        .unwrap();
+        let Outcome::TimedOut(refund_id) = outcome else {
+            panic!("refund should time out, got {outcome:?}");
+        };
+        let refund = refunds::get(&pool, refund_id).await.unwrap();
+        assert_eq!(refund.status, refunds::Status::Pending);
+
+        let entries = export(
+            Card::new_test().account_number(),
+            &[(hold_ref.id(), Operation::ReleaseHold, payment.amount)],
+        );
+        resolve(&pool, &DummyService::default(), &entries)
+            .await
+            .unwrap();
+        let refund = refunds::get(&pool, refund_id).await.unwrap();
+        assert_eq!(refund.status, refunds::Status::Succeeded);
+        let payment = payments::get(&pool, payment.id).await.unwrap();
+        assert_eq!(payment.hold_status, Some(HoldStatus::Released));
+
+        // a refund whose call didn't go through is abandoned
+        let (payment, _) = held_payment(&pool).await;
+        let outcome = refunds::checked_insert(
+            &pool,
+            &TimingOut(Operation::ReleaseHold),
+            &Policy::default(),
+            payment.id,
+            payment.amount,
+            Some(Reason::Other),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(outcome, Outcome::TimedOut(_)));
+        resolve(
+            &pool,
+            &DummyService::default(),
+            &export(Card::new_test().account_number(), &[]),
+        )
+        .await
+        .unwrap();
+        assert!(refunds::list(&pool, payment.id).await.unwrap().is_empty());
+        let payment = payments::get(&pool, payment.id).await.unwrap();
+        assert_eq!(payment.hold_status, Some(HoldStatus::Withdrawn));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_review() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let _resolving = RESOLVING.lock().await;
+        let account_service = DummyService::default();
+
+        let id = reviews::tests::payment_awaiting_review(
+            &pool,
+            &account_service,
+            reviews::DEFAULT_REVIEW_PERIOD,
+        )
+        .await;
+        let review = reviews::approve(&pool, &TimingOut(Operation::WithdrawFunds), id, "alice")
+            .await
+            .unwrap();
+        assert_eq!(review, Review::TimedOut);
+
+        // the review is decided: the payment leaves the queue
+        let queue = reviews::queue(&pool).await.unwrap();
+        assert!(queue.iter().all(|payment| payment.id != id));
+        let review = reviews::reject(&pool, &account_service, id, Some("bob"))
+            .await
+            .unwrap();
+        assert_eq!(review, Review::AlreadyReviewed);
+
+        // the withdrawal didn't go through: the payment is back in the queue
+        resolve(
+            &pool,
+            &account_service,
+            &export(Card::new_test().account_number(), &[]),
+        )
+        .await
+        .unwrap();
+        let payment = payments::get(&pool, id).await.unwrap();
+        assert_eq!(payment.status, Status::RequiresReview);
+        assert_eq!(payment.reviewed_by, None);
+        let queue = reviews::queue(&pool).await.unwrap();
+        assert!(queue.iter().any(|payment| payment.id == id));
+
+        let review = reviews::reject(&pool, &TimingOut(Operation::ReleaseHold), id, Some("bob"))
+            .await
+            .unwrap();
+        assert_eq!(review, Review::TimedOut);
+        let hold_id = payment.hold_id.unwrap();
+        let entries = export(
+            Card::new_test().account_number(),
+            &[(hold_id, Operation::ReleaseHold, payment.amount)],
+        );
+        resolve(&pool, &account_service, &entries).await.unwrap();
+        let payment = payments::get(&pool, id).await.unwrap();
+        assert_eq!(payment.status, Status::Declined);
+        assert_eq!(
+            payment.decline_code,
+            Some(payments::DeclineCode::ReviewRejected)
+        );
+        assert_eq!(payment.reviewed_by.as_deref(), Some("bob"));
+        assert_eq!(payment.hold_status, Some(HoldStatus::Released));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_verification() {
+        let pool = crate::pg_pool()
+            .await
+            .expect("failed to connect to postgres");
+        let _resolving = RESOLVING.lock().await;
+
+        let cards = [Card::new_test(), Card::new_test()];
+        let mut ids = vec![];
+        for card in &cards {
+            let id = verifications::insert(
+                &pool,
+                &String::from(card.clone()),
+                DEFAULT_MERCHANT_ID,
+                verifications::Outcome::TimedOut,
+            )
+            .await
+            .unwrap();
+            record_verification(&pool, id, Operation::PlaceHold, None)
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+
+        // only the first placement went through: its hold is released, but
+        // the release times out
+        let hold_id = Uuid::new_v4();
+        let entries = export(
+            cards[0].account_number(),
+            &[(hold_id, Operation::PlaceHold, verifications::HOLD_AMOUNT)],
+        );
+        resolve(&pool, &TimingOut(Operation::ReleaseHold), &entries)
+            .await
+            .unwrap();
+
+        let placed = verifications::get(&pool, ids[0]).await.unwrap();
+        assert_eq!(placed.status, verifications::Status::Verified);
+        assert_eq!(placed.hold_id, Some(hold_id));
+        assert_eq!(placed.hold_status, Some(HoldStatus::Placed));
+
+        let not_placed = verifications::get(&pool, ids[1]).await.unwrap();
+        assert_eq!(not_placed.status, verifications::Status::Failed);
+        assert_eq!(not_placed.hold_id, None);
+
+        // the release went through after all
+        let entries = export(
+            cards[0].account_number(),
+            &[(hold_id, Operation::ReleaseHold, verifications::HOLD_AMOUNT)],
+        );
+        resolve(&pool, &DummyService::default(), &entries)
+            .await
+            .unwrap();
+        let placed = verifications::get(&pool, ids[0]).await.unwrap();
+        assert_eq!(placed.hold_status, Some(HoldStatus::Released));
+    }
+}
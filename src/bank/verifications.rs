@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::PrimitiveDateTime;
+use uuid::Uuid;
+
+use crate::bank::payments::{DeclineCode, HoldStatus};
+
+/// Amount held to verify a card.
+pub const HOLD_AMOUNT: i32 = 0;
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "VerificationStatus")]
+#[schema(as = VerificationStatus)]
+pub enum Status {
+    /// The hold placement timed out: whether the card is valid isn't known
+    /// until the call is resolved (see `timeouts::resolve`).
+    Processing,
+    /// The account service accepted a hold on the card's account.
+    Verified,
+    /// The account service declined the hold (e.g. invalid account number).
+    Declined,
+    /// The hold placement timed out, and didn't go through.
+    Failed,
+}
+
+/// Module and schema representing a card verification.
+///
+/// Zero-amount payments verify the card instead of charging it, so merchants
+/// can check a card before a real charge: a hold of `HOLD_AMOUNT` is placed on
+/// the card's account, and released right away. The card can still be used
+/// by a payment afterwards. If the placement or the release times out, the
+/// hold is released once the call is resolved (see `timeouts::resolve`).
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Verification {
+    pub id: Uuid,
+    pub card_number: String,
+    pub merchant_id: String,
+    pub status: Status,
+    pub decline_code: Option<DeclineCode>,
+    pub hold_id: Option<Uuid>,
+    /// `Placed` if the hold couldn't be released.
+    pub hold_status: Option<HoldStatus>,
+    pub inserted_at: PrimitiveDateTime,
+}
+
+/// How a verification went.
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum Outcome {
+    Verified {
+        hold_id: Uuid,
+        released: bool,
+    },
+    Declined(DeclineCode),
+    /// The hold placement timed out.
+    TimedOut,
+    /// The hold placement timed out, and didn't go through.
+    Failed,
+}
+
+impl Outcome {
+    fn columns(
+        self,
+    ) -> (
+        Status,
+        Option<DeclineCode>,
+        Option<Uuid>,
+        Option<HoldStatus>,
+    ) {
+        match self {
+            Outcome::Verified { hold_id, released } => {
+                let hold_status = if released {
+                    HoldStatus::Released
+                } else {
+                    HoldStatus::Placed
+                };
+                (Status::Verified, None, Some(hold_id), Some(hold_status))
+            }
+            Outcome::Declined(code) => (Status::Declined, Some(code), None, None),
+            Outcome::TimedOut => (Status::Processing, None, None, None),
+            Outcome::Failed => (Status::Failed, None, None, None),
+        }
+    }
+}
+
+pub async fn insert(
+    pool: &PgPool,
+    card_number: &str,
+    merchant_id: &str,
+    outcome: Outcome,
+) -> Result<Uuid, sqlx::Error> {
+    let (status, decline_code, hold_id, hold_status) = outcome.columns();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO card_verifications ( card_number, merchant_id, status, decline_code, hold_id, hold_status )
+            VALUES ( $1, $2, $3, $4, $5, $6 )
+            RETURNING id
+        "#,
+        card_number,
+        merchant_id,
+        status as Status,
+        decline_code as Option<DeclineCode>,
+        hold_id,
+        hold_status as Option<HoldStatus>
+    )
+    .fetch_one(pool)
+    .await
+    .map(|record| record.id)
+}
+
+/// Records how a verification went, once its timed-out call is resolved.
+pub(crate) async fn update(pool: &PgPool, id: Uuid, outcome: Outcome) -> Result<(), sqlx::Error> {
+    let (status, decline_code, hold_id, hold_status) = outcome.columns();
+
+    sqlx::query!(
+        r#"
+            UPDATE card_verifications
+            SET status = $2, decline_code = $3, hold_id = $4, hold_status = $5
+            WHERE id = $1
+        "#,
+        id,
+        status as Status,
+        decline_code as Option<DeclineCode>,
+        hold_id,
+        hold_status as Option<HoldStatus>
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Verification, sqlx::Error> {
+    sqlx::query_as!(
+        Verification,
+        r#"
+            SELECT id, card_number, merchant_id, status as "status: _", decline_code as "decline_code: _",
+              hold_id, hold_status as "hold_status: _", inserted_at
+            FROM card_verifications
+            WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{payment_instruments::Card, payments::DEFAULT_MERCHANT_ID};
+
+    #[tokio::test]
+    async fn test_insert() {
+        let pool = crate::pg_pool().await.unwrap();
+        let card_number = String::from(Card::new_test());
+
+        let hold_id = Uuid::new_v4();
+        let outcome = Outcome::Verified {
+            hold_id,
+            released: true,
+        };
+        let id = insert(&pool, &card_number, DEFAULT_MERCHANT_ID, outcome)
+            .await
+            .unwrap();
+
+        let verification = get(&pool, id).await.unwrap();
+        assert_eq!(verification.card_number, card_number);
+        assert_eq!(verification.status, Status::Verified);
+        assert_eq!(verification.decline_code, None);
+        assert_eq!(verification.hold_id, Some(hold_id));
+        assert_eq!(verification.hold_status, Some(HoldStatus::Released));
+
+        let outcome = Outcome::Declined(DeclineCode::InvalidAccountNumber);
+        let id = insert(&pool, &card_number, DEFAULT_MERCHANT_ID, outcome)
+            .await
+            .unwrap();
+
+        let verification = get(&pool, id).await.unwrap();
+        assert_eq!(verification.status, Status::Declined);
+        assert_eq!(
+            verification.decline_code,
+            Some(DeclineCode::InvalidAccountNumber)
+        );
+        assert_eq!(verification.hold_id, None);
+        assert_eq!(verification.hold_status, None);
+    }
+}
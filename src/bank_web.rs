@@ -1,23 +1,70 @@
 use axum::{
-    routing::{get, post},
+    http::StatusCode,
+    routing::{get, post, MethodRouter},
     Router,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use utoipa::ToSchema;
 
-use crate::bank::accounts::AccountService;
+use crate::bank::{self, accounts::AccountService};
+use crate::errors::ErrorCode;
+use crate::shutdown::Shutdown;
 
+mod accounts;
+pub mod auth;
+mod disputes;
+mod health;
+mod ledger;
+mod merchants;
+mod metrics;
+mod openapi;
 mod payments;
 mod refunds;
+mod settlements;
+mod shutdown;
+mod v2;
+mod verifications;
+mod versions;
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
-pub struct ErrorResponseBody {
-    error: String,
+/// Problem details of an error (RFC 7807).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct ProblemResponseBody {
+    #[serde(rename = "type")]
+    type_: String,
+    title: String,
+    status: u16,
+    detail: String,
+    code: ErrorCode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    invalid_params: Vec<InvalidParam>,
 }
-impl ErrorResponseBody {
-    pub fn new(s: &'static str) -> Self {
+
+/// A request field which failed validation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct InvalidParam {
+    name: String,
+    code: ErrorCode,
+    reason: String,
+}
+
+impl ProblemResponseBody {
+    pub fn new(status: StatusCode, code: ErrorCode) -> Self {
         Self {
-            error: s.to_string(),
+            type_: format!("/problems/{}", code.as_str()),
+            title: code.title().to_string(),
+            status: status.as_u16(),
+            detail: code.detail().to_string(),
+            code,
+            invalid_params: code
+                .field()
+                .map(|name| InvalidParam {
+                    name: name.to_string(),
+                    code,
+                    reason: code.detail().to_string(),
+                })
+                .into_iter()
+                .collect(),
         }
     }
 }
@@ -27,6 +74,12 @@ pub struct BankWeb<T> {
     pool: PgPool,
     #[allow(dead_code)]
     account_service: T,
+    refund_policy: bank::refunds::Policy,
+    api_tokens: auth::Tokens,
+    risk_engine: bank::risk::Engine,
+    review_period: std::time::Duration,
+    spend_limits: bank::spending::Limits,
+    shutdown: Shutdown,
 }
 
 impl<T: AccountService> BankWeb<T> {
@@ -34,24 +87,147 @@ impl<T: AccountService> BankWeb<T> {
         Self {
             pool,
             account_service,
+            refund_policy: bank::refunds::Policy::default(),
+            api_tokens: auth::Tokens::default(),
+            risk_engine: bank::risk::Engine::default(),
+            review_period: bank::reviews::DEFAULT_REVIEW_PERIOD,
+            spend_limits: bank::spending::Limits::default(),
+            shutdown: Shutdown::new(),
         }
     }
 
-    pub fn into_router(self) -> Router {
-        Router::new()
-            .route("/api/payments", post(payments::post::<T>))
-            .route("/api/payments/:payment_id", get(payments::get::<T>))
-            .route(
+    pub fn with_refund_policy(mut self, refund_policy: bank::refunds::Policy) -> Self {
+        self.refund_policy = refund_policy;
+        self
+    }
+
+    pub fn with_api_tokens(mut self, api_tokens: auth::Tokens) -> Self {
+        self.api_tokens = api_tokens;
+        self
+    }
+
+    pub fn with_risk_engine(mut self, risk_engine: bank::risk::Engine) -> Self {
+        self.risk_engine = risk_engine;
+        self
+    }
+
+    pub fn with_review_period(mut self, review_period: std::time::Duration) -> Self {
+        self.review_period = review_period;
+        self
+    }
+
+    pub fn with_spend_limits(mut self, spend_limits: bank::spending::Limits) -> Self {
+        self.spend_limits = spend_limits;
+        self
+    }
+
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// The routes of v1, by path. Every route must be documented, see
+    /// `openapi::ApiDoc`.
+    fn routes() -> Vec<(&'static str, MethodRouter<Self>)> {
+        vec![
+            (
+                "/api/payments",
+                post(payments::post::<T>).get(payments::index::<T>),
+            ),
+            ("/api/payments/:payment_id", get(payments::get::<T>)),
+            ("/api/payments/:payment_id/tries", get(payments::tries::<T>)),
+            (
+                "/api/payments/:payment_id/attempts",
+                get(payments::attempts::<T>),
+            ),
+            (
+                "/api/payments/review_queue",
+                get(payments::review_queue::<T>),
+            ),
+            (
+                "/api/payments/:payment_id/approve",
+                post(payments::approve::<T>),
+            ),
+            (
+                "/api/payments/:payment_id/reject",
+                post(payments::reject::<T>),
+            ),
+            (
                 "/api/payments/:payment_id/refunds",
-                post(refunds::post::<T>),
-            )
-            .route(
+                post(refunds::post::<T>).get(refunds::index::<T>),
+            ),
+            (
                 "/api/payments/:payment_id/refunds/:refund_id",
                 get(refunds::get::<T>),
-            )
+            ),
+            (
+                "/api/payments/:payment_id/refunds/:refund_id/approve",
+                post(refunds::approve::<T>),
+            ),
+            (
+                "/api/payments/:payment_id/refunds/:refund_id/reject",
+                post(refunds::reject::<T>),
+            ),
+            (
+                "/api/payments/:payment_id/disputes",
+                post(disputes::post::<T>).get(disputes::index::<T>),
+            ),
+            (
+                "/api/payments/:payment_id/disputes/:dispute_id",
+                get(disputes::get::<T>),
+            ),
+            (
+                "/api/payments/:payment_id/disputes/:dispute_id/evidence",
+                post(disputes::evidence::<T>),
+            ),
+            (
+                "/api/payments/:payment_id/disputes/:dispute_id/resolve",
+                post(disputes::resolve::<T>),
+            ),
+            (
+                "/api/merchants/:merchant_id/fee_schedule",
+                get(merchants::get_fee_schedule::<T>).put(merchants::put_fee_schedule::<T>),
+            ),
+            (
+                "/api/accounts/:account_number/spending",
+                get(accounts::spending::<T>),
+            ),
+            (
+                "/api/verifications/:verification_id",
+                get(verifications::get::<T>),
+            ),
+            ("/api/ledger/accounts", get(ledger::index::<T>)),
+            ("/api/ledger/accounts/:account", get(ledger::get::<T>)),
+            ("/api/settlement_batches", get(settlements::index::<T>)),
+            (
+                "/api/settlement_batches/:batch_id",
+                get(settlements::get::<T>),
+            ),
+        ]
+    }
+
+    pub fn into_router(self) -> Router {
+        let route = |router: Router<Self>, (path, method_router)| router.route(path, method_router);
+        let v1 = Self::routes()
+            .into_iter()
+            .fold(Router::new(), route)
+            .layer(axum::middleware::map_response(versions::deprecate));
+        let router = v2::routes()
+            .into_iter()
+            .fold(v1, route)
+            .layer(axum::middleware::from_fn_with_state(
+                self.clone(),
+                shutdown::guard::<T, _>,
+            ))
+            .merge(openapi::router())
             .layer(axum_tracing_opentelemetry::opentelemetry_tracing_layer())
+            .layer(axum::middleware::from_fn(metrics::track))
+            .merge(health::router())
+            .merge(metrics::router())
             .with_state(self)
-            .with_state(())
+            .with_state(());
+
+        versions::select(router)
     }
 }
 
@@ -59,7 +235,10 @@ impl<T: AccountService> BankWeb<T> {
 pub mod tests {
     use axum::{
         body::Bytes,
-        http::{header::CONTENT_TYPE, Method, Request},
+        http::{
+            header::{AUTHORIZATION, CONTENT_TYPE},
+            Method, Request,
+        },
     };
     use http_body::combinators::UnsyncBoxBody;
     use serde::{de::DeserializeOwned, Serialize};
@@ -70,12 +249,12 @@ pub mod tests {
 
     impl BankWeb<DummyService> {
         pub async fn new_test() -> Self {
-            Self {
-                pool: crate::pg_pool()
+            Self::new(
+                crate::pg_pool()
                     .await
                     .expect("failed to create postgres pool"),
-                account_service: DummyService::default(),
-            }
+                DummyService::default(),
+            )
         }
 
         pub async fn new_test_with_response(response: impl Into<String>) -> Self {
@@ -113,16 +292,74 @@ pub mod tests {
         uri: impl AsRef<str>,
         body: &T,
     ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
-        let request = Request::builder()
-            .method(Method::POST)
+        post_with_headers(router, uri, body, &[]).await
+    }
+
+    /// Sends a POST request authenticated with the bearer `token`.
+    pub async fn post_as<T: Serialize>(
+        router: &Router,
+        uri: impl AsRef<str>,
+        body: &T,
+        token: &str,
+    ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
+        let authorization = format!("Bearer {token}");
+        post_with_headers(
+            router,
+            uri,
+            body,
+            &[(AUTHORIZATION.as_str(), &authorization)],
+        )
+        .await
+    }
+
+    pub async fn post_with_headers<T: Serialize>(
+        router: &Router,
+        uri: impl AsRef<str>,
+        body: &T,
+        headers: &[(&str, &str)],
+    ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
+        send_json(router, Method::POST, uri, body, headers).await
+    }
+
+    /// Sends a PUT request authenticated with the bearer `token`.
+    pub async fn put_as<T: Serialize>(
+        router: &Router,
+        uri: impl AsRef<str>,
+        body: &T,
+        token: &str,
+    ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
+        let authorization = format!("Bearer {token}");
+        send_json(
+            router,
+            Method::PUT,
+            uri,
+            body,
+            &[(AUTHORIZATION.as_str(), &authorization)],
+        )
+        .await
+    }
+
+    pub async fn send_json<T: Serialize>(
+        router: &Router,
+        method: Method,
+        uri: impl AsRef<str>,
+        body: &T,
+        headers: &[(&str, &str)],
+    ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
+        let mut request = Request::builder()
+            .method(method)
             .uri(uri.as_ref())
-            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_TYPE, "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request
             .body(
                 serde_json::to_vec(body)
-                    .expect("failed to serialize POST body")
+                    .expect("failed to serialize request body")
                     .into(),
             )
-            .expect("failed to build POST request");
+            .expect("failed to build request");
         send_request(router, request).await
     }
 
@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::BankWeb;
+use crate::bank::{accounts::AccountService, spending};
+use crate::errors::ApiError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct LimitUsage {
+    /// `None` when there's no cap.
+    pub limit: Option<i64>,
+    pub spent_amount: i64,
+    pub remaining_amount: Option<i64>,
+}
+
+impl LimitUsage {
+    fn new(limit: Option<i64>, spent_amount: i64) -> Self {
+        Self {
+            limit,
+            spent_amount,
+            remaining_amount: limit.map(|limit| (limit - spent_amount).max(0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = Spending)]
+pub struct ResponseData {
+    pub account_number: String,
+    pub daily: LimitUsage,
+    pub monthly: LimitUsage,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = SpendingResponseBody)]
+pub struct ResponseBody {
+    #[schema(value_type = Spending)]
+    pub data: ResponseData,
+}
+
+/// Reads how much an account spent in the current day and month, against its
+/// spend limits.
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{account_number}/spending",
+    tag = "accounts",
+    params(
+        ("account_number" = String, Path, description = "Number of the account"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = SpendingResponseBody),
+    )
+)]
+pub async fn spending<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(account_number): Path<String>,
+) -> Result<(StatusCode, Json<ResponseBody>), ApiError> {
+    let usage = spending::usage(&bank_web.pool, &account_number).await?;
+    let limits = bank_web.spend_limits;
+
+    Ok((
+        StatusCode::OK,
+        Json(ResponseBody {
+            data: ResponseData {
+                account_number,
+                daily: LimitUsage::new(limits.daily, usage.daily_amount),
+                monthly: LimitUsage::new(limits.monthly, usage.monthly_amount),
+            },
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bank::payment_instruments::Card,
+        bank_web::{
+            payments,
+            tests::{deserialize_response_body, get, post},
+        },
+    };
+
+    #[tokio::test]
+    async fn should_report_spending_against_limits() {
+        let limits = spending::Limits {
+            daily: Some(100_000_000),
+            monthly: None,
+        };
+        let router = BankWeb::new_test()
+            .await
+            .with_spend_limits(limits)
+            .into_router();
+        let card = Card::new_test();
+        let uri = format!("/api/accounts/{}/spending", card.account_number());
+
+        let response = get(&router, &uri).await;
+        assert_eq!(response.status(), 200);
+        let before = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(before.data.daily.limit, limits.daily);
+        assert_eq!(before.data.monthly.remaining_amount, None);
+
+        let request_body = payments::RequestBody {
+            payment: payments::RequestData {
+                amount: 1205,
+                card_number: card.into(),
+                merchant_id: None,
+            },
+        };
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 201);
+
+        // other tests may be paying from the same account meanwhile
+        let response = get(&router, &uri).await;
+        let after = deserialize_response_body::<ResponseBody>(response).await;
+        assert!(after.data.daily.spent_amount >= before.data.daily.spent_amount + 1205);
+        assert_eq!(
+            after.data.daily.remaining_amount,
+            Some(100_000_000 - after.data.daily.spent_amount)
+        );
+    }
+}
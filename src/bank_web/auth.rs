@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+
+use super::BankWeb;
+use crate::bank::accounts::AccountService;
+use crate::errors::{ApiError, ErrorCode};
+
+/// API tokens of the principals allowed to authenticate, keyed by token.
+#[derive(Debug, Clone, Default)]
+pub struct Tokens(HashMap<String, String>);
+
+impl Tokens {
+    fn principal(&self, token: &str) -> Option<&str> {
+        self.0.get(token).map(String::as_str)
+    }
+}
+
+impl FromIterator<(String, String)> for Tokens {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(principal, token)| (token, principal))
+                .collect(),
+        )
+    }
+}
+
+/// The principal who authenticated a request with a bearer token.
+///
+/// Rejects the request with a 401 if the `Authorization` header is missing or
+/// the token is unknown. Handlers for which authentication is optional can
+/// extract an `Option<Principal>` instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal(pub String);
+
+/// The principal allowed to manage every merchant.
+pub const ADMIN: &str = "admin";
+
+/// The principal the account service authenticates as, on behalf of card
+/// holders.
+pub const ACCOUNT_SERVICE: &str = "account_service";
+
+impl Principal {
+    /// Whether the principal may manage a merchant: only the merchant itself
+    /// (authenticated as the principal named after its id) or the admin can.
+    pub fn manages(&self, merchant_id: &str) -> bool {
+        self.0 == merchant_id || self.0 == ADMIN
+    }
+
+    /// Whether the principal may act on behalf of card holders, e.g. open and
+    /// decide disputes: only the account service or the admin can.
+    pub fn acts_for_card_holders(&self) -> bool {
+        self.0 == ACCOUNT_SERVICE || self.0 == ADMIN
+    }
+}
+
+#[async_trait]
+impl<T: AccountService> FromRequestParts<BankWeb<T>> for Principal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        bank_web: &BankWeb<T>,
+    ) -> Result<Self, Self::Rejection> {
+        parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| bank_web.api_tokens.principal(token))
+            .map(|principal| Self(principal.to_string()))
+            .ok_or(ApiError::Unauthorized(ErrorCode::AuthenticationRequired))
+    }
+}
@@ -0,0 +1,562 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{auth::Principal, openapi, BankWeb};
+use crate::bank::{
+    accounts::AccountService,
+    disputes::{self, Resolution},
+    payments::{self, Status},
+};
+use crate::errors::{ApiError, ErrorCode};
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = NewDispute)]
+pub struct RequestData {
+    amount: i32,
+    reason: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = DisputeRequestBody)]
+pub struct RequestBody {
+    #[schema(value_type = NewDispute)]
+    dispute: RequestData,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = Evidence)]
+pub struct EvidenceRequestData {
+    text: String,
+    #[serde(default)]
+    attachments: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct EvidenceRequestBody {
+    #[schema(value_type = Evidence)]
+    evidence: EvidenceRequestData,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct ResolveRequestBody {
+    #[schema(value_type = DisputeResolution)]
+    resolution: Resolution,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = Dispute)]
+pub struct ResponseData {
+    id: Uuid,
+    payment_id: Uuid,
+    amount: i32,
+    reason: String,
+    #[schema(value_type = DisputeStatus)]
+    status: disputes::Status,
+    evidence_text: Option<String>,
+    evidence_attachments: Vec<String>,
+    debited_amount: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = DisputeResponseBody)]
+pub struct ResponseBody {
+    #[schema(value_type = Dispute)]
+    data: ResponseData,
+}
+
+impl From<disputes::Dispute> for ResponseData {
+    fn from(dispute: disputes::Dispute) -> Self {
+        Self {
+            id: dispute.id,
+            payment_id: dispute.payment_id,
+            amount: dispute.amount,
+            reason: dispute.reason,
+            status: dispute.status,
+            evidence_text: dispute.evidence_text,
+            evidence_attachments: dispute.evidence_attachments,
+            debited_amount: dispute.debited_amount,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = DisputeListResponseBody)]
+pub struct ListResponseBody {
+    #[schema(value_type = Vec<Dispute>)]
+    data: Vec<ResponseData>,
+}
+
+type DisputeResponse = Result<(StatusCode, Json<ResponseBody>), ApiError>;
+
+/// Responds with a dispute, as it's persisted.
+async fn dispute_response(pool: &PgPool, status: StatusCode, dispute_id: Uuid) -> DisputeResponse {
+    let dispute = disputes::get(pool, dispute_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::DisputeNotFound))?;
+
+    Ok((
+        status,
+        Json(ResponseBody {
+            data: dispute.into(),
+        }),
+    ))
+}
+
+/// Opens a dispute against a payment. Called by the account service on behalf
+/// of the card holder: only the account service or the admin can open it.
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/disputes",
+    tag = "disputes",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    request_body = DisputeRequestBody,
+    security(("bearer_token" = [])),
+    responses(
+        (status = 201, description = "Created", body = DisputeResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 403, response = openapi::Forbidden),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+        (status = 422, response = openapi::Invalid),
+    )
+)]
+pub async fn post<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+    principal: Principal,
+    Json(body): Json<RequestBody>,
+) -> DisputeResponse {
+    if !principal.acts_for_card_holders() {
+        return Err(ApiError::Forbidden(ErrorCode::NotAccountServicePrincipal));
+    }
+
+    let payment = payments::get(&bank_web.pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+    if payment.status != Status::Approved {
+        return Err(ApiError::Conflict(ErrorCode::PaymentNotApproved));
+    }
+
+    if body.dispute.amount <= 0 {
+        return Err(ApiError::Validation(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::NonPositiveDisputeAmount,
+        ));
+    }
+
+    let dispute_id = disputes::open(
+        &bank_web.pool,
+        payment_id,
+        body.dispute.amount,
+        &body.dispute.reason,
+    )
+    .await?
+    .ok_or(ApiError::Validation(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::ExcessiveDisputeAmount,
+    ))?;
+
+    dispute_response(&bank_web.pool, StatusCode::CREATED, dispute_id).await
+}
+
+/// Fetches a dispute, which is only visible under the payment it belongs to.
+async fn get_of_payment(
+    pool: &PgPool,
+    payment_id: Uuid,
+    dispute_id: Uuid,
+) -> Result<disputes::Dispute, ApiError> {
+    let dispute = disputes::get(pool, dispute_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::DisputeNotFound))?;
+
+    if dispute.payment_id != payment_id {
+        return Err(ApiError::NotFound(ErrorCode::DisputeNotFound));
+    }
+
+    Ok(dispute)
+}
+
+/// Submits the merchant's evidence against a dispute. Only the merchant of the
+/// payment or the admin can submit it.
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/disputes/{dispute_id}/evidence",
+    tag = "disputes",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+        ("dispute_id" = Uuid, Path, description = "Id of the dispute"),
+    ),
+    request_body = EvidenceRequestBody,
+    security(("bearer_token" = [])),
+    responses(
+        (status = 200, description = "OK", body = DisputeResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 403, response = openapi::Forbidden),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+    )
+)]
+pub async fn evidence<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path((payment_id, dispute_id)): Path<(Uuid, Uuid)>,
+    principal: Principal,
+    Json(body): Json<EvidenceRequestBody>,
+) -> DisputeResponse {
+    get_of_payment(&bank_web.pool, payment_id, dispute_id).await?;
+
+    let payment = payments::get(&bank_web.pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+    if !principal.manages(&payment.merchant_id) {
+        return Err(ApiError::Forbidden(ErrorCode::NotMerchantPrincipal));
+    }
+
+    let submitted = disputes::submit_evidence(
+        &bank_web.pool,
+        dispute_id,
+        &body.evidence.text,
+        &body.evidence.attachments,
+    )
+    .await?;
+    if !submitted {
+        return Err(ApiError::Conflict(ErrorCode::DisputeAlreadyDecided));
+    }
+
+    dispute_response(&bank_web.pool, StatusCode::OK, dispute_id).await
+}
+
+/// Decides a dispute. Like opening it, this is up to the account service (or
+/// the admin): the merchant can't decide its own disputes.
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/disputes/{dispute_id}/resolve",
+    tag = "disputes",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+        ("dispute_id" = Uuid, Path, description = "Id of the dispute"),
+    ),
+    request_body = ResolveRequestBody,
+    security(("bearer_token" = [])),
+    responses(
+        (status = 200, description = "OK", body = DisputeResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 403, response = openapi::Forbidden),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+    )
+)]
+pub async fn resolve<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path((payment_id, dispute_id)): Path<(Uuid, Uuid)>,
+    principal: Principal,
+    Json(body): Json<ResolveRequestBody>,
+) -> DisputeResponse {
+    if !principal.acts_for_card_holders() {
+        return Err(ApiError::Forbidden(ErrorCode::NotAccountServicePrincipal));
+    }
+
+    get_of_payment(&bank_web.pool, payment_id, dispute_id).await?;
+
+    let resolved = disputes::resolve(&bank_web.pool, dispute_id, body.resolution).await?;
+    if !resolved {
+        return Err(ApiError::Conflict(ErrorCode::DisputeAlreadyDecided));
+    }
+
+    dispute_response(&bank_web.pool, StatusCode::OK, dispute_id).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments/{payment_id}/disputes",
+    tag = "disputes",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = DisputeListResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn index<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    payments::get(&bank_web.pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+
+    let data = disputes::list(&bank_web.pool, payment_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ListResponseBody {
+            data: data.into_iter().map(ResponseData::from).collect(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments/{payment_id}/disputes/{dispute_id}",
+    tag = "disputes",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+        ("dispute_id" = Uuid, Path, description = "Id of the dispute"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = DisputeResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn get<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path((payment_id, dispute_id)): Path<(Uuid, Uuid)>,
+) -> DisputeResponse {
+    let dispute = get_of_payment(&bank_web.pool, payment_id, dispute_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ResponseBody {
+            data: dispute.into(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bank::{payment_instruments::Card, payments::DEFAULT_MERCHANT_ID, refunds::Reason},
+        bank_web::{
+            auth::{Tokens, ACCOUNT_SERVICE, ADMIN},
+            payments, refunds,
+            tests::{deserialize_response_body, get, post, post_as},
+            ProblemResponseBody,
+        },
+    };
+
+    const ISSUER_TOKEN: &str = "issuer-token";
+    const MERCHANT_TOKEN: &str = "merchant-token";
+    const ADMIN_TOKEN: &str = "admin-token";
+
+    async fn setup() -> (axum::Router, Uuid) {
+        let tokens = [
+            (ACCOUNT_SERVICE.to_string(), ISSUER_TOKEN.to_string()),
+            (DEFAULT_MERCHANT_ID.to_string(), MERCHANT_TOKEN.to_string()),
+            (ADMIN.to_string(), ADMIN_TOKEN.to_string()),
+        ]
+        .into_iter()
+        .collect::<Tokens>();
+        let router = BankWeb::new_test()
+            .await
+            .with_api_tokens(tokens)
+            .into_router();
+
+        let request_body = payments::RequestBody {
+            payment: payments::RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 201);
+
+        let response_body = deserialize_response_body::<payments::ResponseBody>(response).await;
+        (router, response_body.data.id)
+    }
+
+    fn dispute_request(amount: i32) -> RequestBody {
+        RequestBody {
+            dispute: RequestData {
+                amount,
+                reason: "goods not received".to_string(),
+            },
+        }
+    }
+
+    async fn open_dispute(router: &axum::Router, payment_id: Uuid, amount: i32) -> ResponseData {
+        let uri = format!("/api/payments/{payment_id}/disputes");
+        let response = post_as(router, uri, &dispute_request(amount), ISSUER_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        deserialize_response_body::<ResponseBody>(response)
+            .await
+            .data
+    }
+
+    #[tokio::test]
+    async fn should_require_authentication_to_open_disputes() {
+        let (router, payment_id) = setup().await;
+
+        let uri = format!("/api/payments/{payment_id}/disputes");
+        let response = post(&router, &uri, &dispute_request(500)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = post_as(&router, &uri, &dispute_request(500), "unknown").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn should_only_let_the_account_service_or_admin_open_and_decide_disputes() {
+        let (router, payment_id) = setup().await;
+
+        let uri = format!("/api/payments/{payment_id}/disputes");
+        let response = post_as(&router, &uri, &dispute_request(500), MERCHANT_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::NotAccountServicePrincipal);
+
+        let response = post_as(&router, &uri, &dispute_request(500), ADMIN_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let dispute = deserialize_response_body::<ResponseBody>(response)
+            .await
+            .data;
+
+        // the merchant can't win its own dispute
+        let uri = format!("/api/payments/{payment_id}/disputes/{}/resolve", dispute.id);
+        let request_body = ResolveRequestBody {
+            resolution: Resolution::Won,
+        };
+        let response = post_as(&router, &uri, &request_body, MERCHANT_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::NotAccountServicePrincipal);
+
+        let response = post_as(&router, &uri, &request_body, ADMIN_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, disputes::Status::Won);
+    }
+
+    #[tokio::test]
+    async fn should_debit_merchant_for_lost_disputes() {
+        let (router, payment_id) = setup().await;
+
+        let dispute = open_dispute(&router, payment_id, 500).await;
+        assert_eq!(dispute.status, disputes::Status::Opened);
+
+        let uri = format!("/api/payments/{payment_id}/disputes/{}", dispute.id);
+        let request_body = EvidenceRequestBody {
+            evidence: EvidenceRequestData {
+                text: "signed delivery receipt".to_string(),
+                attachments: vec!["https://example.com/receipt.pdf".to_string()],
+            },
+        };
+        let response = post_as(
+            &router,
+            format!("{uri}/evidence"),
+            &request_body,
+            MERCHANT_TOKEN,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(
+            response_body.data.status,
+            disputes::Status::EvidenceSubmitted
+        );
+        assert_eq!(
+            response_body.data.evidence_attachments,
+            request_body.evidence.attachments
+        );
+
+        let request_body = ResolveRequestBody {
+            resolution: Resolution::Lost,
+        };
+        let response = post_as(
+            &router,
+            format!("{uri}/resolve"),
+            &request_body,
+            ISSUER_TOKEN,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, disputes::Status::Lost);
+        assert_eq!(response_body.data.debited_amount, Some(500));
+
+        let response = post_as(
+            &router,
+            format!("{uri}/resolve"),
+            &request_body,
+            ISSUER_TOKEN,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = get(&router, &uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, disputes::Status::Lost);
+
+        let response = get(&router, format!("/api/payments/{payment_id}/disputes")).await;
+        let response_body = deserialize_response_body::<ListResponseBody>(response).await;
+        assert_eq!(response_body.data.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_only_let_the_merchant_or_admin_submit_evidence() {
+        let (router, payment_id) = setup().await;
+
+        let dispute = open_dispute(&router, payment_id, 500).await;
+        let uri = format!(
+            "/api/payments/{payment_id}/disputes/{}/evidence",
+            dispute.id
+        );
+        let request_body = EvidenceRequestBody {
+            evidence: EvidenceRequestData {
+                text: "signed delivery receipt".to_string(),
+                attachments: vec![],
+            },
+        };
+
+        let response = post(&router, &uri, &request_body).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = post_as(&router, &uri, &request_body, ISSUER_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::NotMerchantPrincipal);
+
+        let response = post_as(&router, &uri, &request_body, ADMIN_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn should_not_refund_disputed_amounts() {
+        let (router, payment_id) = setup().await;
+
+        open_dispute(&router, payment_id, 1000).await;
+
+        let response = get(&router, format!("/api/payments/{payment_id}")).await;
+        let response_body = deserialize_response_body::<payments::ResponseBody>(response).await;
+        assert_eq!(response_body.data.disputed_amount, 1000);
+        assert_eq!(response_body.data.refundable_amount, 205);
+
+        let request_body = refunds::RequestBody {
+            refund: refunds::RequestData {
+                amount: 500,
+                reason: Some(Reason::RequestedByCustomer),
+            },
+        };
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        let response = post(&router, uri, &request_body).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // nor dispute them twice
+        let uri = format!("/api/payments/{payment_id}/disputes");
+        let response = post_as(&router, uri, &dispute_request(500), ISSUER_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashSet, future::Future, time::Duration};
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use super::BankWeb;
+use crate::bank::accounts::AccountService;
+
+/// How long a dependency has to answer a readiness check.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = HealthStatus)]
+pub enum Status {
+    Ok,
+    Degraded,
+}
+
+/// Status of a dependency.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = HealthCheck)]
+pub struct Check {
+    #[schema(value_type = HealthStatus)]
+    pub status: Status,
+    /// Why the dependency is degraded, if it is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Check {
+    fn new(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Self {
+                status: Status::Ok,
+                error: None,
+            },
+            Err(error) => Self {
+                status: Status::Degraded,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = ReadinessChecks)]
+pub struct Checks {
+    /// The database answers queries.
+    #[schema(value_type = HealthCheck)]
+    pub database: Check,
+    /// Every migration embedded in the binary was applied to the database.
+    #[schema(value_type = HealthCheck)]
+    pub migrations: Check,
+    /// The account service is reachable, see `AccountService::health`.
+    #[schema(value_type = HealthCheck)]
+    pub account_service: Check,
+}
+
+impl Checks {
+    fn status(&self) -> Status {
+        let checks = [&self.database, &self.migrations, &self.account_service];
+        if checks.iter().all(|check| check.status == Status::Ok) {
+            Status::Ok
+        } else {
+            Status::Degraded
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = LivenessResponseBody)]
+pub struct LivenessResponseBody {
+    #[schema(value_type = HealthStatus)]
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = ReadinessResponseBody)]
+pub struct ReadinessResponseBody {
+    #[schema(value_type = HealthStatus)]
+    pub status: Status,
+    #[schema(value_type = ReadinessChecks)]
+    pub checks: Checks,
+}
+
+/// Serves the liveness and readiness probes. They're left out of the API's
+/// traces, since orchestrators call them every few seconds.
+pub fn router<T: AccountService>() -> Router<BankWeb<T>> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz::<T>))
+}
+
+/// Runs a check, failing it if it takes longer than `CHECK_TIMEOUT`.
+async fn check(future: impl Future<Output = Result<(), String>>) -> Check {
+    let result = tokio::time::timeout(CHECK_TIMEOUT, future)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {CHECK_TIMEOUT:?}")));
+    Check::new(result)
+}
+
+async fn check_database(pool: &PgPool) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+async fn check_migrations(pool: &PgPool) -> Result<(), String> {
+    let applied: HashSet<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await
+            .map_err(|error| error.to_string())?
+            .into_iter()
+            .collect();
+
+    let pending: Vec<String> = crate::MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration.version.to_string())
+        .collect();
+
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("pending migrations: {}", pending.join(", ")))
+    }
+}
+
+/// Answers as long as the process is able to serve requests, whatever the
+/// state of its dependencies.
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is alive", body = LivenessResponseBody),
+    )
+)]
+pub async fn healthz() -> Json<LivenessResponseBody> {
+    Json(LivenessResponseBody { status: Status::Ok })
+}
+
+/// Checks the dependencies needed to serve the API, concurrently.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every dependency is ok", body = ReadinessResponseBody),
+        (status = 503, description = "A dependency is degraded", body = ReadinessResponseBody),
+    )
+)]
+pub async fn readyz<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+) -> (StatusCode, Json<ReadinessResponseBody>) {
+    let (database, migrations, account_service) = tokio::join!(
+        check(check_database(&bank_web.pool)),
+        check(check_migrations(&bank_web.pool)),
+        check(bank_web.account_service.health()),
+    );
+    let checks = Checks {
+        database,
+        migrations,
+        account_service,
+    };
+
+    let status = checks.status();
+    let status_code = match status {
+        Status::Ok => StatusCode::OK,
+        Status::Degraded => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    if status == Status::Degraded {
+        tracing::warn!(?checks, "not ready");
+    }
+
+    (status_code, Json(ReadinessResponseBody { status, checks }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::accounts::DummyService;
+    use crate::bank_web::tests::{deserialize_response_body, get};
+
+    #[tokio::test]
+    async fn should_be_alive() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let response = get(&router, "/healthz").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<LivenessResponseBody>(response).await;
+        assert_eq!(response_body.status, Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn should_be_ready() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let response = get(&router, "/readyz").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<serde_json::Value>(response).await;
+        assert_eq!(
+            response_body,
+            serde_json::json!({
+                "status": "ok",
+                "checks": {
+                    "database": { "status": "ok" },
+                    "migrations": { "status": "ok" },
+                    "account_service": { "status": "ok" },
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_be_ready_when_database_is_unreachable() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://postgres@localhost:1/unreachable")
+            .unwrap();
+        let router = BankWeb::new(pool, DummyService::default()).into_router();
+
+        let response = get(&router, "/readyz").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response_body = deserialize_response_body::<ReadinessResponseBody>(response).await;
+        assert_eq!(response_body.status, Status::Degraded);
+        assert_eq!(response_body.checks.database.status, Status::Degraded);
+        assert_eq!(response_body.checks.migrations.status, Status::Degraded);
+        assert_eq!(response_body.checks.account_service.status, Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn should_not_be_ready_when_account_service_is_unreachable() {
+        let bank_web = BankWeb::new_test_with_response("service_unavailable").await;
+        let router = bank_web.into_router();
+
+        let response = get(&router, "/readyz").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response_body = deserialize_response_body::<ReadinessResponseBody>(response).await;
+        assert_eq!(response_body.status, Status::Degraded);
+        assert_eq!(response_body.checks.database.status, Status::Ok);
+        assert_eq!(
+            response_body.checks.account_service,
+            Check {
+                status: Status::Degraded,
+                error: Some("service_unavailable".to_string()),
+            }
+        );
+    }
+}
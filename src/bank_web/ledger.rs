@@ -0,0 +1,178 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::{openapi, BankWeb};
+use crate::bank::{
+    accounts::AccountService,
+    ledger::{self, Account},
+};
+use crate::errors::ApiError;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BalanceParams {
+    /// Restricts the balance to the money movements of a single payment.
+    payment_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = LedgerBalance)]
+pub struct ResponseData {
+    #[schema(value_type = LedgerAccount)]
+    account: Account,
+    balance: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = LedgerBalanceResponseBody)]
+pub struct ResponseBody {
+    #[schema(value_type = LedgerBalance)]
+    data: ResponseData,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = LedgerBalanceListResponseBody)]
+pub struct ListResponseBody {
+    #[schema(value_type = Vec<LedgerBalance>)]
+    data: Vec<ResponseData>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/ledger/accounts",
+    tag = "ledger",
+    params(BalanceParams),
+    responses(
+        (status = 200, description = "OK", body = LedgerBalanceListResponseBody),
+    )
+)]
+pub async fn index<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Query(params): Query<BalanceParams>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    let mut data = Vec::with_capacity(Account::ALL.len());
+    for account in Account::ALL {
+        let balance = ledger::balance(&bank_web.pool, account, params.payment_id).await?;
+        data.push(ResponseData { account, balance });
+    }
+
+    Ok((StatusCode::OK, Json(ListResponseBody { data })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/ledger/accounts/{account}",
+    tag = "ledger",
+    params(
+        ("account" = LedgerAccount, Path, description = "Ledger account"),
+        BalanceParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = LedgerBalanceResponseBody),
+        (status = 400, response = openapi::Invalid),
+    )
+)]
+pub async fn get<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(account): Path<Account>,
+    Query(params): Query<BalanceParams>,
+) -> Result<(StatusCode, Json<ResponseBody>), ApiError> {
+    let balance = ledger::balance(&bank_web.pool, account, params.payment_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ResponseBody {
+            data: ResponseData { account, balance },
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bank::{payment_instruments::Card, refunds::Reason},
+        bank_web::{
+            payments, refunds,
+            tests::{deserialize_response_body, get, post},
+        },
+    };
+
+    async fn balance(router: &axum::Router, account: &str, payment_id: Uuid) -> i64 {
+        let uri = format!("/api/ledger/accounts/{account}?payment_id={payment_id}");
+        let response = get(router, uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        deserialize_response_body::<ResponseBody>(response)
+            .await
+            .data
+            .balance
+    }
+
+    #[tokio::test]
+    async fn should_balance_payments_and_refunds() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let request_body = payments::RequestBody {
+            payment: payments::RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let payment_id = deserialize_response_body::<payments::ResponseBody>(response)
+            .await
+            .data
+            .id;
+
+        assert_eq!(balance(&router, "customer_funds", payment_id).await, -1205);
+        assert_eq!(balance(&router, "customer_funds_held", payment_id).await, 0);
+        assert_eq!(
+            balance(&router, "merchant_receivable", payment_id).await,
+            1205
+        );
+
+        let request_body = refunds::RequestBody {
+            refund: refunds::RequestData {
+                amount: 205,
+                reason: Some(Reason::RequestedByCustomer),
+            },
+        };
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        let response = post(&router, uri, &request_body).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = get(
+            &router,
+            format!("/api/ledger/accounts?payment_id={payment_id}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ListResponseBody>(response).await;
+        let balances: Vec<_> = response_body
+            .data
+            .iter()
+            .map(|data| (data.account, data.balance))
+            .collect();
+        assert_eq!(
+            balances,
+            [
+                (Account::CustomerFunds, -1000),
+                (Account::CustomerFundsHeld, 0),
+                (Account::MerchantReceivable, 1000),
+                (Account::RefundsPayable, 0),
+            ]
+        );
+
+        let response = get(&router, "/api/ledger/accounts/unknown").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
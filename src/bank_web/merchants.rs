@@ -0,0 +1,219 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{auth::Principal, openapi, BankWeb};
+use crate::bank::{accounts::AccountService, fees};
+use crate::errors::{ApiError, ErrorCode};
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct FeeScheduleRequestBody {
+    #[schema(value_type = FeeSchedule)]
+    fee_schedule: fees::Schedule,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct FeeScheduleResponseData {
+    merchant_id: String,
+    #[serde(flatten)]
+    #[schema(value_type = FeeSchedule)]
+    schedule: fees::Schedule,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct FeeScheduleResponseBody {
+    data: FeeScheduleResponseData,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/merchants/{merchant_id}/fee_schedule",
+    tag = "merchants",
+    params(
+        ("merchant_id" = String, Path, description = "Id of the merchant"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = FeeScheduleResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn get_fee_schedule<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(merchant_id): Path<String>,
+) -> Result<(StatusCode, Json<FeeScheduleResponseBody>), ApiError> {
+    let schedule = fees::get(&bank_web.pool, &merchant_id)
+        .await?
+        .ok_or(ApiError::NotFound(ErrorCode::FeeScheduleNotFound))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(FeeScheduleResponseBody {
+            data: FeeScheduleResponseData {
+                merchant_id,
+                schedule,
+            },
+        }),
+    ))
+}
+
+/// Sets the fee schedule of a merchant, which only applies to payments approved
+/// from now on. Only the merchant or the admin can set it.
+#[utoipa::path(
+    put,
+    path = "/api/merchants/{merchant_id}/fee_schedule",
+    tag = "merchants",
+    params(
+        ("merchant_id" = String, Path, description = "Id of the merchant"),
+    ),
+    request_body = FeeScheduleRequestBody,
+    security(("bearer_token" = [])),
+    responses(
+        (status = 200, description = "OK", body = FeeScheduleResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 403, response = openapi::Forbidden),
+        (status = 422, response = openapi::Invalid),
+    )
+)]
+pub async fn put_fee_schedule<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(merchant_id): Path<String>,
+    principal: Principal,
+    Json(body): Json<FeeScheduleRequestBody>,
+) -> Result<(StatusCode, Json<FeeScheduleResponseBody>), ApiError> {
+    if !principal.manages(&merchant_id) {
+        return Err(ApiError::Forbidden(ErrorCode::NotMerchantPrincipal));
+    }
+
+    body.fee_schedule.validate().map_err(|violation| {
+        ApiError::Validation(StatusCode::UNPROCESSABLE_ENTITY, violation.into())
+    })?;
+
+    fees::put(&bank_web.pool, &merchant_id, &body.fee_schedule).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(FeeScheduleResponseBody {
+            data: FeeScheduleResponseData {
+                merchant_id,
+                schedule: body.fee_schedule,
+            },
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        bank::{payment_instruments::Card, refunds::Reason},
+        bank_web::{
+            auth::{Tokens, ADMIN},
+            payments, refunds,
+            tests::{deserialize_response_body, get, post, put_as},
+            ProblemResponseBody,
+        },
+    };
+
+    const ADMIN_TOKEN: &str = "admin-token";
+    const MERCHANT_TOKEN: &str = "merchant-token";
+
+    #[tokio::test]
+    async fn should_only_let_the_merchant_or_admin_set_its_fee_schedule() {
+        let merchant_id = Uuid::new_v4().to_string();
+        let tokens = [
+            (ADMIN.to_string(), ADMIN_TOKEN.to_string()),
+            (merchant_id.clone(), MERCHANT_TOKEN.to_string()),
+            ("other".to_string(), "other-token".to_string()),
+        ]
+        .into_iter()
+        .collect::<Tokens>();
+        let router = BankWeb::new_test()
+            .await
+            .with_api_tokens(tokens)
+            .into_router();
+
+        let uri = format!("/api/merchants/{merchant_id}/fee_schedule");
+        let request_body = FeeScheduleRequestBody {
+            fee_schedule: fees::Schedule::default(),
+        };
+        let response = put_as(&router, &uri, &request_body, "other-token").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::NotMerchantPrincipal);
+
+        let response = put_as(&router, &uri, &request_body, MERCHANT_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = put_as(&router, &uri, &request_body, ADMIN_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn should_charge_and_reverse_fees() {
+        let tokens = [(ADMIN.to_string(), ADMIN_TOKEN.to_string())]
+            .into_iter()
+            .collect::<Tokens>();
+        let router = BankWeb::new_test()
+            .await
+            .with_api_tokens(tokens)
+            .into_router();
+
+        let merchant_id = Uuid::new_v4().to_string();
+        let uri = format!("/api/merchants/{merchant_id}/fee_schedule");
+        let response = get(&router, &uri).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // 2.9% + 30
+        let request_body = FeeScheduleRequestBody {
+            fee_schedule: fees::Schedule {
+                percent_bps: 290,
+                fixed_amount: 30,
+                ..fees::Schedule::default()
+            },
+        };
+        let response = put_as(&router, &uri, &request_body, ADMIN_TOKEN).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = put_as(&router, &uri, &request_body, "unknown").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request_body = payments::RequestBody {
+            payment: payments::RequestData {
+                amount: 1000,
+                card_number: Card::new_test().into(),
+                merchant_id: Some(merchant_id.clone()),
+            },
+        };
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let payment = deserialize_response_body::<payments::ResponseBody>(response)
+            .await
+            .data;
+        assert_eq!(payment.fee_amount, 59);
+
+        let request_body = refunds::RequestBody {
+            refund: refunds::RequestData {
+                amount: 500,
+                reason: Some(Reason::RequestedByCustomer),
+            },
+        };
+        let response = post(
+            &router,
+            format!("/api/payments/{}/refunds", payment.id),
+            &request_body,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = get(&router, format!("/api/payments/{}", payment.id)).await;
+        let payment = deserialize_response_body::<payments::ResponseBody>(response)
+            .await
+            .data;
+        // half of 59, rounded up
+        assert_eq!(payment.refunded_fee_amount, 30);
+    }
+}
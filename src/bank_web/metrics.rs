@@ -0,0 +1,82 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, State},
+    http::{header::CONTENT_TYPE, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use super::BankWeb;
+use crate::bank::accounts::AccountService;
+use crate::metrics::metrics;
+
+/// Content type of Prometheus' text format.
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4";
+
+/// Serves the metrics. Scrapes aren't tracked as API requests.
+pub fn router<T: AccountService>() -> Router<BankWeb<T>> {
+    Router::new().route("/metrics", get(serve::<T>))
+}
+
+/// Records the latency of a request, labelled by the route it matched rather
+/// than by its path, so that ids don't make a label each.
+pub async fn track<B>(request: Request<B>, next: Next<B>) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+
+    let started_at = Instant::now();
+    let response = next.run(request).await;
+    metrics().http_request(
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        started_at.elapsed(),
+    );
+
+    response
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Metrics in Prometheus' text format", content_type = "text/plain", body = String),
+    )
+)]
+pub async fn serve<T: AccountService>(State(bank_web): State<BankWeb<T>>) -> impl IntoResponse {
+    metrics().observe_pool(&bank_web.pool);
+    ([(CONTENT_TYPE, TEXT_FORMAT)], metrics().render())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::bank_web::tests::get;
+
+    #[tokio::test]
+    async fn should_serve_metrics() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let response = get(&router, "/api/payments").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get(&router, "/metrics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], TEXT_FORMAT);
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let response_body = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(response_body.contains(
+            r#"http_request_duration_seconds_count{method="GET",route="/api/payments",status="200"}"#
+        ));
+        assert!(response_body.contains(r#"db_pool_connections{state="idle"}"#));
+    }
+}
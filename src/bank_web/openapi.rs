@@ -0,0 +1,448 @@
+use axum::{routing::get, Json, Router};
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        ContentBuilder, Deprecated, OneOfBuilder, Ref, RefOr, Response, ResponseBuilder,
+    },
+    Modify, OpenApi, ToResponse,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::{
+    accounts, disputes, health, ledger, merchants, metrics, payments, refunds, settlements, v2,
+    verifications, InvalidParam, ProblemResponseBody,
+};
+use crate::bank;
+use crate::errors::{ErrorCode, PROBLEM_JSON};
+
+/// OpenAPI document of the API, generated from the handlers' `utoipa::path`
+/// attributes and the request and response types.
+///
+/// Schemas are named after what they represent rather than after their type
+/// (e.g. `Payment` for `payments::ResponseData`), since several modules have
+/// types of the same name: fields referencing them must use the schema name
+/// as `value_type`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        payments::post,
+        payments::index,
+        payments::get,
+        payments::tries,
+        payments::attempts,
+        payments::review_queue,
+        payments::approve,
+        payments::reject,
+        refunds::post,
+        refunds::index,
+        refunds::get,
+        refunds::approve,
+        refunds::reject,
+        disputes::post,
+        disputes::index,
+        disputes::get,
+        disputes::evidence,
+        disputes::resolve,
+        merchants::get_fee_schedule,
+        merchants::put_fee_schedule,
+        accounts::spending,
+        verifications::get,
+        ledger::index,
+        ledger::get,
+        settlements::index,
+        settlements::get,
+        v2::payments::post,
+        v2::payments::index,
+        v2::payments::get,
+        health::healthz,
+        health::readyz,
+        metrics::serve,
+    ),
+    components(
+        schemas(
+            payments::RequestData,
+            payments::RequestBody,
+            payments::ResponseData,
+            payments::ResponseBody,
+            payments::ListResponseBody,
+            payments::TryData,
+            payments::TryListResponseBody,
+            payments::AttemptData,
+            payments::AttemptListResponseBody,
+            refunds::RequestData,
+            refunds::RequestBody,
+            refunds::ResponseData,
+            refunds::ResponseBody,
+            refunds::ListResponseBody,
+            disputes::RequestData,
+            disputes::RequestBody,
+            disputes::EvidenceRequestData,
+            disputes::EvidenceRequestBody,
+            disputes::ResolveRequestBody,
+            disputes::ResponseData,
+            disputes::ResponseBody,
+            disputes::ListResponseBody,
+            merchants::FeeScheduleRequestBody,
+            merchants::FeeScheduleResponseData,
+            merchants::FeeScheduleResponseBody,
+            accounts::LimitUsage,
+            accounts::ResponseData,
+            accounts::ResponseBody,
+            verifications::ResponseData,
+            verifications::ResponseBody,
+            ledger::ResponseData,
+            ledger::ResponseBody,
+            ledger::ListResponseBody,
+            settlements::LineData,
+            settlements::ResponseData,
+            settlements::ResponseBody,
+            settlements::ListResponseBody,
+            v2::Money,
+            v2::payments::RequestData,
+            v2::payments::RequestBody,
+            v2::payments::ResponseData,
+            v2::payments::ResponseBody,
+            v2::payments::ListResponseBody,
+            v2::payments::VerificationData,
+            v2::payments::VerificationResponseBody,
+            health::Status,
+            health::Check,
+            health::Checks,
+            health::LivenessResponseBody,
+            health::ReadinessResponseBody,
+            bank::payments::Status,
+            bank::payments::RefundState,
+            bank::payments::DeclineCode,
+            bank::payments::FailureReason,
+            bank::reconciliation::Operation,
+            bank::refunds::Reason,
+            bank::refunds::Status,
+            bank::refunds::Mechanism,
+            bank::disputes::Status,
+            bank::disputes::Resolution,
+            bank::fees::Schedule,
+            bank::verifications::Status,
+            bank::ledger::Account,
+            bank::settlements::LineKind,
+            ProblemResponseBody,
+            InvalidParam,
+            ErrorCode,
+        ),
+        responses(
+            PaymentCreated,
+            PaymentDeclined,
+            V2PaymentCreated,
+            V2PaymentDeclined,
+            Invalid,
+            Unauthorized,
+            Forbidden,
+            NotFound,
+            Conflict,
+            UpstreamFailed,
+            InternalError,
+        ),
+    ),
+    modifiers(&BearerToken, &DeprecateV1),
+    tags(
+        (name = "payments"),
+        (name = "refunds"),
+        (name = "disputes"),
+        (name = "merchants"),
+        (name = "accounts"),
+        (name = "verifications"),
+        (name = "ledger"),
+        (name = "settlements"),
+        (name = "health", description = "Liveness and readiness probes"),
+        (name = "v2", description = "Payments, with masked card numbers, money objects and timestamps"),
+    ),
+)]
+pub struct ApiDoc;
+
+/// Where the OpenAPI document is served.
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+/// Where the docs UI is served. Its assets are bundled in the binary, so it
+/// works offline.
+pub const DOCS_PATH: &str = "/docs";
+
+/// Serves the OpenAPI document, and the docs UI browsing it.
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route(OPENAPI_PATH, get(|| async { Json(ApiDoc::openapi()) }))
+        .merge(SwaggerUi::new(DOCS_PATH).config(utoipa_swagger_ui::Config::from(OPENAPI_PATH)))
+}
+
+/// Name of the security scheme of the handlers requiring a `Principal`.
+pub const BEARER_TOKEN: &str = "bearer_token";
+
+struct BearerToken;
+
+impl Modify for BearerToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                BEARER_TOKEN,
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+/// Marks the operations of v1 (unversioned `/api` paths) as deprecated, see
+/// `versions::deprecate`.
+struct DeprecateV1;
+
+impl Modify for DeprecateV1 {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let v1_paths = openapi
+            .paths
+            .paths
+            .iter_mut()
+            .filter(|(path, _)| path.starts_with("/api/") && !path.starts_with(v2::PREFIX));
+        for (_, path_item) in v1_paths {
+            for operation in path_item.operations.values_mut() {
+                operation.deprecated = Some(Deprecated::True);
+            }
+        }
+    }
+}
+
+/// A problem details (RFC 7807) response, see `errors::ApiError`.
+fn problem(description: &str) -> Response {
+    ResponseBuilder::new()
+        .description(description)
+        .content(
+            PROBLEM_JSON,
+            ContentBuilder::new()
+                .schema(Ref::from_schema_name("ProblemResponseBody"))
+                .build(),
+        )
+        .build()
+}
+
+/// A payment, or the verification of its card for a zero amount, with the
+/// schemas of an API version.
+fn payment_or_verification(
+    description: &str,
+    payment: &str,
+    verification: &str,
+) -> ResponseBuilder {
+    ResponseBuilder::new().description(description).content(
+        "application/json",
+        ContentBuilder::new()
+            .schema(
+                OneOfBuilder::new()
+                    .item(Ref::from_schema_name(payment))
+                    .item(Ref::from_schema_name(verification)),
+            )
+            .build(),
+    )
+}
+
+/// Payment creation response for the `payment` and `verification` schemas.
+fn payment_created(payment: &str, verification: &str) -> Response {
+    payment_or_verification(
+        "The payment was approved, or the card verified for a zero amount",
+        payment,
+        verification,
+    )
+    .build()
+}
+
+/// Payment creation error response for the `payment` and `verification`
+/// schemas.
+fn payment_declined(payment: &str, verification: &str) -> Response {
+    payment_or_verification(
+        "The payment (or card verification) was declined or failed, see its `decline_code` \
+        and `failure_reason`; or the request was invalid",
+        payment,
+        verification,
+    )
+    .content(
+        PROBLEM_JSON,
+        ContentBuilder::new()
+            .schema(Ref::from_schema_name("ProblemResponseBody"))
+            .build(),
+    )
+    .build()
+}
+
+macro_rules! responses {
+    ($($name:ident => $response:expr;)*) => {
+        $(
+            pub struct $name;
+
+            impl<'r> ToResponse<'r> for $name {
+                fn response() -> (&'r str, RefOr<Response>) {
+                    (stringify!($name), $response.into())
+                }
+            }
+        )*
+    };
+}
+
+responses! {
+    PaymentCreated => payment_created("PaymentResponseBody", "VerificationResponseBody");
+    PaymentDeclined => payment_declined("PaymentResponseBody", "VerificationResponseBody");
+    V2PaymentCreated => payment_created("V2PaymentResponseBody", "V2VerificationResponseBody");
+    V2PaymentDeclined => payment_declined("V2PaymentResponseBody", "V2VerificationResponseBody");
+    Invalid => problem("The request is invalid, see `invalid_params`");
+    Unauthorized => problem("The request must be authenticated with a bearer token");
+    Forbidden => problem("The authenticated principal isn't allowed to make the request");
+    NotFound => problem("The resource doesn't exist");
+    Conflict => problem("The request conflicts with the current state of the resource");
+    UpstreamFailed => problem("The account service failed");
+    InternalError => problem("Something unexpected failed");
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header::CONTENT_TYPE, Method, Request, StatusCode};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::bank::{accounts::DummyService, payment_instruments::Card};
+    use crate::bank_web::{
+        tests::{deserialize_response_body, get, post, send_request},
+        BankWeb,
+    };
+
+    /// Lists the `$ref`s of a JSON value.
+    fn refs<'v>(value: &'v Value, found: &mut Vec<&'v str>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::String(reference)) = object.get("$ref") {
+                    found.push(reference);
+                }
+                object.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(array) => array.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn should_document_every_route() {
+        let router = BankWeb::new_test().await.into_router();
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        let methods = [
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ];
+        let routes = BankWeb::<DummyService>::routes()
+            .into_iter()
+            .chain(v2::routes::<DummyService>());
+        for (path, _) in routes {
+            // e.g. /api/payments/:payment_id is documented as /api/payments/{payment_id}
+            let documented_path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(param) => format!("{{{param}}}"),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            let uri = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(_) => uuid::Uuid::nil().to_string(),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            for method in &methods {
+                let request = Request::builder()
+                    .method(method)
+                    .uri(&uri)
+                    .body(hyper::Body::empty())
+                    .unwrap();
+                let response = send_request(&router, request).await;
+                if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+                    continue;
+                }
+
+                let operation = &openapi["paths"][&documented_path][method.as_str().to_lowercase()];
+                assert!(
+                    operation.is_object(),
+                    "{method} {path} isn't documented, see `openapi::ApiDoc`"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn should_document_payment_responses() {
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        // the account service's answer, the amount charged and the status answered
+        let cases = [
+            ("insufficient_funds", 1205, StatusCode::PAYMENT_REQUIRED),
+            ("service_unavailable", 1205, StatusCode::SERVICE_UNAVAILABLE),
+            ("service_unavailable", 0, StatusCode::BAD_GATEWAY),
+        ];
+        for (account_response, amount, status) in cases {
+            let router = BankWeb::new_test_with_response(account_response)
+                .await
+                .into_router();
+            let card_number: String = Card::new_test().into();
+
+            for (path, amount) in [
+                ("/api/payments", json!(amount)),
+                (
+                    "/api/v2/payments",
+                    json!({ "value": amount, "currency": v2::CURRENCY }),
+                ),
+            ] {
+                let request_body = json!({
+                    "payment": { "amount": amount, "card_number": card_number },
+                });
+                let response = post(&router, path, &request_body).await;
+                assert_eq!(response.status(), status, "POST {path}");
+
+                let content_type = response.headers()[CONTENT_TYPE].to_str().unwrap();
+                let mut documented = &openapi["paths"][path]["post"]["responses"][status.as_str()];
+                if let Some(Value::String(reference)) = documented.get("$ref") {
+                    documented = openapi.pointer(reference.trim_start_matches('#')).unwrap();
+                }
+                assert!(
+                    documented["content"].get(content_type).is_some(),
+                    "POST {path} answers {status} with {content_type}, which isn't documented"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn should_reference_documented_schemas() {
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        let mut found = Vec::new();
+        refs(&openapi, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let pointer = reference.trim_start_matches('#');
+            assert!(
+                openapi.pointer(pointer).is_some(),
+                "{reference} doesn't exist"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_serve_openapi_document_and_docs() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let response = get(&router, OPENAPI_PATH).await;
+        assert_eq!(response.status(), 200);
+        let response_body = deserialize_response_body::<Value>(response).await;
+        assert_eq!(response_body["openapi"], "3.0.3");
+
+        let response = get(&router, format!("{DOCS_PATH}/")).await;
+        assert_eq!(response.status(), 200);
+    }
+}
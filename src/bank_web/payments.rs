@@ -1,199 +1,644 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::{BankWeb, ErrorResponseBody};
+use super::{auth::Principal, openapi, verifications, BankWeb};
 use crate::bank::{
-    accounts::{AccountService, HoldRef},
+    accounts::{self, AccountService},
+    attempts::{self, Call},
     payment_instruments::Card,
-    payments::{self, Status},
+    payments::{self, DeclineCode, FailureReason, Payment, RefundState, Reversal, Status},
+    reconciliation::Operation,
+    refunds::{self, Balance},
+    reviews::{self, Review},
+    risk::{self, Outcome},
+    spending, timeouts,
 };
-use crate::errors::PaymentError;
+use crate::errors::{ApiError, ErrorCode, PaymentError};
+use crate::metrics::metrics;
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = NewPayment)]
 pub struct RequestData {
     pub amount: i32,
     pub card_number: String,
+    #[serde(default)]
+    pub merchant_id: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = PaymentRequestBody)]
 pub struct RequestBody {
+    #[schema(value_type = NewPayment)]
     pub payment: RequestData,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = Payment)]
 pub struct ResponseData {
     pub id: Uuid,
     pub amount: i32,
     pub card_number: String,
+    pub merchant_id: String,
+    #[schema(value_type = PaymentStatus)]
     pub status: payments::Status,
+    /// Fee charged to the merchant.
+    pub fee_amount: i32,
+    /// Part of the fee given back to the merchant by refunds.
+    pub refunded_fee_amount: i32,
+    #[schema(value_type = RefundState)]
+    pub refund_state: RefundState,
+    pub refunded_amount: i32,
+    pub disputed_amount: i32,
+    pub refundable_amount: i32,
+    /// When the payment is rejected, if it's still awaiting review by then.
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub review_deadline_at: Option<OffsetDateTime>,
+    pub reviewed_by: Option<String>,
+    /// Why the payment was declined, if it was.
+    #[schema(value_type = Option<DeclineCode>)]
+    pub decline_code: Option<DeclineCode>,
+    /// Why the payment failed, if it did.
+    #[schema(value_type = Option<FailureReason>)]
+    pub failure_reason: Option<FailureReason>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+impl ResponseData {
+    pub fn new(payment: Payment, balance: Balance) -> Self {
+        Self {
+            id: payment.id,
+            amount: payment.amount,
+            card_number: payment.card_number,
+            merchant_id: payment.merchant_id,
+            status: payment.status,
+            fee_amount: payment.fee_amount,
+            refunded_fee_amount: balance.refunded_fee_amount,
+            refund_state: payment.refund_state,
+            refunded_amount: balance.refunded_amount,
+            disputed_amount: balance.disputed_amount,
+            refundable_amount: balance.refundable_amount,
+            review_deadline_at: payment.review_deadline_at.map(|at| at.assume_utc()),
+            reviewed_by: payment.reviewed_by,
+            decline_code: payment.decline_code,
+            failure_reason: payment.failure_reason,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = PaymentResponseBody)]
 pub struct ResponseBody {
+    #[schema(value_type = Payment)]
     pub data: ResponseData,
 }
-impl ResponseBody {
-    pub fn new(id: Uuid, amount: i32, card_number: String, status: Status) -> Self {
-        ResponseBody {
-            data: ResponseData {
-                id,
-                amount,
-                card_number,
-                status,
-            },
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = PaymentListResponseBody)]
+pub struct ListResponseBody {
+    #[schema(value_type = Vec<Payment>)]
+    pub data: Vec<ResponseData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = PaymentTry)]
+pub struct TryData {
+    pub number: i32,
+    pub amount: i32,
+    pub merchant_id: String,
+    #[schema(value_type = PaymentStatus)]
+    pub status: Status,
+    pub hold_id: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub inserted_at: OffsetDateTime,
+}
+
+impl From<payments::Try> for TryData {
+    fn from(payment_try: payments::Try) -> Self {
+        Self {
+            number: payment_try.number,
+            amount: payment_try.amount,
+            merchant_id: payment_try.merchant_id,
+            status: payment_try.status,
+            hold_id: payment_try.hold_id,
+            inserted_at: payment_try.inserted_at.assume_utc(),
         }
     }
 }
 
-macro_rules! unwrap_or_return {
-    ( $res:expr, $err:expr ) => {
-        match $res {
-            Ok(x) => x,
-            Err(_) => return $err,
-        }
-    };
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = PaymentTryListResponseBody)]
+pub struct TryListResponseBody {
+    #[schema(value_type = Vec<PaymentTry>)]
+    pub data: Vec<TryData>,
 }
 
-macro_rules! check_and_reverse_payment_status {
-    ($bank_web:ident, $payment_result:ident, $payment_id:ident, $card_number:ident, $amount:ident ) => {
-        if let Err(err_str) = $payment_result {
-            let payment_err = PaymentError::from(&err_str);
-            // update payment status to Declined or Failed, according to the payment_err type
-            payments::update(
-                &$bank_web.pool,
-                $payment_id,
-                payment_err.get_payment_status(),
-            )
-            .await
-            .unwrap();
-            return Ok((
-                payment_err.get_http_status_code(),
-                Json(ResponseBody::new(
-                    Uuid::new_v4(),
-                    $amount,
-                    $card_number,
-                    payment_err.get_payment_status(),
-                )),
-            ));
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = PaymentAttempt)]
+pub struct AttemptData {
+    pub id: Uuid,
+    pub refund_id: Option<Uuid>,
+    #[schema(value_type = LedgerOperation)]
+    pub operation: Operation,
+    pub amount: Option<i32>,
+    pub hold_id: Option<Uuid>,
+    pub succeeded: bool,
+    /// The value returned by the account service, or its error.
+    pub result: String,
+    pub latency_us: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub inserted_at: OffsetDateTime,
+}
+
+impl From<attempts::Attempt> for AttemptData {
+    fn from(attempt: attempts::Attempt) -> Self {
+        Self {
+            id: attempt.id,
+            refund_id: attempt.refund_id,
+            operation: attempt.operation,
+            amount: attempt.amount,
+            hold_id: attempt.hold_id,
+            succeeded: attempt.succeeded,
+            result: attempt.result,
+            latency_us: attempt.latency_us,
+            inserted_at: attempt.inserted_at.assume_utc(),
         }
-    };
+    }
 }
 
-pub async fn post<T: AccountService>(
-    State(bank_web): State<BankWeb<T>>,
-    Json(body): Json<RequestBody>,
-) -> Result<(StatusCode, Json<ResponseBody>), (StatusCode, Json<ErrorResponseBody>)> {
-    let amount = body.payment.amount;
-    let card_number = body.payment.card_number.to_string();
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = PaymentAttemptListResponseBody)]
+pub struct AttemptListResponseBody {
+    #[schema(value_type = Vec<PaymentAttempt>)]
+    pub data: Vec<AttemptData>,
+}
 
-    // payment requests for 0 should return a 204 response
-    if amount == 0 {
-        return Err((
-            StatusCode::NO_CONTENT,
-            Json(ErrorResponseBody::new("Amount shouldn't be 0")),
-        ));
-    }
+type PaymentResponse = Result<(StatusCode, Json<ResponseBody>), ApiError>;
 
+/// Responds with a payment, as it's persisted.
+async fn payment_response(pool: &PgPool, status: StatusCode, payment_id: Uuid) -> PaymentResponse {
+    let payment = payments::get(pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+    let balance = refunds::balance(pool, payment_id).await?;
+
+    Ok((
+        status,
+        Json(ResponseBody {
+            data: ResponseData::new(payment, balance),
+        }),
+    ))
+}
+
+/// How a payment request went: the status to answer with, and the id of the
+/// payment (or of the card verification, for zero amounts).
+pub type Processed = Result<(StatusCode, Uuid), ApiError>;
+
+/// Declines or fails a payment after the account service's `error`.
+async fn reverse_payment_status<T: AccountService>(
+    bank_web: &BankWeb<T>,
+    payment_id: Uuid,
+    error: &str,
+) -> Processed {
+    let payment_err = PaymentError::from(error);
+    // update payment status to Declined or Failed, according to the payment_err type
+    payments::reverse(
+        &bank_web.pool,
+        &bank_web.account_service,
+        payment_id,
+        payment_err.get_reversal(),
+    )
+    .await?;
+
+    Ok((payment_err.get_http_status_code(), payment_id))
+}
+
+/// Validates the amount and card number of a payment request.
+pub fn validate(amount: i32, card_number: String) -> Result<Card, ApiError> {
     // payment requests for negative amounts should return a 400 response
     if amount < 0 {
-        return Err((
+        return Err(ApiError::Validation(
             StatusCode::BAD_REQUEST,
-            Json(ErrorResponseBody::new("Amount shouldn't be negative")),
+            ErrorCode::NegativeAmount,
         ));
     }
 
     // invalid card formats should return a 422 response
-    let card = match Card::try_from(card_number.clone()) {
-        Ok(c) => c,
-        Err(_e) => {
-            return Err((
+    Card::try_from(card_number).map_err(|_| {
+        ApiError::Validation(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::InvalidCardNumber,
+        )
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments",
+    tag = "payments",
+    request_body = PaymentRequestBody,
+    responses(
+        (status = 201, response = openapi::PaymentCreated),
+        (status = 202, description = "The payment was flagged for review, its funds are held until it's reviewed", body = PaymentResponseBody),
+        (status = 400, response = openapi::PaymentDeclined),
+        (status = 402, response = openapi::PaymentDeclined),
+        (status = 403, response = openapi::PaymentDeclined),
+        (status = 422, response = openapi::Invalid),
+        (status = 500, response = openapi::PaymentDeclined),
+        (status = 503, response = openapi::PaymentDeclined),
+        (status = 504, description = "The account service timed out: the payment, or the verification of a zero-amount payment, stays processing until it's reconciled", body = PaymentResponseBody),
+        (status = 502, response = openapi::UpstreamFailed),
+    )
+)]
+pub async fn post<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Json(body): Json<RequestBody>,
+) -> Result<Response, ApiError> {
+    let amount = body.payment.amount;
+    let merchant_id = body
+        .payment
+        .merchant_id
+        .unwrap_or_else(|| payments::DEFAULT_MERCHANT_ID.to_string());
+
+    let card = validate(amount, body.payment.card_number)?;
+
+    // payment requests for 0 only verify the card, nothing is charged
+    if amount == 0 {
+        let (status, verification_id) =
+            verifications::verify(&bank_web, card, &merchant_id).await?;
+        return verifications::verification_response(&bank_web.pool, status, verification_id)
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    let (status, payment_id) = charge(&bank_web, card, amount, &merchant_id).await?;
+    payment_response(&bank_web.pool, status, payment_id)
+        .await
+        .map(IntoResponse::into_response)
+}
+
+/// Charges `amount` to a card, for the merchant.
+pub async fn charge<T: AccountService>(
+    bank_web: &BankWeb<T>,
+    card: Card,
+    amount: i32,
+    merchant_id: &str,
+) -> Processed {
+    // insert Processing Payment, or try again a payment which didn't go through
+    let payment_id =
+        payments::insert_or_retry(&bank_web.pool, amount, card.clone().into(), merchant_id)
+            .await?
+            .ok_or(ApiError::Validation(
                 StatusCode::UNPROCESSABLE_ENTITY,
-                Json(ErrorResponseBody::new("Bad Card Number format")),
-            ))
-        }
-    };
+                ErrorCode::CardNumberAlreadyUsed,
+            ))?;
 
-    // insert Processing Payment
-    let payment_id = unwrap_or_return!(
-        payments::insert(
+    // evaluate risk rules, declined payments never reach the account service
+    let decision = bank_web
+        .risk_engine
+        .evaluate(&bank_web.pool, payment_id, card.account_number(), amount)
+        .await?;
+    risk::record(
+        &bank_web.pool,
+        payment_id,
+        card.account_number(),
+        amount,
+        &decision,
+    )
+    .await?;
+    if decision.outcome == Outcome::Decline {
+        let reversal = Reversal::Declined(DeclineCode::RiskDeclined);
+        payments::reverse(
             &bank_web.pool,
-            body.payment.amount,
-            body.payment.card_number,
-            payments::Status::Processing
+            &bank_web.account_service,
+            payment_id,
+            reversal,
         )
-        .await,
-        Err((
-            StatusCode::UNPROCESSABLE_ENTITY,
-            Json(ErrorResponseBody::new("card_number already used")),
-        ))
-    );
+        .await?;
+        return Ok((StatusCode::PAYMENT_REQUIRED, payment_id));
+    }
+
+    // enforce the account's spend limits, declined payments stop counting
+    // against them
+    let reserved = spending::reserve(
+        &bank_web.pool,
+        payment_id,
+        card.account_number(),
+        amount,
+        &bank_web.spend_limits,
+    )
+    .await?;
+    if let Err(exceeded) = reserved {
+        tracing::info!(%payment_id, ?exceeded, "spend limit exceeded");
+        let reversal = Reversal::Declined(DeclineCode::SpendLimitExceeded);
+        payments::reverse(
+            &bank_web.pool,
+            &bank_web.account_service,
+            payment_id,
+            reversal,
+        )
+        .await?;
+        return Ok((StatusCode::PAYMENT_REQUIRED, payment_id));
+    }
+
     // place hold
-    let payment_result = bank_web
-        .account_service
-        .place_hold(card.account_number(), body.payment.amount)
-        .await;
+    let call = Call::new(payment_id, Operation::PlaceHold).with_amount(amount);
+    let hold_result = attempts::record(
+        &bank_web.pool,
+        call,
+        bank_web
+            .account_service
+            .place_hold(card.account_number(), amount),
+    )
+    .await;
+    let hold_ref = match hold_result {
+        Ok(hold_ref) => hold_ref,
+        // the hold may have been placed all the same: the payment stays
+        // `Processing`, so that the card can't be charged again, until the
+        // call is resolved (see `timeouts::resolve`)
+        Err(error) if error == accounts::TIMEOUT => {
+            timeouts::record(&bank_web.pool, call).await?;
+            return Ok((StatusCode::GATEWAY_TIMEOUT, payment_id));
+        }
+        Err(error) => return reverse_payment_status(bank_web, payment_id, &error).await,
+    };
+
+    payments::record_hold(&bank_web.pool, payment_id, hold_ref).await?;
+
+    // payments requiring review keep their funds held until they're reviewed
+    if decision.outcome == Outcome::Review {
+        reviews::require(&bank_web.pool, payment_id, bank_web.review_period).await?;
+        return Ok((StatusCode::ACCEPTED, payment_id));
+    }
+
+    payments::approve(&bank_web.pool, payment_id).await?;
+    let withdrawal =
+        payments::withdraw_funds(&bank_web.pool, &bank_web.account_service, payment_id).await?;
+    match withdrawal {
+        Ok(()) => {
+            metrics().payment_approved();
+            Ok((StatusCode::CREATED, payment_id))
+        }
+        // the funds may have been withdrawn all the same: they stay held until
+        // the call is resolved (see `timeouts::resolve`)
+        Err(error) if error == accounts::TIMEOUT => Ok((StatusCode::GATEWAY_TIMEOUT, payment_id)),
+        Err(error) => reverse_payment_status(bank_web, payment_id, &error).await,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments/{payment_id}",
+    tag = "payments",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = PaymentResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn get<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+) -> PaymentResponse {
+    payment_response(&bank_web.pool, StatusCode::OK, payment_id).await
+}
 
-    // deal with payment_result
-    check_and_reverse_payment_status!(bank_web, payment_result, payment_id, card_number, amount);
+/// Responds with a list of payments.
+async fn list_response(
+    pool: &PgPool,
+    payments: Vec<Payment>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    let ids: Vec<Uuid> = payments.iter().map(|payment| payment.id).collect();
+    let mut balances = refunds::balances(pool, &ids).await?;
 
-    payments::update(&bank_web.pool, payment_id, payments::Status::Approved)
+    let data = payments
+        .into_iter()
+        .map(|payment| {
+            let balance = balances.remove(&payment.id).unwrap_or_default();
+            ResponseData::new(payment, balance)
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(ListResponseBody { data })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments",
+    tag = "payments",
+    params(payments::Filter),
+    responses(
+        (status = 200, description = "OK", body = PaymentListResponseBody),
+    )
+)]
+pub async fn index<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Query(filter): Query<payments::Filter>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    let payments = payments::list(&bank_web.pool, &filter).await?;
+    list_response(&bank_web.pool, payments).await
+}
+
+/// Lists the tries of a payment, in order.
+#[utoipa::path(
+    get,
+    path = "/api/payments/{payment_id}/tries",
+    tag = "payments",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = PaymentTryListResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn tries<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<TryListResponseBody>), ApiError> {
+    payments::get(&bank_web.pool, payment_id)
         .await
-        .unwrap();
-    let payment_result = bank_web
-        .account_service
-        .withdraw_funds(payment_result.unwrap())
-        .await;
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
 
-    // deal with payment_result
-    check_and_reverse_payment_status!(bank_web, payment_result, payment_id, card_number, amount);
+    let tries = payments::tries(&bank_web.pool, payment_id).await?;
 
     Ok((
-        StatusCode::CREATED,
-        Json(ResponseBody::new(
-            payment_id,
-            amount,
-            card_number,
-            payments::Status::Approved,
-        )),
+        StatusCode::OK,
+        Json(TryListResponseBody {
+            data: tries.into_iter().map(TryData::from).collect(),
+        }),
     ))
 }
 
-pub async fn get<T: AccountService>(
+/// Lists the account service calls made for a payment, in order.
+#[utoipa::path(
+    get,
+    path = "/api/payments/{payment_id}/attempts",
+    tag = "payments",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = PaymentAttemptListResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn attempts<T: AccountService>(
     State(bank_web): State<BankWeb<T>>,
     Path(payment_id): Path<Uuid>,
-) -> Result<(StatusCode, Json<ResponseBody>), (StatusCode, Json<ErrorResponseBody>)> {
-    let payment = payments::get(&bank_web.pool, payment_id).await.unwrap();
+) -> Result<(StatusCode, Json<AttemptListResponseBody>), ApiError> {
+    payments::get(&bank_web.pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+
+    let attempts = attempts::list(&bank_web.pool, payment_id).await?;
 
     Ok((
         StatusCode::OK,
-        Json(ResponseBody {
-            data: ResponseData {
-                id: payment.id,
-                amount: payment.amount,
-                card_number: payment.card_number,
-                status: payment.status,
-            },
+        Json(AttemptListResponseBody {
+            data: attempts.into_iter().map(AttemptData::from).collect(),
         }),
     ))
 }
 
+/// Lists the payments awaiting review, the closest to their deadline first.
+#[utoipa::path(
+    get,
+    path = "/api/payments/review_queue",
+    tag = "payments",
+    responses(
+        (status = 200, description = "Payments awaiting review", body = PaymentListResponseBody),
+    )
+)]
+pub async fn review_queue<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    let payments = reviews::queue(&bank_web.pool).await?;
+    list_response(&bank_web.pool, payments).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/approve",
+    tag = "payments",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    security(("bearer_token" = [])),
+    responses(
+        (status = 200, description = "OK", body = PaymentResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+        (status = 502, response = openapi::UpstreamFailed),
+        (status = 504, description = "The account service timed out: the review only takes effect once it's reconciled", body = PaymentResponseBody),
+    )
+)]
+pub async fn approve<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+    principal: Principal,
+) -> PaymentResponse {
+    payments::get(&bank_web.pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+
+    let review = reviews::approve(
+        &bank_web.pool,
+        &bank_web.account_service,
+        payment_id,
+        &principal.0,
+    )
+    .await?;
+
+    reviewed(&bank_web.pool, payment_id, review).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/reject",
+    tag = "payments",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    security(("bearer_token" = [])),
+    responses(
+        (status = 200, description = "OK", body = PaymentResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+        (status = 502, response = openapi::UpstreamFailed),
+        (status = 504, description = "The account service timed out: the review only takes effect once it's reconciled", body = PaymentResponseBody),
+    )
+)]
+pub async fn reject<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+    principal: Principal,
+) -> PaymentResponse {
+    payments::get(&bank_web.pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+
+    let review = reviews::reject(
+        &bank_web.pool,
+        &bank_web.account_service,
+        payment_id,
+        Some(&principal.0),
+    )
+    .await?;
+
+    reviewed(&bank_web.pool, payment_id, review).await
+}
+
+/// Responds to the review of a payment.
+async fn reviewed(pool: &PgPool, payment_id: Uuid, review: Review) -> PaymentResponse {
+    match review {
+        Review::Approved | Review::Rejected => {
+            payment_response(pool, StatusCode::OK, payment_id).await
+        }
+        Review::TimedOut => payment_response(pool, StatusCode::GATEWAY_TIMEOUT, payment_id).await,
+        Review::AlreadyReviewed => Err(ApiError::Conflict(ErrorCode::PaymentNotAwaitingReview)),
+        Review::Failed(error) => Err(ApiError::Upstream(
+            ErrorCode::ReviewFailed,
+            PaymentError::from(&error),
+        )),
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
     use super::*;
-    use crate::bank::accounts::{AccountService, DummyService, HoldRef};
+    use crate::bank::accounts::{AccountService, DummyService, HoldRef, Timeout};
+    use crate::errors::PROBLEM_JSON;
     use crate::{
-        bank::{payment_instruments::Card, payments::Status},
-        bank_web::tests::{deserialize_response_body, get, post},
+        bank::{
+            fees, ledger,
+            payment_instruments::Card,
+            payments::{HoldStatus, Status},
+        },
+        bank_web::{
+            auth::Tokens,
+            tests::{deserialize_response_body, get, post, post_as},
+            ProblemResponseBody,
+        },
     };
-    use std::sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+    use axum::http::header::CONTENT_TYPE;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
     };
 
     #[derive(Clone, Default)]
@@ -202,12 +647,15 @@ pub mod tests {
         place_hold_count: Arc<AtomicUsize>,
         release_hold_count: Arc<AtomicUsize>,
         withdraw_funds_count: Arc<AtomicUsize>,
+        place_hold_delay: Duration,
+        withdraw_funds_error: Option<&'static str>,
     }
 
     #[async_trait::async_trait]
     impl AccountService for MockService {
         async fn place_hold(&self, account_number: &str, amount: i32) -> Result<HoldRef, String> {
             self.place_hold_count.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.place_hold_delay).await;
             self.dummy.place_hold(account_number, amount).await
         }
 
@@ -218,8 +666,19 @@ pub mod tests {
 
         async fn withdraw_funds(&self, hold_ref: HoldRef) -> Result<(), String> {
             self.withdraw_funds_count.fetch_add(1, Ordering::SeqCst);
+            if let Some(error) = self.withdraw_funds_error {
+                return Err(error.into());
+            }
             self.dummy.withdraw_funds(hold_ref).await
         }
+
+        async fn capture_funds(&self, hold_ref: HoldRef, amount: i32) -> Result<(), String> {
+            self.dummy.capture_funds(hold_ref, amount).await
+        }
+
+        async fn credit_funds(&self, account_number: &str, amount: i32) -> Result<(), String> {
+            self.dummy.credit_funds(account_number, amount).await
+        }
     }
 
     #[tokio::test]
@@ -232,6 +691,7 @@ pub mod tests {
             payment: RequestData {
                 amount: -1,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -244,6 +704,40 @@ pub mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn should_answer_problem_details_for_invalid_payment() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: -1,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 400);
+        assert_eq!(response.headers()[CONTENT_TYPE], PROBLEM_JSON);
+
+        let response_body = deserialize_response_body::<serde_json::Value>(response).await;
+        assert_eq!(
+            response_body,
+            serde_json::json!({
+                "type": "/problems/negative_amount",
+                "title": "Invalid amount",
+                "status": 400,
+                "detail": "Amount shouldn't be negative",
+                "code": "negative_amount",
+                "invalid_params": [{
+                    "name": "payment.amount",
+                    "code": "negative_amount",
+                    "reason": "Amount shouldn't be negative",
+                }],
+            })
+        );
+    }
+
     #[tokio::test]
     async fn should_withdraw_funds_on_successful_payment() {
         let pool = crate::pg_pool().await.unwrap();
@@ -254,6 +748,7 @@ pub mod tests {
             payment: RequestData {
                 amount: 123,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -267,6 +762,7 @@ pub mod tests {
             payment: RequestData {
                 amount: 123,
                 card_number: card.into(),
+                merchant_id: None,
             },
         };
         let response = post(&router, "/api/payments", &request_body).await;
@@ -295,6 +791,333 @@ pub mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn should_not_place_hold_for_payment_declined_by_risk_rules() {
+        let pool = crate::pg_pool().await.unwrap();
+        let mock_service = MockService::default();
+        let card = Card::new_test();
+        let risk_engine = risk::Engine::new(vec![risk::Rule::Blocklist {
+            name: "blocked".to_string(),
+            accounts: vec![card.account_number().to_string()],
+        }]);
+        let router = BankWeb::new(pool.clone(), mock_service.clone())
+            .with_risk_engine(risk_engine)
+            .into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 123,
+                card_number: card.into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 402);
+        assert_eq!(
+            mock_service.place_hold_count.load(Ordering::SeqCst),
+            0,
+            "should not try to place hold for declined payment"
+        );
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::Declined);
+        assert_eq!(
+            response_body.data.decline_code,
+            Some(DeclineCode::RiskDeclined)
+        );
+
+        let decision = risk::tests::get(&pool, response_body.data.id)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, Outcome::Decline);
+        assert_eq!(decision.rule.as_deref(), Some("blocked"));
+    }
+
+    #[tokio::test]
+    async fn should_hold_funds_of_payment_requiring_review_until_approved() {
+        let pool = crate::pg_pool().await.unwrap();
+        let mock_service = MockService::default();
+        let risk_engine = risk::Engine::new(vec![risk::Rule::AmountThreshold {
+            name: "large".to_string(),
+            min_amount: 1000,
+            action: risk::Action::Review,
+        }]);
+        let tokens = [("reviewer".to_string(), "reviewer-token".to_string())]
+            .into_iter()
+            .collect::<Tokens>();
+        let router = BankWeb::new(pool, mock_service.clone())
+            .with_risk_engine(risk_engine)
+            .with_api_tokens(tokens)
+            .into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 202);
+        assert_eq!(mock_service.place_hold_count.load(Ordering::SeqCst), 1);
+        assert_eq!(mock_service.withdraw_funds_count.load(Ordering::SeqCst), 0);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::RequiresReview);
+        assert_eq!(response_body.data.refundable_amount, 0);
+        assert!(response_body.data.review_deadline_at.is_some());
+        let payment_id = response_body.data.id;
+
+        let response = get(&router, "/api/payments/review_queue").await;
+        assert_eq!(response.status(), 200);
+        let response_body = deserialize_response_body::<ListResponseBody>(response).await;
+        assert!(response_body.data.iter().any(|p| p.id == payment_id));
+
+        let uri = format!("/api/payments/{payment_id}/approve");
+        let response = post(&router, &uri, &()).await;
+        assert_eq!(response.status(), 401);
+
+        let response = post_as(&router, &uri, &(), "reviewer-token").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(mock_service.withdraw_funds_count.load(Ordering::SeqCst), 1);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::Approved);
+        assert_eq!(response_body.data.reviewed_by.as_deref(), Some("reviewer"));
+        assert_eq!(response_body.data.refundable_amount, 1205);
+
+        let uri = format!("/api/payments/{payment_id}/reject");
+        let response = post_as(&router, &uri, &(), "reviewer-token").await;
+        assert_eq!(response.status(), 409);
+        assert_eq!(mock_service.release_hold_count.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn should_not_place_hold_for_payment_over_spend_limit() {
+        let pool = crate::pg_pool().await.unwrap();
+        let mock_service = MockService::default();
+        let limits = spending::Limits {
+            daily: None,
+            monthly: Some(1000),
+        };
+        let router = BankWeb::new(pool.clone(), mock_service.clone())
+            .with_spend_limits(limits)
+            .into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 402);
+        assert_eq!(
+            mock_service.place_hold_count.load(Ordering::SeqCst),
+            0,
+            "should not try to place hold over the spend limit"
+        );
+
+        // the declined payment is answered like any other declined payment
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        let payment = payments::get(&pool, response_body.data.id).await.unwrap();
+        assert_eq!(payment.status, Status::Declined);
+        assert_eq!(payment.decline_code, Some(DeclineCode::SpendLimitExceeded));
+        assert_eq!(
+            response_body.data.decline_code,
+            Some(DeclineCode::SpendLimitExceeded)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_retry_failed_payment_with_the_same_card() {
+        let failing_router = BankWeb::new_test_with_response("service_unavailable")
+            .await
+            .into_router();
+        let router = BankWeb::new_test().await.into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&failing_router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 503);
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 201);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::Approved);
+
+        let uri = format!("/api/payments/{}/tries", response_body.data.id);
+        let response = get(&router, uri).await;
+        assert_eq!(response.status(), 200);
+        let response_body = deserialize_response_body::<TryListResponseBody>(response).await;
+        let statuses: Vec<_> = response_body.data.iter().map(|t| t.status).collect();
+        assert_eq!(statuses, [Status::Failed, Status::Approved]);
+
+        // the card is used for good once the payment is approved
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 422);
+    }
+
+    #[tokio::test]
+    async fn should_retry_payment_whose_withdrawal_failed() {
+        let pool = crate::pg_pool().await.unwrap();
+        let failing_service = MockService {
+            withdraw_funds_error: Some("service_unavailable"),
+            ..MockService::default()
+        };
+        let failing_router = BankWeb::new(pool.clone(), failing_service).into_router();
+        let router = BankWeb::new(pool, MockService::default()).into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&failing_router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 503);
+
+        // the hold of the failed try was released, so the card can be retried
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 201);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::Approved);
+
+        let uri = format!("/api/payments/{}/tries", response_body.data.id);
+        let response = get(&router, uri).await;
+        let response_body = deserialize_response_body::<TryListResponseBody>(response).await;
+        let statuses: Vec<_> = response_body.data.iter().map(|t| t.status).collect();
+        assert_eq!(statuses, [Status::Failed, Status::Approved]);
+        assert!(response_body.data.iter().all(|t| t.hold_id.is_some()));
+        assert_ne!(response_body.data[0].hold_id, response_body.data[1].hold_id);
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_payment_whose_hold_timed_out() {
+        let pool = crate::pg_pool().await.unwrap();
+        let mock_service = MockService {
+            place_hold_delay: Duration::from_millis(100),
+            ..MockService::default()
+        };
+        let account_service = Timeout::new(mock_service.clone(), Duration::from_millis(10));
+        let router = BankWeb::new(pool, account_service).into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 504);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::Processing);
+        assert_eq!(response_body.data.failure_reason, None);
+
+        // the hold may have been placed, so the card can't be charged again
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 422);
+        assert_eq!(mock_service.place_hold_count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn should_release_hold_when_withdrawal_fails() {
+        let pool = crate::pg_pool().await.unwrap();
+        let mock_service = MockService {
+            withdraw_funds_error: Some("service_unavailable"),
+            ..MockService::default()
+        };
+        let router = BankWeb::new(pool.clone(), mock_service.clone()).into_router();
+
+        let merchant_id = Uuid::new_v4().to_string();
+        let schedule = fees::Schedule {
+            fixed_amount: 10,
+            ..fees::Schedule::default()
+        };
+        fees::put(&pool, &merchant_id, &schedule).await.unwrap();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: Some(merchant_id),
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 503);
+        assert_eq!(mock_service.release_hold_count.load(Ordering::SeqCst), 1);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        let payment = payments::get(&pool, response_body.data.id).await.unwrap();
+        assert_eq!(payment.status, Status::Failed);
+        assert_eq!(payment.hold_status, Some(HoldStatus::Released));
+        // the merchant isn't charged for a payment which didn't go through
+        assert_eq!(payment.fee_amount, 0);
+
+        // the hold placed and released cancel out on the ledger
+        let held = ledger::balance(&pool, ledger::Account::CustomerFundsHeld, Some(payment.id))
+            .await
+            .unwrap();
+        assert_eq!(held, 0);
+    }
+
+    #[tokio::test]
+    async fn should_record_account_service_calls() {
+        let failing_router = BankWeb::new_test_with_response("service_unavailable")
+            .await
+            .into_router();
+        let router = BankWeb::new_test().await.into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        post(&failing_router, "/api/payments", &request_body).await;
+        let response = post(&router, "/api/payments", &request_body).await;
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+
+        let uri = format!("/api/payments/{}/attempts", response_body.data.id);
+        let response = get(&router, uri).await;
+        assert_eq!(response.status(), 200);
+
+        let response_body = deserialize_response_body::<AttemptListResponseBody>(response).await;
+        let attempts: Vec<_> = response_body
+            .data
+            .iter()
+            .map(|a| (a.operation, a.amount, a.succeeded))
+            .collect();
+        assert_eq!(
+            attempts,
+            [
+                (Operation::PlaceHold, Some(1205), false),
+                (Operation::PlaceHold, Some(1205), true),
+                (Operation::WithdrawFunds, None, true),
+            ]
+        );
+        assert_eq!(response_body.data[0].result, "service_unavailable");
+        assert_eq!(response_body.data[0].hold_id, None);
+        assert_eq!(response_body.data[1].hold_id, response_body.data[2].hold_id);
+    }
+
     #[tokio::test]
     async fn should_approve_valid_payment() {
         let router = BankWeb::new_test().await.into_router();
@@ -303,6 +1126,7 @@ pub mod tests {
             payment: RequestData {
                 amount: 1205,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -319,6 +1143,11 @@ pub mod tests {
         let response_body = deserialize_response_body::<ResponseBody>(response).await;
         assert_eq!(response_body.data.amount, request_body.payment.amount);
         assert_eq!(response_body.data.status, Status::Approved);
+        assert_eq!(response_body.data.refunded_amount, 0);
+        assert_eq!(
+            response_body.data.refundable_amount,
+            request_body.payment.amount
+        );
     }
 
     #[tokio::test]
@@ -331,6 +1160,7 @@ pub mod tests {
             payment: RequestData {
                 amount: 1205,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -340,6 +1170,50 @@ pub mod tests {
         let response_body = deserialize_response_body::<ResponseBody>(response).await;
         assert_eq!(response_body.data.amount, request_body.payment.amount);
         assert_eq!(response_body.data.status, Status::Declined);
+
+        // the declined payment can be looked up by the id it was answered with
+        let uri = format!("/api/payments/{}", response_body.data.id);
+        let response = get(&router, uri).await;
+        assert_eq!(response.status(), 200);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::Declined);
+        assert_eq!(
+            response_body.data.decline_code,
+            Some(DeclineCode::InsufficientFunds)
+        );
+        assert_eq!(response_body.data.failure_reason, None);
+    }
+
+    #[tokio::test]
+    async fn should_fail_payment_and_return_503_when_service_is_unavailable() {
+        let router = BankWeb::new_test_with_response("service_unavailable")
+            .await
+            .into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 503);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        let uri = format!("/api/payments/{}", response_body.data.id);
+        let response = get(&router, uri).await;
+        assert_eq!(response.status(), 200);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, Status::Failed);
+        assert_eq!(response_body.data.decline_code, None);
+        assert_eq!(
+            response_body.data.failure_reason,
+            Some(FailureReason::ServiceUnavailable)
+        );
     }
 
     #[tokio::test]
@@ -352,6 +1226,7 @@ pub mod tests {
             payment: RequestData {
                 amount: 1205,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -364,18 +1239,107 @@ pub mod tests {
     }
 
     #[tokio::test]
-    async fn should_return_204_for_zero_amount() {
-        let router = BankWeb::new_test().await.into_router();
+    async fn should_verify_card_for_zero_amount() {
+        let pool = crate::pg_pool().await.unwrap();
+        let mock_service = MockService::default();
+        let router = BankWeb::new(pool, mock_service.clone()).into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 0,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 201);
+        assert_eq!(mock_service.place_hold_count.load(Ordering::SeqCst), 1);
+        assert_eq!(mock_service.release_hold_count.load(Ordering::SeqCst), 1);
+        assert_eq!(mock_service.withdraw_funds_count.load(Ordering::SeqCst), 0);
+
+        let response_body =
+            deserialize_response_body::<verifications::ResponseBody>(response).await;
+        assert_eq!(
+            response_body.data.card_number,
+            request_body.payment.card_number
+        );
+        assert_eq!(
+            response_body.data.status,
+            crate::bank::verifications::Status::Verified
+        );
+
+        let uri = format!("/api/verifications/{}", response_body.data.id);
+        let response = get(&router, uri).await;
+        assert_eq!(response.status(), 200);
+
+        // the card can still be charged afterwards
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 123,
+                ..request_body.payment
+            },
+        };
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 201);
+    }
+
+    #[tokio::test]
+    async fn should_keep_verification_processing_when_hold_times_out() {
+        let pool = crate::pg_pool().await.unwrap();
+        let mock_service = MockService {
+            place_hold_delay: Duration::from_millis(100),
+            ..MockService::default()
+        };
+        let account_service = Timeout::new(mock_service.clone(), Duration::from_millis(10));
+        let router = BankWeb::new(pool, account_service).into_router();
 
         let request_body = RequestBody {
             payment: RequestData {
                 amount: 0,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
         let response = post(&router, "/api/payments", &request_body).await;
-        assert_eq!(response.status(), 204);
+        assert_eq!(response.status(), 504);
+        let response_body =
+            deserialize_response_body::<verifications::ResponseBody>(response).await;
+        assert_eq!(
+            response_body.data.status,
+            crate::bank::verifications::Status::Processing
+        );
+        assert_eq!(mock_service.release_hold_count.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn should_decline_verification_for_invalid_account_number() {
+        let router = BankWeb::new_test_with_response("invalid_account_number")
+            .await
+            .into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 0,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), 403);
+
+        let response_body =
+            deserialize_response_body::<verifications::ResponseBody>(response).await;
+        assert_eq!(
+            response_body.data.status,
+            crate::bank::verifications::Status::Declined
+        );
+        assert_eq!(
+            response_body.data.decline_code,
+            Some(DeclineCode::InvalidAccountNumber)
+        );
     }
 
     #[tokio::test]
@@ -386,6 +1350,7 @@ pub mod tests {
             payment: RequestData {
                 amount: 123,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -395,7 +1360,18 @@ pub mod tests {
         let response = post(&router, "/api/payments", &request_body).await;
         assert_eq!(response.status(), 422);
 
-        let response_body = deserialize_response_body::<ErrorResponseBody>(response).await;
-        assert_eq!(response_body.error, "card_number already used");
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::CardNumberAlreadyUsed);
+    }
+
+    #[tokio::test]
+    async fn should_return_404_for_unknown_payment() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let response = get(&router, format!("/api/payments/{}", Uuid::new_v4())).await;
+        assert_eq!(response.status(), 404);
+
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::PaymentNotFound);
     }
 }
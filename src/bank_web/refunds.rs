@@ -4,116 +4,358 @@ use axum::{
     Json,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::{BankWeb, ErrorResponseBody};
-use crate::bank::{accounts::AccountService, payments::Status, refunds};
+use super::{auth::Principal, openapi, BankWeb};
+use crate::bank::{
+    accounts::AccountService,
+    payments::{self, Status},
+    refunds::{self, Approval, Mechanism, Outcome, PolicyViolation, Reason},
+};
+use crate::errors::{ApiError, ErrorCode, PaymentError};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = NewRefund)]
 pub struct RequestData {
-    amount: i32,
+    pub amount: i32,
+    #[schema(value_type = Option<RefundReason>)]
+    pub reason: Option<Reason>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = RefundRequestBody)]
 pub struct RequestBody {
-    refund: RequestData,
+    #[schema(value_type = NewRefund)]
+    pub refund: RequestData,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = Refund)]
 pub struct ResponseData {
     id: Uuid,
     amount: i32,
     payment_id: Uuid,
+    #[schema(value_type = RefundReason)]
+    reason: Reason,
+    #[schema(value_type = RefundStatus)]
+    status: refunds::Status,
+    #[schema(value_type = Option<RefundMechanism>)]
+    mechanism: Option<Mechanism>,
+    fee_amount: i32,
+    requested_by: Option<String>,
+    reviewed_by: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = RefundResponseBody)]
 pub struct ResponseBody {
+    #[schema(value_type = Refund)]
     data: ResponseData,
 }
 
-impl ResponseBody {
-    pub fn new(id: Uuid, amount: i32, payment_id: Uuid) -> Self {
+impl From<refunds::Refund> for ResponseData {
+    fn from(refund: refunds::Refund) -> Self {
         Self {
-            data: ResponseData {
-                id,
-                amount,
-                payment_id,
-            },
+            id: refund.id,
+            amount: refund.amount,
+            payment_id: refund.payment_id,
+            reason: refund.reason,
+            status: refund.status,
+            mechanism: refund.mechanism,
+            fee_amount: refund.fee_amount,
+            requested_by: refund.requested_by,
+            reviewed_by: refund.reviewed_by,
         }
     }
 }
 
-macro_rules! unwrap_or_return {
-    ( $e:expr, $err:expr ) => {
-        match $e {
-            Ok(x) => x,
-            Err(_) => return $err,
-        }
-    };
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = RefundListResponseBody)]
+pub struct ListResponseBody {
+    #[schema(value_type = Vec<Refund>)]
+    data: Vec<ResponseData>,
+}
+
+type RefundResponse = Result<(StatusCode, Json<ResponseBody>), ApiError>;
+
+/// Responds with a refund, as it's persisted.
+async fn refund_response(pool: &PgPool, status: StatusCode, refund_id: Uuid) -> RefundResponse {
+    let refund = refunds::get(pool, refund_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::RefundNotFound))?;
+
+    Ok((
+        status,
+        Json(ResponseBody {
+            data: refund.into(),
+        }),
+    ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/refunds",
+    tag = "refunds",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    request_body = RefundRequestBody,
+    security((), ("bearer_token" = [])),
+    responses(
+        (status = 201, description = "Created", body = RefundResponseBody),
+        (status = 202, description = "The refund awaits approval", body = RefundResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+        (status = 422, response = openapi::Invalid),
+        (status = 502, response = openapi::UpstreamFailed),
+        (status = 504, description = "The account service timed out: the refund stays pending until it's reconciled", body = RefundResponseBody),
+    )
+)]
 pub async fn post<T: AccountService>(
     State(bank_web): State<BankWeb<T>>,
     Path(payment_id): Path<Uuid>,
+    principal: Option<Principal>,
     Json(body): Json<RequestBody>,
-) -> Result<(StatusCode, Json<ResponseBody>), (StatusCode, Json<ErrorResponseBody>)> {
-    // body.refund.amount
-
-    // Gettting the payment details from payment table
-    let payment_result = crate::bank::payments::get(&bank_web.pool, payment_id)
+) -> RefundResponse {
+    let payment = payments::get(&bank_web.pool, payment_id)
         .await
-        .ok();
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+    if payment.status != Status::Approved {
+        return Err(ApiError::Conflict(ErrorCode::PaymentNotApproved));
+    }
 
-    if let Some(p) = payment_result {
-        if p.status != Status::Approved {
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(ErrorResponseBody::new("has a status other than approved")),
+    // large refunds are only effective once approved by another principal
+    if bank_web.refund_policy.requires_approval(body.refund.amount) {
+        let Some(Principal(requested_by)) = principal else {
+            return Err(ApiError::Unauthorized(
+                ErrorCode::RefundAuthenticationRequired,
             ));
+        };
+
+        let refund_id = refunds::insert_awaiting_approval(
+            &bank_web.pool,
+            &bank_web.refund_policy,
+            payment_id,
+            body.refund.amount,
+            body.refund.reason,
+            &requested_by,
+        )
+        .await?
+        .map_err(policy_violation)?;
+
+        return refund_response(&bank_web.pool, StatusCode::ACCEPTED, refund_id).await;
+    }
+
+    let requested_by = principal.map(|Principal(principal)| principal);
+    let outcome = refunds::checked_insert(
+        &bank_web.pool,
+        &bank_web.account_service,
+        &bank_web.refund_policy,
+        payment_id,
+        body.refund.amount,
+        body.refund.reason,
+        requested_by.as_deref(),
+    )
+    .await?;
+
+    match outcome {
+        Outcome::Refunded(refund_id) => {
+            refund_response(&bank_web.pool, StatusCode::CREATED, refund_id).await
         }
-    } else {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponseBody::new("payment doesn't exist")),
-        ));
-    };
+        Outcome::ExcessiveAmount => Err(excessive_amount()),
+        Outcome::Violation(violation) => Err(policy_violation(violation)),
+        Outcome::InProgress => Err(ApiError::Conflict(ErrorCode::RefundInProgress)),
+        Outcome::Failed(error) => Err(refund_failed(&error)),
+        Outcome::TimedOut(refund_id) => {
+            refund_response(&bank_web.pool, StatusCode::GATEWAY_TIMEOUT, refund_id).await
+        }
+    }
+}
+
+fn excessive_amount() -> ApiError {
+    ApiError::Validation(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::ExcessiveRefundAmount,
+    )
+}
 
-    let refund_id = unwrap_or_return!(
-        refunds::checked_insert(&bank_web.pool, payment_id, body.refund.amount).await,
-        Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponseBody::new(
-                "can't add refund since the db problem"
-            )),
-        ))
-    );
-
-    if refund_id.is_none() {
-        Err((
-            StatusCode::UNPROCESSABLE_ENTITY,
-            Json(ErrorResponseBody::new("excessive refund amount requested")),
-        ))
-    } else {
-        Ok((
-            StatusCode::CREATED,
-            Json(ResponseBody::new(
-                refund_id.unwrap(),
-                body.refund.amount,
-                payment_id,
-            )),
-        ))
+fn policy_violation(violation: PolicyViolation) -> ApiError {
+    ApiError::Validation(StatusCode::UNPROCESSABLE_ENTITY, violation.into())
+}
+
+fn refund_failed(error: &str) -> ApiError {
+    ApiError::Upstream(ErrorCode::RefundFailed, PaymentError::from(error))
+}
+
+/// Fetches a refund of a payment, which is only visible under that payment.
+async fn get_of_payment(
+    pool: &PgPool,
+    payment_id: Uuid,
+    refund_id: Uuid,
+) -> Result<refunds::Refund, ApiError> {
+    let refund = refunds::get(pool, refund_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::RefundNotFound))?;
+
+    if refund.payment_id != payment_id {
+        return Err(ApiError::NotFound(ErrorCode::RefundNotFound));
+    }
+
+    Ok(refund)
+}
+
+/// Fetches a refund awaiting approval, on behalf of a principal other than its
+/// requester.
+async fn get_for_review(
+    pool: &PgPool,
+    payment_id: Uuid,
+    refund_id: Uuid,
+    principal: &Principal,
+) -> Result<refunds::Refund, ApiError> {
+    let refund = get_of_payment(pool, payment_id, refund_id).await?;
+
+    if refund.requested_by.as_deref() == Some(principal.0.as_str()) {
+        return Err(ApiError::Forbidden(ErrorCode::SelfReview));
+    }
+
+    Ok(refund)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/refunds/{refund_id}/approve",
+    tag = "refunds",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+        ("refund_id" = Uuid, Path, description = "Id of the refund"),
+    ),
+    security(("bearer_token" = [])),
+    responses(
+        (status = 200, description = "OK", body = RefundResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 403, response = openapi::Forbidden),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+        (status = 502, response = openapi::UpstreamFailed),
+        (status = 504, description = "The account service timed out: the refund stays pending until it's reconciled", body = RefundResponseBody),
+    )
+)]
+pub async fn approve<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path((payment_id, refund_id)): Path<(Uuid, Uuid)>,
+    principal: Principal,
+) -> RefundResponse {
+    get_for_review(&bank_web.pool, payment_id, refund_id, &principal).await?;
+
+    let approval = refunds::approve(
+        &bank_web.pool,
+        &bank_web.account_service,
+        refund_id,
+        &principal.0,
+    )
+    .await?;
+
+    match approval {
+        Approval::Approved => refund_response(&bank_web.pool, StatusCode::OK, refund_id).await,
+        Approval::ExcessiveAmount => Err(excessive_amount()),
+        Approval::AlreadyReviewed => Err(ApiError::Conflict(ErrorCode::RefundNotAwaitingApproval)),
+        Approval::InProgress => Err(ApiError::Conflict(ErrorCode::RefundInProgress)),
+        Approval::Failed(error) => Err(refund_failed(&error)),
+        Approval::TimedOut => {
+            refund_response(&bank_web.pool, StatusCode::GATEWAY_TIMEOUT, refund_id).await
+        }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/payments/{payment_id}/refunds/{refund_id}/reject",
+    tag = "refunds",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+        ("refund_id" = Uuid, Path, description = "Id of the refund"),
+    ),
+    security(("bearer_token" = [])),
+    responses(
+        (status = 200, description = "OK", body = RefundResponseBody),
+        (status = 401, response = openapi::Unauthorized),
+        (status = 403, response = openapi::Forbidden),
+        (status = 404, response = openapi::NotFound),
+        (status = 409, response = openapi::Conflict),
+    )
+)]
+pub async fn reject<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path((payment_id, refund_id)): Path<(Uuid, Uuid)>,
+    principal: Principal,
+) -> RefundResponse {
+    get_for_review(&bank_web.pool, payment_id, refund_id, &principal).await?;
+
+    let rejected = refunds::reject(&bank_web.pool, refund_id, &principal.0).await?;
+    if !rejected {
+        return Err(ApiError::Conflict(ErrorCode::RefundNotAwaitingApproval));
+    }
+
+    refund_response(&bank_web.pool, StatusCode::OK, refund_id).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments/{payment_id}/refunds",
+    tag = "refunds",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = RefundListResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn index<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    payments::get(&bank_web.pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+
+    let data = refunds::list(&bank_web.pool, payment_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ListResponseBody {
+            data: data.into_iter().map(ResponseData::from).collect(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments/{payment_id}/refunds/{refund_id}",
+    tag = "refunds",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+        ("refund_id" = Uuid, Path, description = "Id of the refund"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = RefundResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
 pub async fn get<T: AccountService>(
     State(bank_web): State<BankWeb<T>>,
     Path((payment_id, refund_id)): Path<(Uuid, Uuid)>,
-) -> Result<(StatusCode, Json<ResponseBody>), (StatusCode, Json<ErrorResponseBody>)> {
-    let data = refunds::get(&bank_web.pool, refund_id).await.unwrap();
+) -> RefundResponse {
+    let refund = get_of_payment(&bank_web.pool, payment_id, refund_id).await?;
 
     Ok((
         StatusCode::OK,
-        Json(ResponseBody::new(data.id, data.amount, payment_id)),
+        Json(ResponseBody {
+            data: refund.into(),
+        }),
     ))
 }
 
@@ -121,20 +363,31 @@ pub async fn get<T: AccountService>(
 mod tests {
     use super::*;
     use crate::{
-        bank::{payment_instruments::Card, payments::Status},
+        bank::{
+            accounts::DummyService,
+            payment_instruments::Card,
+            payments::{RefundState, Status},
+        },
         bank_web::{
+            auth::Tokens,
             payments,
-            tests::{deserialize_response_body, get, post},
+            tests::{deserialize_response_body, get, post, post_as},
+            ProblemResponseBody,
         },
     };
 
     async fn setup() -> (axum::Router, payments::ResponseBody) {
-        let router = BankWeb::new_test().await.into_router();
+        setup_with(BankWeb::new_test().await).await
+    }
+
+    async fn setup_with(bank_web: BankWeb<DummyService>) -> (axum::Router, payments::ResponseBody) {
+        let router = bank_web.into_router();
 
         let request_body = payments::RequestBody {
             payment: payments::RequestData {
                 amount: 1205,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -149,7 +402,10 @@ mod tests {
 
     async fn request_refund(router: axum::Router, payment_id: Uuid) -> StatusCode {
         let request_body = RequestBody {
-            refund: RequestData { amount: 1205 },
+            refund: RequestData {
+                amount: 1205,
+                reason: Some(Reason::RequestedByCustomer),
+            },
         };
 
         let uri = format!("/api/payments/{payment_id}/refunds",);
@@ -165,6 +421,7 @@ mod tests {
             payment: payments::RequestData {
                 amount: 1205,
                 card_number: Card::new_test().into(),
+                merchant_id: None,
             },
         };
 
@@ -188,7 +445,10 @@ mod tests {
         let payment_id = payment_response_body.data.id;
 
         let request_body = RequestBody {
-            refund: RequestData { amount: 42 },
+            refund: RequestData {
+                amount: 42,
+                reason: Some(Reason::RequestedByCustomer),
+            },
         };
 
         let uri = format!("/api/payments/{payment_id}/refunds",);
@@ -205,6 +465,188 @@ mod tests {
 
         let response_body = deserialize_response_body::<ResponseBody>(response).await;
         assert_eq!(response_body.data.amount, request_body.refund.amount);
+        assert_eq!(response_body.data.reason, Reason::RequestedByCustomer);
+        // the payment's funds were withdrawn right away
+        assert_eq!(response_body.data.mechanism, Some(Mechanism::Credit));
+    }
+
+    async fn assert_refund_rejected(
+        router: &axum::Router,
+        payment_id: Uuid,
+        refund: RequestData,
+        code: ErrorCode,
+    ) {
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        let response = post(router, uri, &RequestBody { refund }).await;
+        assert_eq!(response.status(), 422);
+
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, code);
+    }
+
+    #[tokio::test]
+    async fn should_reject_refund_without_reason() {
+        let (router, payment_response_body) = setup().await;
+
+        let refund = RequestData {
+            amount: 42,
+            reason: None,
+        };
+        let code = ErrorCode::RefundReasonRequired;
+        assert_refund_rejected(&router, payment_response_body.data.id, refund, code).await;
+    }
+
+    #[tokio::test]
+    async fn should_reject_refund_below_minimum_amount() {
+        let (router, payment_response_body) = setup().await;
+
+        for amount in [0, -42] {
+            let refund = RequestData {
+                amount,
+                reason: Some(Reason::Duplicate),
+            };
+            let code = ErrorCode::RefundAmountBelowMinimum;
+            assert_refund_rejected(&router, payment_response_body.data.id, refund, code).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_refund_of_old_payment() {
+        let bank_web = BankWeb::new_test().await;
+        let pool = bank_web.pool.clone();
+        let (router, payment_response_body) = setup_with(bank_web).await;
+        let payment_id = payment_response_body.data.id;
+
+        sqlx::query!(
+            "UPDATE payments SET inserted_at = inserted_at - interval '181 days' WHERE id = $1",
+            payment_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let refund = RequestData {
+            amount: 42,
+            reason: Some(Reason::Other),
+        };
+        let code = ErrorCode::PaymentTooOld;
+        assert_refund_rejected(&router, payment_id, refund, code).await;
+    }
+
+    #[tokio::test]
+    async fn should_reject_refunds_beyond_maximum_count() {
+        let bank_web = BankWeb::new_test()
+            .await
+            .with_refund_policy(refunds::Policy {
+                max_refunds_per_payment: Some(1),
+                ..refunds::Policy::default()
+            });
+        let (router, payment_response_body) = setup_with(bank_web).await;
+        let payment_id = payment_response_body.data.id;
+
+        let refund = RequestData {
+            amount: 42,
+            reason: Some(Reason::Fraudulent),
+        };
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        let response = post(
+            &router,
+            uri,
+            &RequestBody {
+                refund: refund.clone(),
+            },
+        )
+        .await;
+        assert_eq!(response.status(), 201);
+
+        let code = ErrorCode::TooManyRefunds;
+        assert_refund_rejected(&router, payment_id, refund, code).await;
+    }
+
+    #[tokio::test]
+    async fn should_list_refunds_and_update_refundable_amount() {
+        let (router, payment_response_body) = setup().await;
+        let payment_id = payment_response_body.data.id;
+
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        for amount in [42, 100] {
+            let request_body = RequestBody {
+                refund: RequestData {
+                    amount,
+                    reason: Some(Reason::RequestedByCustomer),
+                },
+            };
+            let response = post(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 201);
+        }
+
+        let response = get(&router, &uri).await;
+        assert_eq!(response.status(), 200);
+
+        let response_body = deserialize_response_body::<ListResponseBody>(response).await;
+        let amounts: Vec<i32> = response_body.data.iter().map(|r| r.amount).collect();
+        assert_eq!(amounts, vec![42, 100]);
+
+        let response = get(&router, format!("/api/payments/{payment_id}")).await;
+        let response_body = deserialize_response_body::<payments::ResponseBody>(response).await;
+        assert_eq!(response_body.data.refund_state, RefundState::Partial);
+        assert_eq!(response_body.data.refunded_amount, 142);
+        assert_eq!(
+            response_body.data.refundable_amount,
+            payment_response_body.data.amount - 142
+        );
+    }
+
+    #[tokio::test]
+    async fn should_list_fully_refunded_payments() {
+        let (router, payment_response_body) = setup().await;
+        let payment_id = payment_response_body.data.id;
+
+        let status = request_refund(router.clone(), payment_id).await;
+        assert_eq!(status, 201);
+
+        let response = get(&router, "/api/payments?refund_state=full").await;
+        assert_eq!(response.status(), 200);
+
+        let response_body = deserialize_response_body::<payments::ListResponseBody>(response).await;
+        let payment = response_body
+            .data
+            .iter()
+            .find(|p| p.id == payment_id)
+            .expect("fully refunded payment should be listed");
+        assert_eq!(payment.refund_state, RefundState::Full);
+        assert_eq!(payment.refundable_amount, 0);
+        assert!(response_body
+            .data
+            .iter()
+            .all(|p| p.refund_state == RefundState::Full));
+    }
+
+    #[tokio::test]
+    async fn should_return_404_for_refund_of_another_payment() {
+        let (router, payment_response_body) = setup().await;
+        let payment_id = payment_response_body.data.id;
+
+        let request_body = RequestBody {
+            refund: RequestData {
+                amount: 42,
+                reason: Some(Reason::RequestedByCustomer),
+            },
+        };
+
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        let response = post(&router, uri, &request_body).await;
+        assert_eq!(response.status(), 201);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        let refund_id = response_body.data.id;
+
+        let (_, other_payment_response_body) = setup().await;
+        let other_payment_id = other_payment_response_body.data.id;
+
+        let uri = format!("/api/payments/{other_payment_id}/refunds/{refund_id}");
+        let response = get(&router, uri).await;
+        assert_eq!(response.status(), 404);
     }
 
     #[tokio::test]
@@ -215,6 +657,7 @@ mod tests {
         let request_body = RequestBody {
             refund: RequestData {
                 amount: payment_response_body.data.amount + 1,
+                reason: Some(Reason::RequestedByCustomer),
             },
         };
 
@@ -222,7 +665,120 @@ mod tests {
         let response = post(&router, uri, &request_body).await;
         assert_eq!(response.status(), 422);
 
-        let response_body = deserialize_response_body::<ErrorResponseBody>(response).await;
-        assert_eq!(response_body.error, "excessive refund amount requested");
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::ExcessiveRefundAmount);
+    }
+
+    const MAKER_TOKEN: &str = "maker-token";
+    const CHECKER_TOKEN: &str = "checker-token";
+
+    async fn setup_with_approval() -> (axum::Router, Uuid) {
+        let tokens = [("maker", MAKER_TOKEN), ("checker", CHECKER_TOKEN)]
+            .into_iter()
+            .map(|(principal, token)| (principal.to_string(), token.to_string()))
+            .collect::<Tokens>();
+        let bank_web = BankWeb::new_test()
+            .await
+            .with_refund_policy(refunds::Policy {
+                approval_threshold: Some(100),
+                ..refunds::Policy::default()
+            })
+            .with_api_tokens(tokens);
+
+        let (router, payment_response_body) = setup_with(bank_web).await;
+        (router, payment_response_body.data.id)
+    }
+
+    async fn request_large_refund(router: &axum::Router, payment_id: Uuid) -> Uuid {
+        let request_body = RequestBody {
+            refund: RequestData {
+                amount: 1000,
+                reason: Some(Reason::RequestedByCustomer),
+            },
+        };
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        let response = post_as(router, uri, &request_body, MAKER_TOKEN).await;
+        assert_eq!(response.status(), 202);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, refunds::Status::AwaitingApproval);
+        assert_eq!(response_body.data.requested_by.as_deref(), Some("maker"));
+        response_body.data.id
+    }
+
+    #[tokio::test]
+    async fn should_require_approval_for_large_refunds() {
+        let (router, payment_id) = setup_with_approval().await;
+        let refund_id = request_large_refund(&router, payment_id).await;
+
+        let response = get(&router, format!("/api/payments/{payment_id}")).await;
+        let response_body = deserialize_response_body::<payments::ResponseBody>(response).await;
+        assert_eq!(response_body.data.refunded_amount, 0);
+
+        let uri = format!("/api/payments/{payment_id}/refunds/{refund_id}/approve");
+        let response = post(&router, &uri, &()).await;
+        assert_eq!(response.status(), 401);
+
+        let response = post_as(&router, &uri, &(), MAKER_TOKEN).await;
+        assert_eq!(response.status(), 403);
+
+        let response = post_as(&router, &uri, &(), CHECKER_TOKEN).await;
+        assert_eq!(response.status(), 200);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, refunds::Status::Succeeded);
+        assert_eq!(response_body.data.reviewed_by.as_deref(), Some("checker"));
+
+        let response = post_as(&router, &uri, &(), CHECKER_TOKEN).await;
+        assert_eq!(response.status(), 409);
+
+        let response = get(&router, format!("/api/payments/{payment_id}")).await;
+        let response_body = deserialize_response_body::<payments::ResponseBody>(response).await;
+        assert_eq!(response_body.data.refunded_amount, 1000);
+    }
+
+    #[tokio::test]
+    async fn should_not_credit_rejected_refunds() {
+        let (router, payment_id) = setup_with_approval().await;
+        let refund_id = request_large_refund(&router, payment_id).await;
+
+        let uri = format!("/api/payments/{payment_id}/refunds/{refund_id}/reject");
+        let response = post_as(&router, &uri, &(), CHECKER_TOKEN).await;
+        assert_eq!(response.status(), 200);
+
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data.status, refunds::Status::Rejected);
+
+        let uri = format!("/api/payments/{payment_id}/refunds/{refund_id}/approve");
+        let response = post_as(&router, &uri, &(), CHECKER_TOKEN).await;
+        assert_eq!(response.status(), 409);
+
+        let response = get(&router, format!("/api/payments/{payment_id}")).await;
+        let response_body = deserialize_response_body::<payments::ResponseBody>(response).await;
+        assert_eq!(response_body.data.refunded_amount, 0);
+    }
+
+    #[tokio::test]
+    async fn should_require_authentication_for_large_refunds() {
+        let (router, payment_id) = setup_with_approval().await;
+
+        let request_body = RequestBody {
+            refund: RequestData {
+                amount: 1000,
+                reason: Some(Reason::RequestedByCustomer),
+            },
+        };
+        let uri = format!("/api/payments/{payment_id}/refunds");
+        let response = post(&router, &uri, &request_body).await;
+        assert_eq!(response.status(), 401);
+
+        let request_body = RequestBody {
+            refund: RequestData {
+                amount: 100,
+                reason: Some(Reason::RequestedByCustomer),
+            },
+        };
+        let response = post(&router, &uri, &request_body).await;
+        assert_eq!(response.status(), 201);
     }
 }
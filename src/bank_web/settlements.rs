@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::{openapi, BankWeb};
+use crate::bank::{
+    accounts::AccountService,
+    settlements::{self, LineKind},
+};
+use crate::errors::{ApiError, ErrorCode};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParams {
+    merchant_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = SettlementLine)]
+pub struct LineData {
+    id: Uuid,
+    #[schema(value_type = SettlementLineKind)]
+    kind: LineKind,
+    payment_id: Uuid,
+    refund_id: Option<Uuid>,
+    amount: i32,
+    fee_amount: i32,
+}
+
+impl From<settlements::Line> for LineData {
+    fn from(line: settlements::Line) -> Self {
+        Self {
+            id: line.id,
+            kind: line.kind,
+            payment_id: line.payment_id,
+            refund_id: line.refund_id,
+            amount: line.amount,
+            fee_amount: line.fee_amount,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = SettlementBatch)]
+pub struct ResponseData {
+    id: Uuid,
+    merchant_id: String,
+    #[serde(with = "time::serde::rfc3339")]
+    cut_off_at: OffsetDateTime,
+    payments_amount: i64,
+    refunds_amount: i64,
+    fees_amount: i64,
+    net_amount: i64,
+    /// Only included when fetching a single batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<SettlementLine>>)]
+    lines: Option<Vec<LineData>>,
+}
+
+impl From<settlements::Batch> for ResponseData {
+    fn from(batch: settlements::Batch) -> Self {
+        Self {
+            id: batch.id,
+            merchant_id: batch.merchant_id,
+            cut_off_at: batch.cut_off_at.assume_utc(),
+            payments_amount: batch.payments_amount,
+            refunds_amount: batch.refunds_amount,
+            fees_amount: batch.fees_amount,
+            net_amount: batch.net_amount,
+            lines: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = SettlementBatchResponseBody)]
+pub struct ResponseBody {
+    #[schema(value_type = SettlementBatch)]
+    data: ResponseData,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[schema(as = SettlementBatchListResponseBody)]
+pub struct ListResponseBody {
+    #[schema(value_type = Vec<SettlementBatch>)]
+    data: Vec<ResponseData>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/settlement_batches",
+    tag = "settlements",
+    params(ListParams),
+    responses(
+        (status = 200, description = "OK", body = SettlementBatchListResponseBody),
+    )
+)]
+pub async fn index<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Query(params): Query<ListParams>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    let batches = settlements::list(&bank_web.pool, params.merchant_id.as_deref()).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ListResponseBody {
+            data: batches.into_iter().map(ResponseData::from).collect(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/settlement_batches/{batch_id}",
+    tag = "settlements",
+    params(
+        ("batch_id" = Uuid, Path, description = "Id of the settlement batch"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = SettlementBatchResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn get<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(batch_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ResponseBody>), ApiError> {
+    let batch = settlements::get(&bank_web.pool, batch_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::SettlementBatchNotFound))?;
+
+    let lines = settlements::lines(&bank_web.pool, batch_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ResponseBody {
+            data: ResponseData {
+                lines: Some(lines.into_iter().map(LineData::from).collect()),
+                ..batch.into()
+            },
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::PrimitiveDateTime;
+
+    use super::*;
+    use crate::{
+        bank::payment_instruments::Card,
+        bank_web::{
+            payments,
+            tests::{deserialize_response_body, get, post},
+        },
+    };
+
+    #[tokio::test]
+    async fn should_list_settled_payments() {
+        let bank_web = BankWeb::new_test().await;
+        let pool = bank_web.pool.clone();
+        let router = bank_web.into_router();
+
+        let merchant_id = Uuid::new_v4().to_string();
+        let request_body = payments::RequestBody {
+            payment: payments::RequestData {
+                amount: 1205,
+                card_number: Card::new_test().into(),
+                merchant_id: Some(merchant_id.clone()),
+            },
+        };
+        let response = post(&router, "/api/payments", &request_body).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let now = OffsetDateTime::now_utc();
+        settlements::settle(&pool, PrimitiveDateTime::new(now.date(), now.time()))
+            .await
+            .expect("failed to settle");
+
+        let response = get(
+            &router,
+            format!("/api/settlement_batches?merchant_id={merchant_id}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ListResponseBody>(response).await;
+        assert_eq!(response_body.data.len(), 1);
+        assert_eq!(response_body.data[0].net_amount, 1205);
+
+        let batch_id = response_body.data[0].id;
+        let response = get(&router, format!("/api/settlement_batches/{batch_id}")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        let lines = response_body.data.lines.expect("missing batch lines");
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].kind, LineKind::Payment);
+        assert_eq!(lines[0].amount, 1205);
+
+        let response = get(
+            &router,
+            format!("/api/settlement_batches/{}", Uuid::new_v4()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,97 @@
+use axum::{extract::State, http::Request, middleware::Next, response::Response};
+
+use super::BankWeb;
+use crate::bank::accounts::AccountService;
+
+/// Guards the requests which change state, so that payments and refunds
+/// reach a terminal status before the process exits, see `Shutdown::guard`.
+pub async fn guard<T: AccountService, B: Send + 'static>(
+    State(bank_web): State<BankWeb<T>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if request.method().is_safe() {
+        return next.run(request).await;
+    }
+
+    bank_web.shutdown.guard(next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::bank::{
+        accounts::{DummyService, HoldRef},
+        payment_instruments::Card,
+    };
+    use crate::bank_web::{
+        payments::{RequestBody, RequestData},
+        tests::post,
+    };
+    use crate::shutdown::Shutdown;
+
+    /// Takes a while to place holds.
+    #[derive(Clone, Default)]
+    struct SlowService {
+        dummy: DummyService,
+        withdraw_funds_count: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl AccountService for SlowService {
+        async fn place_hold(&self, account_number: &str, amount: i32) -> Result<HoldRef, String> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            self.dummy.place_hold(account_number, amount).await
+        }
+
+        async fn release_hold(&self, hold_ref: HoldRef) -> Result<(), String> {
+            self.dummy.release_hold(hold_ref).await
+        }
+
+        async fn withdraw_funds(&self, hold_ref: HoldRef) -> Result<(), String> {
+            self.withdraw_funds_count.fetch_add(1, Ordering::SeqCst);
+            self.dummy.withdraw_funds(hold_ref).await
+        }
+
+        async fn capture_funds(&self, hold_ref: HoldRef, amount: i32) -> Result<(), String> {
+            self.dummy.capture_funds(hold_ref, amount).await
+        }
+
+        async fn credit_funds(&self, account_number: &str, amount: i32) -> Result<(), String> {
+            self.dummy.credit_funds(account_number, amount).await
+        }
+    }
+
+    #[tokio::test]
+    async fn should_complete_payments_whose_client_disconnected() {
+        let pool = crate::pg_pool().await.unwrap();
+        let service = SlowService::default();
+        let shutdown = Shutdown::new();
+        let router = BankWeb::new(pool, service.clone())
+            .with_shutdown(shutdown.clone())
+            .into_router();
+
+        let request_body = RequestBody {
+            payment: RequestData {
+                amount: 123,
+                card_number: Card::new_test().into(),
+                merchant_id: None,
+            },
+        };
+        let request = post(&router, "/api/payments", &request_body);
+        // the client disconnects while the hold is being placed
+        let _ = tokio::time::timeout(Duration::from_millis(10), request).await;
+
+        shutdown.trigger();
+        shutdown.drain().await;
+        assert_eq!(service.withdraw_funds_count.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,57 @@
+//! Version 2 of the API, under `/api/v2`.
+//!
+//! It shares the `bank` domain layer with v1 (`/api`), but has its own request
+//! and response types: card numbers are masked, amounts are money objects and
+//! resources have their timestamps. It only covers payments so far, the other
+//! resources are still served by v1.
+
+use axum::routing::{get, post, MethodRouter};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::BankWeb;
+use crate::bank::accounts::AccountService;
+
+pub mod payments;
+
+/// Path under which v2 is served.
+pub const PREFIX: &str = "/api/v2";
+
+/// Currency of every amount: the bank only moves euros.
+pub const CURRENCY: &str = "EUR";
+
+/// An amount of money, in minor units (cents) of its currency.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = Money)]
+pub struct Money {
+    pub value: i32,
+    /// ISO 4217 code of the currency, always `EUR`.
+    #[schema(example = "EUR")]
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(value: i32) -> Self {
+        Self {
+            value,
+            currency: CURRENCY.to_string(),
+        }
+    }
+}
+
+const PAYMENTS: &str = "/api/v2/payments";
+const PAYMENT: &str = "/api/v2/payments/:payment_id";
+
+/// The paths served by v2, in the router's syntax.
+pub const PATHS: [&str; 2] = [PAYMENTS, PAYMENT];
+
+/// The routes of v2, by path.
+pub fn routes<T: AccountService>() -> Vec<(&'static str, MethodRouter<BankWeb<T>>)> {
+    vec![
+        (
+            PAYMENTS,
+            post(payments::post::<T>).get(payments::index::<T>),
+        ),
+        (PAYMENT, get(payments::get::<T>)),
+    ]
+}
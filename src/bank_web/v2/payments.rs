@@ -0,0 +1,377 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{Money, CURRENCY};
+use crate::bank::{
+    accounts::AccountService,
+    payment_instruments::Card,
+    payments::{self, DeclineCode, FailureReason, Payment, RefundState, Status},
+    refunds::{self, Balance},
+    verifications::{self, Verification},
+};
+use crate::bank_web::{openapi, payments as v1, BankWeb};
+use crate::errors::{ApiError, ErrorCode};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = V2NewPayment)]
+pub struct RequestData {
+    pub amount: Money,
+    pub card_number: String,
+    #[serde(default)]
+    pub merchant_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = V2PaymentRequestBody)]
+pub struct RequestBody {
+    #[schema(value_type = V2NewPayment)]
+    pub payment: RequestData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = V2Payment)]
+pub struct ResponseData {
+    pub id: Uuid,
+    pub amount: Money,
+    /// Card number with all but its last digits masked.
+    pub masked_card_number: String,
+    pub merchant_id: String,
+    #[schema(value_type = PaymentStatus)]
+    pub status: Status,
+    /// Fee charged to the merchant.
+    pub fee: Money,
+    /// Part of the fee given back to the merchant by refunds.
+    pub refunded_fee: Money,
+    #[schema(value_type = RefundState)]
+    pub refund_state: RefundState,
+    pub refunded: Money,
+    pub disputed: Money,
+    pub refundable: Money,
+    /// When the payment is rejected, if it's still awaiting review by then.
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub review_deadline_at: Option<OffsetDateTime>,
+    pub reviewed_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option", default)]
+    pub reviewed_at: Option<OffsetDateTime>,
+    /// Why the payment was declined, if it was.
+    #[schema(value_type = Option<DeclineCode>)]
+    pub decline_code: Option<DeclineCode>,
+    /// Why the payment failed, if it did.
+    #[schema(value_type = Option<FailureReason>)]
+    pub failure_reason: Option<FailureReason>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub inserted_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl ResponseData {
+    pub fn new(payment: Payment, balance: Balance) -> Self {
+        Self {
+            id: payment.id,
+            amount: Money::new(payment.amount),
+            masked_card_number: Card(payment.card_number).masked(),
+            merchant_id: payment.merchant_id,
+            status: payment.status,
+            fee: Money::new(payment.fee_amount),
+            refunded_fee: Money::new(balance.refunded_fee_amount),
+            refund_state: payment.refund_state,
+            refunded: Money::new(balance.refunded_amount),
+            disputed: Money::new(balance.disputed_amount),
+            refundable: Money::new(balance.refundable_amount),
+            review_deadline_at: payment.review_deadline_at.map(|at| at.assume_utc()),
+            reviewed_by: payment.reviewed_by,
+            reviewed_at: payment.reviewed_at.map(|at| at.assume_utc()),
+            decline_code: payment.decline_code,
+            failure_reason: payment.failure_reason,
+            inserted_at: payment.inserted_at.assume_utc(),
+            updated_at: payment.updated_at.assume_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = V2PaymentResponseBody)]
+pub struct ResponseBody {
+    #[schema(value_type = V2Payment)]
+    pub data: ResponseData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = V2PaymentListResponseBody)]
+pub struct ListResponseBody {
+    #[schema(value_type = Vec<V2Payment>)]
+    pub data: Vec<ResponseData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = V2Verification)]
+pub struct VerificationData {
+    pub id: Uuid,
+    /// Card number with all but its last digits masked.
+    pub masked_card_number: String,
+    pub merchant_id: String,
+    #[schema(value_type = VerificationStatus)]
+    pub status: verifications::Status,
+    #[schema(value_type = Option<DeclineCode>)]
+    pub decline_code: Option<DeclineCode>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub inserted_at: OffsetDateTime,
+}
+
+impl From<Verification> for VerificationData {
+    fn from(verification: Verification) -> Self {
+        Self {
+            id: verification.id,
+            masked_card_number: Card(verification.card_number).masked(),
+            merchant_id: verification.merchant_id,
+            status: verification.status,
+            decline_code: verification.decline_code,
+            inserted_at: verification.inserted_at.assume_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = V2VerificationResponseBody)]
+pub struct VerificationResponseBody {
+    #[schema(value_type = V2Verification)]
+    pub data: VerificationData,
+}
+
+type PaymentResponse = Result<(StatusCode, Json<ResponseBody>), ApiError>;
+
+/// Responds with a payment, as it's persisted.
+async fn payment_response(pool: &PgPool, status: StatusCode, payment_id: Uuid) -> PaymentResponse {
+    let payment = payments::get(pool, payment_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::PaymentNotFound))?;
+    let balance = refunds::balance(pool, payment_id).await?;
+
+    Ok((
+        status,
+        Json(ResponseBody {
+            data: ResponseData::new(payment, balance),
+        }),
+    ))
+}
+
+/// Responds with a verification, as it's persisted.
+async fn verification_response(
+    pool: &PgPool,
+    status: StatusCode,
+    verification_id: Uuid,
+) -> Result<(StatusCode, Json<VerificationResponseBody>), ApiError> {
+    let verification = verifications::get(pool, verification_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::VerificationNotFound))?;
+
+    Ok((
+        status,
+        Json(VerificationResponseBody {
+            data: verification.into(),
+        }),
+    ))
+}
+
+/// Charges a card like `POST /api/payments`, or verifies it for a zero
+/// amount.
+#[utoipa::path(
+    post,
+    path = "/api/v2/payments",
+    tag = "v2",
+    request_body = V2PaymentRequestBody,
+    responses(
+        (status = 201, response = openapi::V2PaymentCreated),
+        (status = 202, description = "The payment was flagged for review, its funds are held until it's reviewed", body = V2PaymentResponseBody),
+        (status = 400, response = openapi::V2PaymentDeclined),
+        (status = 402, response = openapi::V2PaymentDeclined),
+        (status = 403, response = openapi::V2PaymentDeclined),
+        (status = 422, response = openapi::Invalid),
+        (status = 500, response = openapi::V2PaymentDeclined),
+        (status = 503, response = openapi::V2PaymentDeclined),
+        (status = 504, description = "The account service timed out: the payment, or the verification of a zero-amount payment, stays processing until it's reconciled", body = V2PaymentResponseBody),
+        (status = 502, response = openapi::UpstreamFailed),
+    )
+)]
+pub async fn post<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Json(body): Json<RequestBody>,
+) -> Result<Response, ApiError> {
+    let amount = body.payment.amount;
+    let merchant_id = body
+        .payment
+        .merchant_id
+        .unwrap_or_else(|| payments::DEFAULT_MERCHANT_ID.to_string());
+
+    if amount.currency != CURRENCY {
+        return Err(ApiError::Validation(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::UnsupportedCurrency,
+        ));
+    }
+    let card = v1::validate(amount.value, body.payment.card_number)?;
+
+    // payment requests for 0 only verify the card, nothing is charged
+    if amount.value == 0 {
+        let (status, verification_id) =
+            crate::bank_web::verifications::verify(&bank_web, card, &merchant_id).await?;
+        return verification_response(&bank_web.pool, status, verification_id)
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    let (status, payment_id) = v1::charge(&bank_web, card, amount.value, &merchant_id).await?;
+    payment_response(&bank_web.pool, status, payment_id)
+        .await
+        .map(IntoResponse::into_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/payments/{payment_id}",
+    tag = "v2",
+    params(
+        ("payment_id" = Uuid, Path, description = "Id of the payment"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = V2PaymentResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn get<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(payment_id): Path<Uuid>,
+) -> PaymentResponse {
+    payment_response(&bank_web.pool, StatusCode::OK, payment_id).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v2/payments",
+    tag = "v2",
+    params(payments::Filter),
+    responses(
+        (status = 200, description = "OK", body = V2PaymentListResponseBody),
+    )
+)]
+pub async fn index<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Query(filter): Query<payments::Filter>,
+) -> Result<(StatusCode, Json<ListResponseBody>), ApiError> {
+    let payments = payments::list(&bank_web.pool, &filter).await?;
+    let ids: Vec<Uuid> = payments.iter().map(|payment| payment.id).collect();
+    let mut balances = refunds::balances(&bank_web.pool, &ids).await?;
+
+    let data = payments
+        .into_iter()
+        .map(|payment| {
+            let balance = balances.remove(&payment.id).unwrap_or_default();
+            ResponseData::new(payment, balance)
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(ListResponseBody { data })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank_web::{
+        tests::{deserialize_response_body, get, post},
+        ProblemResponseBody,
+    };
+
+    fn request_body(amount: Money, card: &Card) -> RequestBody {
+        RequestBody {
+            payment: RequestData {
+                amount,
+                card_number: card.card_number().to_string(),
+                merchant_id: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn should_answer_payment_with_masked_card_and_money() {
+        let router = BankWeb::new_test().await.into_router();
+        let card = Card::new_test();
+
+        let response = post(
+            &router,
+            "/api/v2/payments",
+            &request_body(Money::new(123), &card),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        let payment = response_body.data;
+        assert_eq!(payment.amount, Money::new(123));
+        assert_eq!(payment.masked_card_number, card.masked());
+        assert_eq!(payment.status, Status::Approved);
+        assert_eq!(payment.refundable, Money::new(123));
+        assert!(payment.inserted_at <= payment.updated_at);
+
+        // the same payment is served by v1, in its own shape
+        let response = get(&router, format!("/api/payments/{}", payment.id)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<v1::ResponseBody>(response).await;
+        assert_eq!(response_body.data.amount, 123);
+        assert_eq!(response_body.data.card_number, card.card_number());
+
+        let response = get(&router, format!("/api/v2/payments/{}", payment.id)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ResponseBody>(response).await;
+        assert_eq!(response_body.data, payment);
+
+        let response = get(&router, "/api/v2/payments?status=approved").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_body = deserialize_response_body::<ListResponseBody>(response).await;
+        assert!(response_body.data.contains(&payment));
+    }
+
+    #[tokio::test]
+    async fn should_verify_card_for_zero_amount() {
+        let router = BankWeb::new_test().await.into_router();
+        let card = Card::new_test();
+
+        let response = post(
+            &router,
+            "/api/v2/payments",
+            &request_body(Money::new(0), &card),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response_body = deserialize_response_body::<VerificationResponseBody>(response).await;
+        assert_eq!(response_body.data.masked_card_number, card.masked());
+        assert_eq!(response_body.data.status, verifications::Status::Verified);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unsupported_currency() {
+        let router = BankWeb::new_test().await.into_router();
+        let amount = Money {
+            value: 123,
+            currency: "USD".to_string(),
+        };
+
+        let response = post(
+            &router,
+            "/api/v2/payments",
+            &request_body(amount, &Card::new_test()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::UnsupportedCurrency);
+    }
+}
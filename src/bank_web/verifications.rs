@@ -0,0 +1,169 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{openapi, payments::Processed, BankWeb};
+use crate::bank::{
+    accounts::{self, AccountService},
+    payment_instruments::Card,
+    payments::{DeclineCode, Reversal},
+    reconciliation::Operation,
+    timeouts,
+    verifications::{self, Outcome, Status, Verification},
+};
+use crate::errors::{ApiError, ErrorCode, PaymentError};
+use crate::metrics;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = Verification)]
+pub struct ResponseData {
+    pub id: Uuid,
+    pub card_number: String,
+    pub merchant_id: String,
+    #[schema(value_type = VerificationStatus)]
+    pub status: Status,
+    #[schema(value_type = Option<DeclineCode>)]
+    pub decline_code: Option<DeclineCode>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub inserted_at: OffsetDateTime,
+}
+
+impl From<Verification> for ResponseData {
+    fn from(verification: Verification) -> Self {
+        Self {
+            id: verification.id,
+            card_number: verification.card_number,
+            merchant_id: verification.merchant_id,
+            status: verification.status,
+            decline_code: verification.decline_code,
+            inserted_at: verification.inserted_at.assume_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[schema(as = VerificationResponseBody)]
+pub struct ResponseBody {
+    #[schema(value_type = Verification)]
+    pub data: ResponseData,
+}
+
+type VerificationResponse = Result<(StatusCode, Json<ResponseBody>), ApiError>;
+
+/// Responds with a verification, as it's persisted.
+pub async fn verification_response(
+    pool: &PgPool,
+    status: StatusCode,
+    verification_id: Uuid,
+) -> VerificationResponse {
+    let verification = verifications::get(pool, verification_id)
+        .await
+        .map_err(ApiError::not_found(ErrorCode::VerificationNotFound))?;
+
+    Ok((
+        status,
+        Json(ResponseBody {
+            data: verification.into(),
+        }),
+    ))
+}
+
+/// Verifies a card, for a zero-amount payment: places a hold on the card's
+/// account and releases it right away. Returns the id of the verification.
+///
+/// Declines are answered like the declines of payments. If the account
+/// service fails, whether the card is valid isn't known, so nothing is
+/// persisted. If it times out, the hold may have been placed all the same:
+/// the verification stays processing until the call is resolved, and so does
+/// the release of a hold which timed out (see `timeouts::resolve`).
+pub async fn verify<T: AccountService>(
+    bank_web: &BankWeb<T>,
+    card: Card,
+    merchant_id: &str,
+) -> Processed {
+    let (hold_result, _) = metrics::account_service_call(
+        Operation::PlaceHold,
+        bank_web
+            .account_service
+            .place_hold(card.account_number(), verifications::HOLD_AMOUNT),
+    )
+    .await;
+
+    let (status, outcome, timed_out) = match hold_result {
+        Ok(hold_ref) => {
+            let (released, _) = metrics::account_service_call(
+                Operation::ReleaseHold,
+                bank_web.account_service.release_hold(hold_ref),
+            )
+            .await;
+            let timed_out = match &released {
+                Ok(()) => None,
+                Err(error) if error == accounts::TIMEOUT => {
+                    Some((Operation::ReleaseHold, Some(hold_ref.id())))
+                }
+                Err(error) => {
+                    tracing::warn!(hold_id = %hold_ref.id(), %error, "failed to release verification hold");
+                    None
+                }
+            };
+            let outcome = Outcome::Verified {
+                hold_id: hold_ref.id(),
+                released: released.is_ok(),
+            };
+            (StatusCode::CREATED, outcome, timed_out)
+        }
+        Err(error) if error == accounts::TIMEOUT => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Outcome::TimedOut,
+            Some((Operation::PlaceHold, None)),
+        ),
+        Err(error) => {
+            let payment_err = PaymentError::from(&error);
+            let Reversal::Declined(decline_code) = payment_err.get_reversal() else {
+                return Err(ApiError::Upstream(
+                    ErrorCode::VerificationFailed,
+                    payment_err,
+                ));
+            };
+            (
+                payment_err.get_http_status_code(),
+                Outcome::Declined(decline_code),
+                None,
+            )
+        }
+    };
+
+    let verification_id =
+        verifications::insert(&bank_web.pool, &String::from(card), merchant_id, outcome).await?;
+    if let Some((operation, hold_id)) = timed_out {
+        timeouts::record_verification(&bank_web.pool, verification_id, operation, hold_id).await?;
+    }
+
+    Ok((status, verification_id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/verifications/{verification_id}",
+    tag = "verifications",
+    params(
+        ("verification_id" = Uuid, Path, description = "Id of the verification"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = VerificationResponseBody),
+        (status = 404, response = openapi::NotFound),
+    )
+)]
+pub async fn get<T: AccountService>(
+    State(bank_web): State<BankWeb<T>>,
+    Path(verification_id): Path<Uuid>,
+) -> VerificationResponse {
+    verification_response(&bank_web.pool, StatusCode::OK, verification_id).await
+}
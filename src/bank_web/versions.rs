@@ -0,0 +1,201 @@
+use std::convert::Infallible;
+
+use axum::{
+    body::Body,
+    http::{
+        header::{HeaderName, HeaderValue, LINK},
+        uri::PathAndQuery,
+        Request, Response, StatusCode, Uri,
+    },
+    response::IntoResponse,
+    Router,
+};
+use tower::{service_fn, ServiceExt};
+
+use super::v2;
+use crate::errors::{ApiError, ErrorCode};
+
+/// Header selecting the version of the API for requests to unversioned paths,
+/// e.g. `Api-Version: 2` routes `/api/payments` to `/api/v2/payments`.
+/// Unversioned paths are v1 by default, and so are the resources v2 doesn't
+/// cover yet.
+pub const API_VERSION: HeaderName = HeaderName::from_static("api-version");
+
+/// Header telling clients that a resource is deprecated (RFC 9745).
+pub const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+
+/// When v1 was deprecated, as the `Deprecation` header's structured date.
+const V1_DEPRECATED_AT: &str = "@1792281600";
+
+/// Where v1 clients learn about the deprecation, and about v2.
+const V1_DEPRECATION_LINK: &str = "</docs/>; rel=\"deprecation\"";
+
+/// Marks a v1 response as deprecated.
+pub async fn deprecate<B>(mut response: Response<B>) -> Response<B> {
+    let headers = response.headers_mut();
+    headers.insert(DEPRECATION, HeaderValue::from_static(V1_DEPRECATED_AT));
+    headers.insert(LINK, HeaderValue::from_static(V1_DEPRECATION_LINK));
+    response
+}
+
+/// Routes requests to unversioned paths to the version selected by their
+/// `Api-Version` header, before `router` routes them.
+///
+/// Versioned paths (`/api/v2/...`) are left as is, whatever the header, and so
+/// are the paths v2 doesn't serve: they're still served by v1.
+pub fn select(router: Router) -> Router {
+    Router::new().fallback_service(service_fn(move |mut request: Request<Body>| {
+        let router = router.clone();
+        async move {
+            let Some(version) = request.headers().get(API_VERSION) else {
+                return router.oneshot(request).await;
+            };
+
+            match version.as_bytes() {
+                b"1" => {}
+                b"2" => {
+                    if let Some(uri) = versioned(request.uri(), v2::PREFIX) {
+                        *request.uri_mut() = uri;
+                    }
+                }
+                _ => {
+                    let error = ApiError::Validation(
+                        StatusCode::BAD_REQUEST,
+                        ErrorCode::UnsupportedApiVersion,
+                    );
+                    return Ok::<_, Infallible>(error.into_response());
+                }
+            }
+
+            router.oneshot(request).await
+        }
+    }))
+}
+
+/// Moves an unversioned `/api/...` URI under `prefix`, e.g. `/api/v2`, if v2
+/// serves the resulting path.
+fn versioned(uri: &Uri, prefix: &str) -> Option<Uri> {
+    let path = uri.path().strip_prefix("/api/")?;
+    if path.starts_with("v2/") || path == "v2" {
+        return None;
+    }
+    let versioned_path = format!("{prefix}/{path}");
+    if !v2::PATHS
+        .iter()
+        .any(|pattern| matches(pattern, &versioned_path))
+    {
+        return None;
+    }
+
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{versioned_path}?{query}"),
+        None => versioned_path,
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// Whether `path` matches a route `pattern`, whose `:name` segments match any
+/// segment.
+fn matches(pattern: &str, path: &str) -> bool {
+    let mut segments = path.split('/');
+    pattern.split('/').all(|pattern_segment| {
+        segments.next().is_some_and(|segment| {
+            pattern_segment == segment || (pattern_segment.starts_with(':') && !segment.is_empty())
+        })
+    }) && segments.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::payments::Payment;
+    use crate::bank_web::{
+        tests::{deserialize_response_body, get, send_request},
+        BankWeb, ProblemResponseBody,
+    };
+
+    fn get_with_version(uri: &str, version: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .header(API_VERSION, version)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_versioned() {
+        let uri = Uri::from_static("/api/payments?status=approved");
+        assert_eq!(
+            versioned(&uri, "/api/v2").unwrap(),
+            "/api/v2/payments?status=approved"
+        );
+
+        assert_eq!(
+            versioned(&Uri::from_static("/api/v2/payments"), "/api/v2"),
+            None
+        );
+        assert_eq!(
+            versioned(&Uri::from_static("/openapi.json"), "/api/v2"),
+            None
+        );
+
+        let uri = Uri::from_static("/api/payments/5f0e7c2a-54b2-4a8e-9e5a-0c2a1c7d4b3e");
+        assert_eq!(
+            versioned(&uri, "/api/v2").unwrap(),
+            "/api/v2/payments/5f0e7c2a-54b2-4a8e-9e5a-0c2a1c7d4b3e"
+        );
+        // v2 doesn't serve refunds yet
+        let uri = Uri::from_static("/api/payments/5f0e7c2a-54b2-4a8e-9e5a-0c2a1c7d4b3e/refunds");
+        assert_eq!(versioned(&uri, "/api/v2"), None);
+        assert_eq!(
+            versioned(&Uri::from_static("/api/payments/"), "/api/v2"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn should_deprecate_v1_responses_only() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let response = get(&router, "/api/payments").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[DEPRECATION], V1_DEPRECATED_AT);
+        assert_eq!(response.headers()[LINK], V1_DEPRECATION_LINK);
+
+        let response = get(&router, "/api/v2/payments").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(DEPRECATION));
+    }
+
+    #[tokio::test]
+    async fn should_select_version_by_header() {
+        let router = BankWeb::new_test().await.into_router();
+
+        let response = send_request(&router, get_with_version("/api/payments", "2")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(DEPRECATION));
+
+        let response = send_request(&router, get_with_version("/api/payments", "1")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(DEPRECATION));
+
+        let response = send_request(&router, get_with_version("/api/payments", "3")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response_body = deserialize_response_body::<ProblemResponseBody>(response).await;
+        assert_eq!(response_body.code, ErrorCode::UnsupportedApiVersion);
+    }
+
+    #[tokio::test]
+    async fn should_serve_resources_v2_doesnt_cover_with_v1() {
+        let router = BankWeb::new_test().await.into_router();
+        let pool = crate::pg_pool().await.unwrap();
+        let payment = Payment::new_test(&pool).await.unwrap();
+
+        let uri = format!("/api/payments/{}/refunds", payment.id);
+        let response = send_request(&router, get_with_version(&uri, "2")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(DEPRECATION));
+    }
+}
@@ -1,11 +1,25 @@
-use axum::http::StatusCode;
+use axum::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
-use crate::bank::payments::Status;
+use crate::bank::{
+    fees::ScheduleViolation,
+    payments::{DeclineCode, FailureReason, Reversal},
+    refunds::PolicyViolation,
+};
+use crate::bank_web::ProblemResponseBody;
+
+/// Media type of the error responses.
+pub const PROBLEM_JSON: &str = "application/problem+json";
 
 #[derive(Debug)]
 pub struct PaymentError {
     pub code: i32,
+    #[allow(dead_code)]
     pub message: String,
 }
 
@@ -31,6 +45,8 @@ impl PaymentError {
             "invalid_amount" => (400, "Bad Request"),
             "insufficient_funds" => (402, "Payment Required"),
             "service_unavailable" => (503, "Service unavailable"),
+            // the call may have gone through, see `accounts::Timeout`
+            "timeout" => (504, "Gateway Timeout"),
             _ => (500, "Internal Error"),
         };
         PaymentError {
@@ -39,10 +55,14 @@ impl PaymentError {
         }
     }
 
-    pub fn get_payment_status(&self) -> Status {
+    /// How the payment ends, and why.
+    pub fn get_reversal(&self) -> Reversal {
         match self.code {
-            402 | 403 => Status::Declined,
-            _ => Status::Failed,
+            402 => Reversal::Declined(DeclineCode::InsufficientFunds),
+            403 => Reversal::Declined(DeclineCode::InvalidAccountNumber),
+            400 => Reversal::Failed(FailureReason::InvalidAmount),
+            503 => Reversal::Failed(FailureReason::ServiceUnavailable),
+            _ => Reversal::Failed(FailureReason::AccountServiceError),
         }
     }
 
@@ -50,3 +70,290 @@ impl PaymentError {
         StatusCode::from_u16(self.code as u16).unwrap_or(StatusCode::NOT_FOUND)
     }
 }
+
+/// Stable, machine-readable codes of the errors answered by the API, which
+/// clients can match on rather than on the human-readable detail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    AuthenticationRequired,
+    RefundAuthenticationRequired,
+    SelfReview,
+    NotMerchantPrincipal,
+    NotAccountServicePrincipal,
+    PaymentNotFound,
+    RefundNotFound,
+    DisputeNotFound,
+    FeeScheduleNotFound,
+    SettlementBatchNotFound,
+    VerificationNotFound,
+    NegativeAmount,
+    InvalidCardNumber,
+    CardNumberAlreadyUsed,
+    PaymentNotApproved,
+    PaymentNotAwaitingReview,
+    RefundReasonRequired,
+    RefundAmountBelowMinimum,
+    PaymentTooOld,
+    TooManyRefunds,
+    ExcessiveRefundAmount,
+    RefundNotAwaitingApproval,
+    RefundInProgress,
+    NonPositiveDisputeAmount,
+    ExcessiveDisputeAmount,
+    DisputeAlreadyDecided,
+    FeePercentOutOfRange,
+    NegativeFeeAmount,
+    FeeCapBelowMinimum,
+    RefundFailed,
+    ReviewFailed,
+    VerificationFailed,
+    UnsupportedApiVersion,
+    UnsupportedCurrency,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AuthenticationRequired => "authentication_required",
+            Self::RefundAuthenticationRequired => "refund_authentication_required",
+            Self::SelfReview => "self_review",
+            Self::NotMerchantPrincipal => "not_merchant_principal",
+            Self::NotAccountServicePrincipal => "not_account_service_principal",
+            Self::PaymentNotFound => "payment_not_found",
+            Self::RefundNotFound => "refund_not_found",
+            Self::DisputeNotFound => "dispute_not_found",
+            Self::FeeScheduleNotFound => "fee_schedule_not_found",
+            Self::SettlementBatchNotFound => "settlement_batch_not_found",
+            Self::VerificationNotFound => "verification_not_found",
+            Self::NegativeAmount => "negative_amount",
+            Self::InvalidCardNumber => "invalid_card_number",
+            Self::CardNumberAlreadyUsed => "card_number_already_used",
+            Self::PaymentNotApproved => "payment_not_approved",
+            Self::PaymentNotAwaitingReview => "payment_not_awaiting_review",
+            Self::RefundReasonRequired => "refund_reason_required",
+            Self::RefundAmountBelowMinimum => "refund_amount_below_minimum",
+            Self::PaymentTooOld => "payment_too_old",
+            Self::TooManyRefunds => "too_many_refunds",
+            Self::ExcessiveRefundAmount => "excessive_refund_amount",
+            Self::RefundNotAwaitingApproval => "refund_not_awaiting_approval",
+            Self::RefundInProgress => "refund_in_progress",
+            Self::NonPositiveDisputeAmount => "non_positive_dispute_amount",
+            Self::ExcessiveDisputeAmount => "excessive_dispute_amount",
+            Self::DisputeAlreadyDecided => "dispute_already_decided",
+            Self::FeePercentOutOfRange => "fee_percent_out_of_range",
+            Self::NegativeFeeAmount => "negative_fee_amount",
+            Self::FeeCapBelowMinimum => "fee_cap_below_minimum",
+            Self::RefundFailed => "refund_failed",
+            Self::ReviewFailed => "review_failed",
+            Self::VerificationFailed => "verification_failed",
+            Self::UnsupportedApiVersion => "unsupported_api_version",
+            Self::UnsupportedCurrency => "unsupported_currency",
+            Self::InternalError => "internal_error",
+        }
+    }
+
+    /// Short summary of the problem, the same for every occurrence.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::AuthenticationRequired | Self::RefundAuthenticationRequired => {
+                "Authentication required"
+            }
+            Self::SelfReview => "Self review",
+            Self::NotMerchantPrincipal | Self::NotAccountServicePrincipal => "Forbidden",
+            Self::PaymentNotFound
+            | Self::RefundNotFound
+            | Self::DisputeNotFound
+            | Self::FeeScheduleNotFound
+            | Self::SettlementBatchNotFound
+            | Self::VerificationNotFound => "Not found",
+            Self::NegativeAmount | Self::NonPositiveDisputeAmount => "Invalid amount",
+            Self::InvalidCardNumber => "Invalid card number",
+            Self::CardNumberAlreadyUsed => "Card number already used",
+            Self::PaymentNotApproved
+            | Self::PaymentNotAwaitingReview
+            | Self::RefundNotAwaitingApproval
+            | Self::RefundInProgress
+            | Self::DisputeAlreadyDecided => "Invalid state",
+            Self::RefundReasonRequired
+            | Self::RefundAmountBelowMinimum
+            | Self::PaymentTooOld
+            | Self::TooManyRefunds => "Refund policy violated",
+            Self::ExcessiveRefundAmount | Self::ExcessiveDisputeAmount => "Excessive amount",
+            Self::FeePercentOutOfRange | Self::NegativeFeeAmount | Self::FeeCapBelowMinimum => {
+                "Invalid fee schedule"
+            }
+            Self::RefundFailed | Self::ReviewFailed | Self::VerificationFailed => {
+                "Account service failed"
+            }
+            Self::UnsupportedApiVersion => "Unsupported API version",
+            Self::UnsupportedCurrency => "Unsupported currency",
+            Self::InternalError => "Internal error",
+        }
+    }
+
+    /// Human-readable explanation of this occurrence of the problem.
+    pub fn detail(&self) -> &'static str {
+        match self {
+            Self::AuthenticationRequired => "authentication required",
+            Self::RefundAuthenticationRequired => {
+                "authentication required for refunds above the approval threshold"
+            }
+            Self::SelfReview => "refund must be reviewed by another principal than its requester",
+            Self::NotMerchantPrincipal => "only the merchant or an admin can manage the merchant",
+            Self::NotAccountServicePrincipal => {
+                "only the account service or an admin can open and decide disputes"
+            }
+            Self::PaymentNotFound => "payment doesn't exist",
+            Self::RefundNotFound => "refund doesn't exist",
+            Self::DisputeNotFound => "dispute doesn't exist",
+            Self::FeeScheduleNotFound => "fee schedule doesn't exist",
+            Self::SettlementBatchNotFound => "settlement batch doesn't exist",
+            Self::VerificationNotFound => "verification doesn't exist",
+            Self::NegativeAmount => "Amount shouldn't be negative",
+            Self::InvalidCardNumber => "Bad Card Number format",
+            Self::CardNumberAlreadyUsed => "card_number already used",
+            Self::PaymentNotApproved => "payment isn't approved",
+            Self::PaymentNotAwaitingReview => "payment isn't awaiting review",
+            Self::RefundReasonRequired => "refund reason is required",
+            Self::RefundAmountBelowMinimum => "refund amount is below the minimum",
+            Self::PaymentTooOld => "payment is too old to be refunded",
+            Self::TooManyRefunds => "maximum number of refunds reached for payment",
+            Self::ExcessiveRefundAmount => "excessive refund amount requested",
+            Self::RefundNotAwaitingApproval => "refund isn't awaiting approval",
+            Self::RefundInProgress => {
+                "another refund or an account service call of the payment is in progress"
+            }
+            Self::NonPositiveDisputeAmount => "dispute amount must be positive",
+            Self::ExcessiveDisputeAmount => "excessive dispute amount requested",
+            Self::DisputeAlreadyDecided => "dispute was already decided",
+            Self::FeePercentOutOfRange => "percent_bps must be between 0 and 10000",
+            Self::NegativeFeeAmount => "fee amounts can't be negative",
+            Self::FeeCapBelowMinimum => "max_amount can't be below min_amount",
+            Self::RefundFailed => "account service failed to refund the payment",
+            Self::ReviewFailed => "account service failed to review the payment",
+            Self::VerificationFailed => "account service failed to verify the card",
+            Self::UnsupportedApiVersion => "Api-Version must be 1 or 2",
+            Self::UnsupportedCurrency => "amounts must be in EUR",
+            Self::InternalError => "internal error",
+        }
+    }
+
+    /// The request field at fault, for validation errors about a single field.
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            Self::NegativeAmount => Some("payment.amount"),
+            Self::InvalidCardNumber | Self::CardNumberAlreadyUsed => Some("payment.card_number"),
+            Self::UnsupportedCurrency => Some("payment.amount.currency"),
+            Self::RefundReasonRequired => Some("refund.reason"),
+            Self::RefundAmountBelowMinimum | Self::ExcessiveRefundAmount => Some("refund.amount"),
+            Self::NonPositiveDisputeAmount | Self::ExcessiveDisputeAmount => Some("dispute.amount"),
+            Self::FeePercentOutOfRange => Some("fee_schedule.percent_bps"),
+            Self::FeeCapBelowMinimum => Some("fee_schedule.max_amount"),
+            _ => None,
+        }
+    }
+}
+
+impl From<PolicyViolation> for ErrorCode {
+    fn from(violation: PolicyViolation) -> Self {
+        match violation {
+            PolicyViolation::MissingReason => Self::RefundReasonRequired,
+            PolicyViolation::AmountBelowMinimum => Self::RefundAmountBelowMinimum,
+            PolicyViolation::PaymentTooOld => Self::PaymentTooOld,
+            PolicyViolation::TooManyRefunds => Self::TooManyRefunds,
+        }
+    }
+}
+
+impl From<ScheduleViolation> for ErrorCode {
+    fn from(violation: ScheduleViolation) -> Self {
+        match violation {
+            ScheduleViolation::PercentOutOfRange => Self::FeePercentOutOfRange,
+            ScheduleViolation::NegativeAmount => Self::NegativeFeeAmount,
+            ScheduleViolation::CapBelowMinimum => Self::FeeCapBelowMinimum,
+        }
+    }
+}
+
+/// Error of an API handler, answered with an `application/problem+json`
+/// `ProblemResponseBody` (RFC 7807).
+#[derive(Debug)]
+pub enum ApiError {
+    /// The requested resource doesn't exist.
+    NotFound(ErrorCode),
+    /// The request is invalid, answered with the given status (usually 422).
+    Validation(StatusCode, ErrorCode),
+    /// The request must be authenticated.
+    Unauthorized(ErrorCode),
+    /// The authenticated principal isn't allowed to make the request.
+    Forbidden(ErrorCode),
+    /// The request conflicts with the current state of the resource.
+    Conflict(ErrorCode),
+    /// The account service failed, with the given error: it's logged, and
+    /// answered as a bad gateway.
+    Upstream(ErrorCode, PaymentError),
+    /// Anything else, e.g. the database failed: the details are logged, but
+    /// not returned.
+    Internal(String),
+}
+
+impl ApiError {
+    /// Maps a failure to fetch a resource: `RowNotFound` to `NotFound`, other
+    /// errors to `Internal`.
+    pub fn not_found(code: ErrorCode) -> impl FnOnce(sqlx::Error) -> Self {
+        move |error| match error {
+            sqlx::Error::RowNotFound => Self::NotFound(code),
+            error => error.into(),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Validation(status, _) => *status,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Upstream(..) => StatusCode::BAD_GATEWAY,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(code)
+            | Self::Validation(_, code)
+            | Self::Unauthorized(code)
+            | Self::Forbidden(code)
+            | Self::Conflict(code)
+            | Self::Upstream(code, _) => *code,
+            Self::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(error: sqlx::Error) -> Self {
+        Self::Internal(error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let code = self.code();
+        match &self {
+            Self::Internal(error) => tracing::error!(%error, "internal error"),
+            Self::Upstream(_, error) => tracing::warn!(%error, "{}", code.detail()),
+            _ => {}
+        }
+
+        (
+            self.status(),
+            [(CONTENT_TYPE, PROBLEM_JSON)],
+            Json(ProblemResponseBody::new(self.status(), code)),
+        )
+            .into_response()
+    }
+}
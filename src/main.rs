@@ -1,50 +1,168 @@
-use std::{net::SocketAddr, time::Duration};
+use std::path::Path;
 
+use clap::Parser;
 use dotenvy::dotenv;
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, PgPool};
 
+use crate::bank::accounts::AccountService;
 use crate::bank_web::BankWeb;
+use crate::config::{Cli, Command, Config};
+use crate::shutdown::Shutdown;
 
 mod bank;
 mod bank_web;
+mod config;
 mod errors;
+mod metrics;
+mod shutdown;
 
-pub async fn pg_pool() -> Result<PgPool, sqlx::Error> {
-    dotenv().expect("failed to load .env");
+/// The database migrations, embedded in the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
 
+pub async fn connect(database: &config::Database) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new()
-        .max_connections(5)
-        .acquire_timeout(Duration::from_secs(1))
-        .connect(&std::env::var("DATABASE_URL").expect("DATABASE_URL must be in environment"))
+        .max_connections(database.max_connections)
+        .acquire_timeout(database.acquire_timeout())
+        .connect(&database.url)
         .await
 }
 
+/// Connects to the database at `DATABASE_URL`, with the default pool settings.
+#[cfg(test)]
+pub async fn pg_pool() -> Result<PgPool, sqlx::Error> {
+    dotenv().ok();
+
+    connect(&config::Database {
+        url: std::env::var("DATABASE_URL").expect("DATABASE_URL must be in environment"),
+        ..config::Database::default()
+    })
+    .await
+}
+
 #[tokio::main]
 async fn main() {
-    dotenv().expect("failed to load .env");
+    // the environment may be set without a .env file
+    dotenv().ok();
+
+    let cli = Cli::parse();
+    let config = Config::load(&cli).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(2);
+    });
 
-    init_tracing();
+    init_tracing(&config.tracing);
 
-    let pool = pg_pool().await.expect("failed to connect to postgres");
+    let pool = connect(&config.database)
+        .await
+        .expect("failed to connect to postgres");
 
-    sqlx::migrate!()
+    MIGRATOR
         .run(&pool)
         .await
         .expect("failed to run sqlx migrations");
 
-    let account_service = bank::accounts::DummyService::default();
-    let router = BankWeb::new(pool, account_service).into_router();
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], 4000));
+    let account_service = match config.account_service.kind {
+        config::Kind::Dummy => bank::accounts::DummyService::default(),
+    };
+    let account_service =
+        bank::accounts::Timeout::new(account_service, config.account_service.timeout());
+
+    if let Some(Command::Reconcile { path }) = &cli.command {
+        reconcile(&pool, &account_service, path).await;
+        return;
+    }
+
+    let shutdown = Shutdown::new();
+    tokio::spawn(shutdown.clone().listen());
+
+    let settlements = tokio::spawn(bank::settlements::run_daily(
+        pool.clone(),
+        config.settlements.cut_off(),
+        shutdown.clone(),
+    ));
+
+    let reviews = tokio::spawn(bank::reviews::run_expiry(
+        pool.clone(),
+        account_service.clone(),
+        shutdown.clone(),
+    ));
+
+    let router = BankWeb::new(pool, account_service)
+        .with_refund_policy(config.refunds.policy())
+        .with_api_tokens(config.auth.tokens())
+        .with_risk_engine(config.risk.engine())
+        .with_review_period(config.reviews.period())
+        .with_spend_limits(config.spending.limits())
+        .with_shutdown(shutdown.clone())
+        .into_router();
+
+    let addr = config.server.bind;
     tracing::info!("listening on http://{}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(router.into_make_service())
+    // on shutdown, stop accepting connections, then wait for the requests and
+    // workers in flight, up to the deadline
+    let drained = async {
+        axum::Server::bind(&addr)
+            .serve(router.into_make_service())
+            .with_graceful_shutdown(shutdown.triggered())
+            .await
+            .expect("failed to serve");
+        shutdown.drain().await;
+        let _ = tokio::join!(settlements, reviews);
+    };
+    tokio::select! {
+        () = drained => tracing::info!("shut down"),
+        () = shutdown.deadline(config.server.shutdown_timeout()) => {
+            tracing::warn!("shutdown deadline elapsed, abandoning the work in flight");
+        }
+    }
+
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+async fn reconcile<T: AccountService>(pool: &PgPool, account_service: &T, path: &Path) {
+    let entries = bank::reconciliation::read_export(path).expect("failed to read ledger export");
+    // the export tells whether the calls which timed out went through
+    let resolved = bank::timeouts::resolve(pool, account_service, &entries)
+        .await
+        .expect("failed to resolve timed-out calls");
+    println!("resolved {resolved} timed-out account service calls");
+
+    let discrepancies = bank::reconciliation::reconcile(pool, &entries)
         .await
-        .expect("failed to serve");
+        .expect("failed to reconcile ledger export");
+    let run_id = bank::reconciliation::record_run(
+        pool,
+        &path.display().to_string(),
+        entries.len(),
+        &discrepancies,
+    )
+    .await
+    .expect("failed to record discrepancies");
+
+    for discrepancy in &discrepancies {
+        println!(
+            "{:?}\thold {}\t{}\taccount {}\texpected {:?}\tactual {:?}",
+            discrepancy.kind,
+            discrepancy.hold_id,
+            discrepancy.operation,
+            discrepancy.account,
+            discrepancy.expected_amount,
+            discrepancy.actual_amount,
+        );
+    }
+    println!(
+        "reconciliation {run_id}: {} entries, {} discrepancies",
+        entries.len(),
+        discrepancies.len()
+    );
+
+    if !discrepancies.is_empty() {
+        std::process::exit(1);
+    }
 }
 
-pub fn init_tracing() {
+pub fn init_tracing(config: &config::Tracing) {
     use opentelemetry_otlp::WithExportConfig;
     use tracing_subscriber::prelude::*;
 
@@ -56,7 +174,7 @@ pub fn init_tracing() {
 
     let otel_exporter = opentelemetry_otlp::new_exporter()
         .tonic()
-        .with_endpoint("http://localhost:4317");
+        .with_endpoint(&config.otlp_endpoint);
 
     let otel_resource = opentelemetry::sdk::Resource::new([opentelemetry::KeyValue::new(
         "service.name",
@@ -0,0 +1,256 @@
+use std::{
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::bank::{
+    payments::{Reversal, Status},
+    reconciliation::Operation,
+    refunds,
+};
+
+/// Prometheus metrics of the service, served by `GET /metrics`.
+///
+/// They're process-wide, so that the domain layer can record them without
+/// threading them through every function.
+pub struct Metrics {
+    registry: Registry,
+    payments: IntCounterVec,
+    refunds: IntCounterVec,
+    refunded_amount: IntCounter,
+    http_requests: HistogramVec,
+    account_service_calls: HistogramVec,
+    account_service_errors: IntCounterVec,
+    db_pool_connections: IntGaugeVec,
+}
+
+/// Latency buckets, in seconds, from 1ms to 10s.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Label of a value, as it's serialized in API responses.
+fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(label)) => label,
+        _ => String::new(),
+    }
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let payments = IntCounterVec::new(
+            Opts::new(
+                "payments_total",
+                "Payments which reached a final status, by status and decline code or failure reason",
+            ),
+            &["status", "reason"],
+        )
+        .unwrap();
+        let refunds = IntCounterVec::new(
+            Opts::new("refunds_total", "Refunds, by the status they reached"),
+            &["status"],
+        )
+        .unwrap();
+        let refunded_amount = IntCounter::new(
+            "refunded_amount_total",
+            "Amount given back by succeeded refunds, in cents",
+        )
+        .unwrap();
+        let http_requests = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Latency of the API's requests, by route",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let account_service_calls = HistogramVec::new(
+            HistogramOpts::new(
+                "account_service_call_duration_seconds",
+                "Latency of the account service calls, by operation",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["operation"],
+        )
+        .unwrap();
+        let account_service_errors = IntCounterVec::new(
+            Opts::new(
+                "account_service_call_errors_total",
+                "Account service calls which returned an error, by operation",
+            ),
+            &["operation"],
+        )
+        .unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Connections of the database pool, by state",
+            ),
+            &["state"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(payments.clone())).unwrap();
+        registry.register(Box::new(refunds.clone())).unwrap();
+        registry
+            .register(Box::new(refunded_amount.clone()))
+            .unwrap();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(account_service_calls.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(account_service_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_pool_connections.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            payments,
+            refunds,
+            refunded_amount,
+            http_requests,
+            account_service_calls,
+            account_service_errors,
+            db_pool_connections,
+        }
+    }
+
+    /// Counts a payment which was approved.
+    pub fn payment_approved(&self) {
+        self.payments
+            .with_label_values(&[&label(&Status::Approved), ""])
+            .inc();
+    }
+
+    /// Counts a payment which was declined or failed.
+    pub fn payment_reversed(&self, reversal: Reversal) {
+        let reason = match reversal {
+            Reversal::Declined(code) => label(&code),
+            Reversal::Failed(reason) => label(&reason),
+        };
+        self.payments
+            .with_label_values(&[&label(&reversal.status()), &reason])
+            .inc();
+    }
+
+    /// Counts a refund which reached `status`, and the amount given back if
+    /// it succeeded.
+    pub fn refund(&self, status: refunds::Status, amount: i32) {
+        self.refunds.with_label_values(&[&label(&status)]).inc();
+        if status == refunds::Status::Succeeded {
+            self.refunded_amount
+                .inc_by(u64::try_from(amount).unwrap_or_default());
+        }
+    }
+
+    /// Counts a refund the account service failed to give back.
+    pub fn refund_failed(&self) {
+        self.refunds.with_label_values(&["failed"]).inc();
+    }
+
+    pub fn http_request(&self, method: &str, route: &str, status: u16, latency: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .observe(latency.as_secs_f64());
+    }
+
+    /// Samples the connections of the database pool.
+    pub fn observe_pool(&self, pool: &PgPool) {
+        let size = i64::from(pool.size());
+        let idle = i64::try_from(pool.num_idle()).unwrap_or(i64::MAX);
+        self.db_pool_connections
+            .with_label_values(&["idle"])
+            .set(idle);
+        self.db_pool_connections
+            .with_label_values(&["in_use"])
+            .set(size - idle);
+    }
+
+    /// Renders the metrics in Prometheus' text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("failed to encode metrics");
+        String::from_utf8(buffer).expect("metrics aren't UTF-8")
+    }
+}
+
+/// Makes an account service call, timing it. Returns its result, and how long
+/// it took.
+pub async fn account_service_call<R>(
+    operation: Operation,
+    future: impl Future<Output = Result<R, String>>,
+) -> (Result<R, String>, Duration) {
+    let started_at = Instant::now();
+    let result = future.await;
+    let latency = started_at.elapsed();
+
+    let operation = label(&operation);
+    metrics()
+        .account_service_calls
+        .with_label_values(&[&operation])
+        .observe(latency.as_secs_f64());
+    if result.is_err() {
+        metrics()
+            .account_service_errors
+            .with_label_values(&[&operation])
+            .inc();
+    }
+
+    (result, latency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::payments::DeclineCode;
+
+    #[test]
+    fn test_payment_reversed() {
+        let reversal = Reversal::Declined(DeclineCode::InsufficientFunds);
+        let counter = metrics()
+            .payments
+            .with_label_values(&["declined", "insufficient_funds"]);
+        let before = counter.get();
+
+        metrics().payment_reversed(reversal);
+        assert!(counter.get() > before);
+        assert!(metrics()
+            .render()
+            .contains(r#"payments_total{reason="insufficient_funds",status="declined"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_account_service_call() {
+        let errors = metrics()
+            .account_service_errors
+            .with_label_values(&["release_hold"]);
+        let before = errors.get();
+
+        let (result, _) =
+            account_service_call::<()>(Operation::ReleaseHold, async { Err("error".into()) }).await;
+        assert!(result.is_err());
+        assert!(errors.get() > before);
+    }
+}
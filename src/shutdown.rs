@@ -0,0 +1,139 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+/// Graceful shutdown of the service, on SIGTERM or SIGINT.
+///
+/// Once it's triggered, the server stops accepting connections and the
+/// background workers stop after their current pass. The work which mustn't
+/// be interrupted, e.g. a payment between placing its hold and approving it,
+/// is guarded so that the process waits for it before exiting.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    /// Read by every guarded future, so that writing waits for all of them.
+    in_flight: Arc<RwLock<()>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Triggers the shutdown when the process receives SIGTERM or SIGINT.
+    pub async fn listen(self) {
+        signal().await;
+        tracing::info!("shutting down");
+        self.trigger();
+    }
+
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    /// Resolves once the shutdown is triggered.
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let token = self.token.clone();
+        async move { token.cancelled().await }
+    }
+
+    /// Resolves `timeout` after the shutdown is triggered.
+    pub async fn deadline(&self, timeout: Duration) {
+        self.triggered().await;
+        tokio::time::sleep(timeout).await;
+    }
+
+    /// Runs `future` to completion, even if its caller is dropped (e.g. when
+    /// the client disconnects), and holds the shutdown until it's done.
+    pub async fn guard<F>(&self, future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let in_flight = self.in_flight.clone().read_owned().await;
+        let task = tokio::spawn(async move {
+            let output = future.await;
+            drop(in_flight);
+            output
+        });
+
+        match task.await {
+            Ok(output) => output,
+            Err(error) => std::panic::resume_unwind(error.into_panic()),
+        }
+    }
+
+    /// Resolves once every guarded future is done.
+    pub async fn drain(&self) {
+        let _ = self.in_flight.write().await;
+    }
+}
+
+async fn signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = interrupt => {}
+        () = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_guard_outlives_its_caller() {
+        let shutdown = Shutdown::new();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let guarded = shutdown.guard({
+            let done = done.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                done.store(true, Ordering::SeqCst);
+            }
+        });
+        // the caller gives up on the guarded future once it started
+        let _ = tokio::time::timeout(Duration::from_millis(10), guarded).await;
+        assert!(!done.load(Ordering::SeqCst));
+
+        shutdown.trigger();
+        shutdown.drain().await;
+        assert!(done.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let shutdown = Shutdown::new();
+        let deadline = shutdown.deadline(Duration::ZERO);
+        tokio::pin!(deadline);
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), &mut deadline)
+                .await
+                .is_err(),
+            "the deadline only runs once the shutdown is triggered"
+        );
+
+        shutdown.trigger();
+        deadline.await;
+    }
+}